@@ -1,4 +1,5 @@
 fn main() {
     std::fs::create_dir_all("../build").expect("Directory creation failed");
-    tauri_build::build()
+    #[cfg(feature = "app")]
+    tauri_build::build();
 }
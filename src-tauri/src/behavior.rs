@@ -9,3 +9,6 @@ pub use self::support_behavior::SupportBehavior;
 
 mod shout_behavior;
 pub use self::shout_behavior::ShoutBehavior;
+
+#[cfg(test)]
+mod behavior_tests;
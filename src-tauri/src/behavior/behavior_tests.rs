@@ -0,0 +1,455 @@
+//! Behaviors driven without a client window, checking the input they send.
+
+use std::{sync::Arc, time::Duration};
+
+use image::{Rgba, RgbaImage};
+use serde_json::{json, Value};
+use slog::Logger;
+
+use crate::{
+    behavior::{Behavior, FarmingBehavior, ShoutBehavior, SupportBehavior},
+    image_analyzer::ImageAnalyzer,
+    ipc::{BotConfig, FrontendInfo},
+    layout::HudLayout,
+    movement::{ActionDuration, Movement, MovementAccessor, MovementDirection},
+    platform::{InputAction, KeyMode, RecordingInputSink},
+    utils::{ManualClock, RngSource},
+};
+
+/// Everything a behavior needs, with recorded input and a manual clock.
+struct Harness {
+    logger: Logger,
+    input: Arc<RecordingInputSink>,
+    clock: Arc<ManualClock>,
+    movement: MovementAccessor,
+}
+
+impl Harness {
+    fn new() -> Self {
+        let input = Arc::new(RecordingInputSink::new());
+        let clock = Arc::new(ManualClock::new());
+        Self {
+            logger: Logger::root(slog::Discard, slog::o!()),
+            movement: MovementAccessor::new(input.clone(), clock.clone(), &RngSource::new(0)),
+            input,
+            clock,
+        }
+    }
+
+    fn behavior<'a, B: Behavior<'a>>(&'a self) -> B {
+        B::new(
+            &self.logger,
+            &self.movement,
+            &*self.input,
+            &*self.clock,
+            &RngSource::new(0),
+        )
+    }
+}
+
+/// Slot bars with `slots` placed as `(slot_bar_index, slot_index, slot)`.
+fn slot_bars(slots: &[(usize, usize, Value)]) -> Value {
+    let unused = json!({ "slot_type": "Unused", "slot_enabled": false });
+    let mut bars = vec![vec![unused; 10]; 9];
+    for (bar, index, slot) in slots {
+        bars[*bar][*index] = slot.clone();
+    }
+    Value::Array(
+        bars.into_iter()
+            .map(|slots| json!({ "slots": slots }))
+            .collect(),
+    )
+}
+
+/// A running bot in `mode`, with `config` as the config of the mode named `key`.
+fn bot_config(mode: &str, key: &str, config: Value) -> BotConfig {
+    let mut bot_config = json!({
+        "change_id": 0,
+        "is_running": true,
+        "mode": mode,
+        "farming_config": {},
+        "support_config": {},
+        "shout_config": {},
+    });
+    bot_config[key] = config;
+    serde_json::from_value(bot_config).unwrap()
+}
+
+fn food_slot() -> Value {
+    json!({
+        "slot_type": "Food",
+        "slot_threshold": 50,
+        "slot_cooldown": 60000,
+        "slot_enabled": true,
+    })
+}
+
+/// The default HUD layout with more elements, e.g. the minimap.
+fn layout(elements: Value) -> HudLayout {
+    let mut layout = serde_json::to_value(HudLayout::default()).unwrap();
+    for (key, rect) in elements.as_object().unwrap() {
+        layout[key] = rect.clone();
+    }
+    serde_json::from_value(layout).unwrap()
+}
+
+#[test]
+fn test_farming_restores_hp() {
+    let harness = Harness::new();
+    let input = &harness.input;
+    let mut behavior: FarmingBehavior = harness.behavior();
+    let config = bot_config(
+        "Farming",
+        "farming_config",
+        json!({ "slot_bars": slot_bars(&[(0, 1, food_slot())]) }),
+    );
+    let mut frontend_info = FrontendInfo::default();
+    let mut analyzer = ImageAnalyzer::headless();
+
+    // Healthy
+    analyzer.client_stats.hp.value = 80;
+    behavior.run_iteration(&mut frontend_info, &config, &mut analyzer);
+    assert!(!input.take().contains(&InputAction::Slot(0, 1)));
+
+    // Hurt
+    analyzer.client_stats.hp.value = 30;
+    behavior.run_iteration(&mut frontend_info, &config, &mut analyzer);
+    assert!(input.take().contains(&InputAction::Slot(0, 1)));
+
+    // Food is on cooldown
+    behavior.run_iteration(&mut frontend_info, &config, &mut analyzer);
+    assert!(!input.take().contains(&InputAction::Slot(0, 1)));
+
+    harness.clock.advance(Duration::from_secs(60));
+    behavior.run_iteration(&mut frontend_info, &config, &mut analyzer);
+    assert!(input.take().contains(&InputAction::Slot(0, 1)));
+}
+
+#[test]
+fn test_farming_mobs_timeout() {
+    let harness = Harness::new();
+    let input = &harness.input;
+    let mut behavior: FarmingBehavior = harness.behavior();
+    let config = bot_config(
+        "Farming",
+        "farming_config",
+        json!({ "mobs_timeout": 60000 }),
+    );
+    let mut frontend_info = FrontendInfo::default();
+    let mut analyzer = ImageAnalyzer::headless();
+    analyzer.client_stats.hp.value = 100;
+
+    // Searching turns around for a while
+    for _ in 0..10 {
+        behavior.run_iteration(&mut frontend_info, &config, &mut analyzer);
+    }
+    assert!(!input.take().contains(&InputAction::Exit));
+
+    harness.clock.advance(Duration::from_secs(60));
+    for _ in 0..2 {
+        behavior.run_iteration(&mut frontend_info, &config, &mut analyzer);
+    }
+    assert!(input.take().contains(&InputAction::Exit));
+}
+
+#[test]
+fn test_support_restores_hp() {
+    let harness = Harness::new();
+    let input = &harness.input;
+    let mut behavior: SupportBehavior = harness.behavior();
+    let config = bot_config(
+        "Support",
+        "support_config",
+        json!({ "slot_bars": slot_bars(&[(2, 0, food_slot())]) }),
+    );
+    let mut frontend_info = FrontendInfo::default();
+    let mut analyzer = ImageAnalyzer::headless();
+
+    analyzer.client_stats.hp.value = 30;
+    behavior.run_iteration(&mut frontend_info, &config, &mut analyzer);
+    assert_eq!(input.take(), vec![InputAction::Slot(2, 0)]);
+}
+
+#[test]
+fn test_shout_messages() {
+    let harness = Harness::new();
+    let input = &harness.input;
+    let mut behavior: ShoutBehavior = harness.behavior();
+    let config = bot_config(
+        "AutoShout",
+        "shout_config",
+        json!({ "shout_interval": 30000, "shout_messages": ["WTS stuff", "  "] }),
+    );
+    let mut frontend_info = FrontendInfo::default();
+    let mut analyzer = ImageAnalyzer::headless();
+    behavior.start(&config);
+
+    // Waits for the interval first
+    behavior.run_iteration(&mut frontend_info, &config, &mut analyzer);
+    assert_eq!(input.take(), vec![]);
+
+    let press = |key: &str| InputAction::Key(key.to_string(), KeyMode::Press);
+    harness.clock.advance(Duration::from_secs(30));
+    behavior.run_iteration(&mut frontend_info, &config, &mut analyzer);
+    assert_eq!(
+        input.take(),
+        vec![
+            press("Enter"),
+            InputAction::Message("WTS stuff".to_string()),
+            press("Enter"),
+            press("Escape"),
+        ]
+    );
+
+    // Blank messages are skipped
+    harness.clock.advance(Duration::from_secs(30));
+    behavior.run_iteration(&mut frontend_info, &config, &mut analyzer);
+    assert_eq!(input.take(), vec![]);
+}
+
+#[test]
+fn test_farming_follows_minimap() {
+    let harness = Harness::new();
+    let input = &harness.input;
+    let mut behavior: FarmingBehavior = harness.behavior();
+    let config = bot_config("Farming", "farming_config", json!({ "use_minimap": true }));
+    let mut frontend_info = FrontendInfo::default();
+
+    // The player arrow points north in the middle of the minimap, a mob is to the west
+    let mut image = RgbaImage::from_pixel(800, 600, Rgba([0, 0, 0, 255]));
+    for y in 84..=96 {
+        let half_width = (y - 84) / 3;
+        for x in (730 - half_width)..=(730 + half_width) {
+            image.put_pixel(x, y, Rgba([255, 216, 0, 255]));
+        }
+    }
+    for x in 690..693 {
+        image.put_pixel(x, 92, Rgba([255, 48, 48, 255]));
+    }
+    let mut analyzer = ImageAnalyzer::from_image(image);
+    analyzer.set_layout(layout(json!({
+        "minimap": { "anchor": "TopRight", "x": 670, "y": 30, "w": 120, "h": 120 },
+    })));
+    analyzer.client_stats.hp.value = 100;
+
+    // Turns left towards the mob instead of rotating right blindly
+    for _ in 0..2 {
+        behavior.run_iteration(&mut frontend_info, &config, &mut analyzer);
+    }
+    let actions = input.take();
+    let hold = |key: &str| InputAction::Key(key.to_string(), KeyMode::Hold);
+    assert!(actions.contains(&hold("Left")));
+    assert!(actions.contains(&hold("W")));
+    assert!(!actions.contains(&hold("Right")));
+}
+
+#[test]
+fn test_farming_returns_to_anchor() {
+    let harness = Harness::new();
+    let input = &harness.input;
+    let mut behavior: FarmingBehavior = harness.behavior();
+    let config = bot_config("Farming", "farming_config", json!({ "leash_radius": 10 }));
+    let mut frontend_info = FrontendInfo::default();
+    let mut analyzer = ImageAnalyzer::headless();
+    analyzer.client_stats.hp.value = 100;
+    behavior.start(&config);
+
+    // Drift 25m away from where farming started
+    let drift = [Movement::Move(
+        MovementDirection::Forward,
+        ActionDuration::Fixed(5000),
+    )];
+    harness
+        .movement
+        .schedule(|coordinator| coordinator.play(drift.clone()));
+    input.take();
+
+    // Turns around and walks back before searching again
+    for _ in 0..12 {
+        behavior.run_iteration(&mut frontend_info, &config, &mut analyzer);
+    }
+    let distance = harness.movement.odometer().distance();
+    assert!(distance <= 5.0, "still {distance}m away");
+    assert!(input
+        .take()
+        .contains(&InputAction::Key("W".to_string(), KeyMode::Hold)));
+}
+
+#[test]
+fn test_farming_walks_patrol_route() {
+    let harness = Harness::new();
+    let input = &harness.input;
+    let mut behavior: FarmingBehavior = harness.behavior();
+    let config = bot_config(
+        "Farming",
+        "farming_config",
+        json!({ "patrol_route": ["W 1000 | 2000", " ", "Jump, Right 500"] }),
+    );
+    behavior.update(&config);
+    let mut frontend_info = FrontendInfo::default();
+    let mut analyzer = ImageAnalyzer::headless();
+    analyzer.client_stats.hp.value = 100;
+
+    let hold = |key: &str| InputAction::Key(key.to_string(), KeyMode::Hold);
+    for _ in 0..6 {
+        behavior.run_iteration(&mut frontend_info, &config, &mut analyzer);
+    }
+    // Walks to the first waypoint, then waits there
+    assert_eq!(
+        input
+            .take()
+            .into_iter()
+            .filter(|action| matches!(action, InputAction::Key(_, KeyMode::Hold)))
+            .collect::<Vec<_>>(),
+        vec![hold("W")]
+    );
+
+    harness.clock.advance(Duration::from_secs(2));
+    for _ in 0..4 {
+        behavior.run_iteration(&mut frontend_info, &config, &mut analyzer);
+    }
+    let actions = input.take();
+    assert!(actions.contains(&hold("Space")));
+    assert!(actions.contains(&hold("Right")));
+    assert!(!actions.contains(&hold("W")));
+}
+
+#[test]
+fn test_farming_flies_on_mobs_timeout() {
+    let harness = Harness::new();
+    let input = &harness.input;
+    let mut behavior: FarmingBehavior = harness.behavior();
+    let flying_slot = json!({ "slot_type": "Flying", "slot_enabled": true });
+    let config = bot_config(
+        "Farming",
+        "farming_config",
+        json!({
+            "slot_bars": slot_bars(&[(0, 9, flying_slot)]),
+            "mobs_timeout": 60000,
+            "fly_on_mobs_timeout": true,
+            "flight_duration": 2000,
+        }),
+    );
+    let mut frontend_info = FrontendInfo::default();
+    let mut analyzer = ImageAnalyzer::headless();
+    analyzer.client_stats.hp.value = 100;
+    let mut run = |iterations: usize| {
+        for _ in 0..iterations {
+            behavior.run_iteration(&mut frontend_info, &config, &mut analyzer);
+        }
+    };
+    let slot_uses = |actions: &[InputAction]| {
+        actions
+            .iter()
+            .filter(|action| **action == InputAction::Slot(0, 9))
+            .count()
+    };
+
+    // Mounts instead of exiting
+    run(2);
+    harness.clock.advance(Duration::from_secs(60));
+    run(2);
+    let actions = input.take();
+    assert_eq!(slot_uses(&actions), 1);
+    assert!(!actions.contains(&InputAction::Exit));
+
+    // Flies straight ahead, then dismounts
+    harness.clock.advance(Duration::from_secs(4));
+    run(8);
+    let actions = input.take();
+    assert!(actions.contains(&InputAction::Key("W".to_string(), KeyMode::Hold)));
+    assert_eq!(slot_uses(&actions), 1);
+    assert!(harness.movement.odometer().distance() >= 30.0);
+
+    // Lands and searches the new area, leashed to it
+    harness.clock.advance(Duration::from_secs(4));
+    run(2);
+    assert_eq!(harness.movement.odometer().distance(), 0.0);
+    assert_eq!(slot_uses(&input.take()), 0);
+}
+
+#[test]
+fn test_farming_gives_up_flying() {
+    let harness = Harness::new();
+    let input = &harness.input;
+    let mut behavior: FarmingBehavior = harness.behavior();
+    let flying_slot = json!({ "slot_type": "Flying", "slot_enabled": true });
+    let config = bot_config(
+        "Farming",
+        "farming_config",
+        json!({
+            "slot_bars": slot_bars(&[(0, 9, flying_slot)]),
+            "mobs_timeout": 60000,
+            "fly_on_mobs_timeout": true,
+        }),
+    );
+    let mut frontend_info = FrontendInfo::default();
+
+    // The layout has a flight gauge which never shows
+    let mut analyzer =
+        ImageAnalyzer::from_image(RgbaImage::from_pixel(800, 600, Rgba([0, 0, 0, 255])));
+    analyzer.set_layout(layout(json!({
+        "flight_gauge": { "anchor": "TopRight", "x": 670, "y": 155, "w": 120, "h": 8 },
+    })));
+    analyzer.client_stats.hp.value = 100;
+
+    let mut run = |iterations: usize| {
+        for _ in 0..iterations {
+            behavior.run_iteration(&mut frontend_info, &config, &mut analyzer);
+        }
+    };
+    run(2);
+    harness.clock.advance(Duration::from_secs(61));
+    run(2);
+
+    // Mounting is tried a few times, then the bot farms where it is
+    for _ in 0..3 {
+        harness.clock.advance(Duration::from_secs(11));
+        run(1);
+    }
+    let actions = input.take();
+    let slot_uses = actions
+        .iter()
+        .filter(|action| **action == InputAction::Slot(0, 9))
+        .count();
+    assert_eq!(slot_uses, 3);
+    assert!(!actions.contains(&InputAction::Exit));
+
+    // The next time out disconnects like without a flying slot
+    run(2);
+    harness.clock.advance(Duration::from_secs(61));
+    run(2);
+    let actions = input.take();
+    assert!(!actions.contains(&InputAction::Slot(0, 9)));
+    assert!(actions.contains(&InputAction::Exit));
+}
+
+#[test]
+fn test_farming_gets_unstuck() {
+    let harness = Harness::new();
+    let input = &harness.input;
+    let mut behavior: FarmingBehavior = harness.behavior();
+    let config = bot_config(
+        "Farming",
+        "farming_config",
+        json!({ "patrol_route": ["W 1000"] }),
+    );
+    behavior.update(&config);
+    let mut frontend_info = FrontendInfo::default();
+
+    // Walking on, while the scene never changes
+    let image = RgbaImage::from_fn(800, 600, |x, y| {
+        Rgba([(x % 256) as u8, (y % 256) as u8, 0, 255])
+    });
+    let mut analyzer = ImageAnalyzer::from_image(image);
+    analyzer.client_stats.hp.value = 100;
+    for _ in 0..20 {
+        behavior.run_iteration(&mut frontend_info, &config, &mut analyzer);
+    }
+
+    assert!(input
+        .take()
+        .contains(&InputAction::Key("S".to_string(), KeyMode::Hold)));
+    let info = serde_json::to_value(frontend_info).unwrap();
+    assert!(info["stuck_count"].as_u64().unwrap() >= 1);
+}
@@ -1,21 +1,78 @@
 use std::time::{ Duration, Instant };
 
-use rand::prelude::SliceRandom;
+use rand::{ prelude::SliceRandom, rngs::StdRng };
 use slog::Logger;
-use tauri::{ Manager, Window };
 
 use super::Behavior;
 use crate::{
-    data::{ AliveState, Bounds, MobType, Point, Target, TargetType },
-    image_analyzer::ImageAnalyzer,
-    ipc::{ BotConfig, FarmingConfig, FrontendInfo, SlotType },
-    movement::MovementAccessor,
-    platform::{ eval_mob_click, send_slot_eval },
+    data::{ AliveState, Bounds, MinimapDotKind, MobType, Point, Target, TargetType },
+    debug_frames::FrameAnnotations,
+    image_analyzer::{ ImageAnalyzer, MotionEstimator },
+    ipc::{
+        BotConfig,
+        FarmingConfig,
+        FrontendInfo,
+        MobPolicy,
+        MobPolicyStats,
+        PatrolStep,
+        SlotType,
+        StatValue,
+        Waypoint,
+        WaypointAction,
+    },
+    movement::{ Movement, MovementAccessor, MovementDirection },
+    platform::InputSink,
     play,
-    utils::DateTime,
+    utils::{ Clock, DateTime, RngSource, RngStream },
 };
 
 const MAX_DISTANCE_FOR_AOE: i32 = 75;
+/// Distance to the player at which avoided mobs make the character move away
+const MAX_DISTANCE_FOR_AVOIDANCE: i32 = 250;
+/// Distance in pixels within which a mob counts as the one seen on the last scan
+const SAME_MOB_DISTANCE: u32 = 50;
+/// Share of the leash radius the character walks back to before searching again
+const LEASH_RETURN_RATIO: f32 = 0.5;
+/// Rough meters walked per pixel between the player and a clicked mob
+const METERS_PER_SCREEN_PIXEL: f32 = 0.04;
+/// Distance in meters at which a minimap target of the patrol route counts as reached
+const WAYPOINT_ARRIVAL_RADIUS: f32 = 3.0;
+/// Distance in meters at which the destination of a flight counts as reached
+const FLIGHT_ARRIVAL_RADIUS: f32 = 10.0;
+/// Time a board or broom takes to (un)mount, used when the HUD can't tell
+const MOUNT_DURATION: u128 = 3000;
+/// Time after which (un)mounting is tried again
+const MOUNT_TIMEOUT: u128 = 10000;
+/// Times (un)mounting is tried again before giving up on flying
+const MAX_MOUNT_RETRIES: u32 = 2;
+
+/// Mobs of a class handled by a policy, each one counted once while it stays in view.
+#[derive(Debug, Default)]
+struct MobPolicyTracker {
+    stats: MobPolicyStats,
+    /// Mobs of the class on the last scan
+    last_seen: Vec<Bounds>,
+}
+
+impl MobPolicyTracker {
+    /// Record the mobs of a scan and return how many weren't in view on the last one.
+    fn track(&mut self, mobs: &[Target]) -> u32 {
+        let is_new = |mob: &&Target| {
+            let center = mob.bounds.center();
+            !self.last_seen.iter().any(|seen| {
+                let seen = seen.center();
+                seen.x.abs_diff(center.x).max(seen.y.abs_diff(center.y)) <= SAME_MOB_DISTANCE
+            })
+        };
+        let new_mobs = mobs.iter().filter(is_new).count() as u32;
+        self.last_seen = mobs
+            .iter()
+            .map(|mob| mob.bounds)
+            .collect();
+        self.stats.seen = mobs.len() as u32;
+        new_mobs
+    }
+}
 
 #[derive(Debug, Clone, Copy)]
 enum State {
@@ -25,13 +82,42 @@ enum State {
     VerifyTarget(Target),
     Attacking(Target),
     AfterEnemyKill(Target),
+    /// Walking back towards where farming started
+    ReturningToAnchor,
+    /// Relocating on a board or broom
+    Flying(FlightStage),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FlightStage {
+    Mounting,
+    Cruising,
+    Dismounting,
+}
+
+impl State {
+    fn name(&self) -> &'static str {
+        match self {
+            State::NoEnemyFound => "NoEnemyFound",
+            State::SearchingForEnemy => "SearchingForEnemy",
+            State::EnemyFound(_) => "EnemyFound",
+            State::VerifyTarget(_) => "VerifyTarget",
+            State::Attacking(_) => "Attacking",
+            State::AfterEnemyKill(_) => "AfterEnemyKill",
+            State::ReturningToAnchor => "ReturningToAnchor",
+            State::Flying(FlightStage::Mounting) => "Mounting",
+            State::Flying(FlightStage::Cruising) => "Flying",
+            State::Flying(FlightStage::Dismounting) => "Dismounting",
+        }
+    }
 }
 
 pub struct FarmingBehavior<'a> {
-    rng: rand::rngs::ThreadRng,
+    rng: StdRng,
     logger: &'a Logger,
     movement: &'a MovementAccessor,
-    window: &'a Window,
+    input: &'a dyn InputSink,
+    clock: &'a dyn Clock,
     state: State,
     slots_usage_last_time: [[Option<Instant>; 10]; 9],
     last_initial_attack_time: Instant,
@@ -52,22 +138,50 @@ pub struct FarmingBehavior<'a> {
     concurrent_mobs_under_attack: u32,
     wait_duration: Option<Duration>,
     wait_start: Instant,
+    /// Policy stats by `MobType`
+    mob_policies: [MobPolicyTracker; 3],
+    /// Patrol route and flight destination of the config, parsed when it changes
+    patrol_route: Vec<Waypoint>,
+    flight_destination: Option<Waypoint>,
+    /// Waypoint of the patrol route walked to, kept across fights
+    patrol_index: usize,
+    /// When the current waypoint was reached
+    patrol_dwell_start: Option<Instant>,
+    /// When the current stage of a flight started
+    flight_stage_start: Instant,
+    /// Times the current stage of a flight was tried again
+    mount_retries: u32,
+    /// Whether the flying slot didn't respond, the time out then disconnects as without it
+    flight_given_up: bool,
+    motion: MotionEstimator,
+    stuck_count: u32,
+    /// Whether unreadable mob names were already reported
+    unreadable_names_logged: bool,
+    /// Whether the missing minimap of the HUD layout was already reported
+    missing_minimap_logged: bool,
 }
 
 impl<'a> Behavior<'a> for FarmingBehavior<'a> {
     // Calculate max distance of mobs
 
-    fn new(logger: &'a Logger, movement: &'a MovementAccessor, window: &'a Window) -> Self {
+    fn new(
+        logger: &'a Logger,
+        movement: &'a MovementAccessor,
+        input: &'a dyn InputSink,
+        clock: &'a dyn Clock,
+        rng: &RngSource
+    ) -> Self {
         Self {
             logger,
             movement,
-            window,
-            rng: rand::thread_rng(),
+            input,
+            clock,
+            rng: rng.stream(RngStream::Farming),
             state: State::SearchingForEnemy, //Start with buff before attacking
             slots_usage_last_time: [[None; 10]; 9],
-            last_initial_attack_time: Instant::now(),
+            last_initial_attack_time: clock.now(),
             //searching_for_enemy_timeout: Instant::now(),
-            last_kill_time: Instant::now(),
+            last_kill_time: clock.now(),
             avoided_bounds: vec![],
             is_attacking: false,
             rotation_movement_tries: 0,
@@ -75,19 +189,45 @@ impl<'a> Behavior<'a> for FarmingBehavior<'a> {
             obstacle_avoidance_count: 0,
             last_summon_pet_time: None,
             last_killed_type: MobType::Passive,
-            start_time: Instant::now(),
+            start_time: clock.now(),
             already_attack_count: 0,
             last_click_pos: None,
             stealed_target_count: 0,
             last_no_ennemy_time: None,
             concurrent_mobs_under_attack: 0,
             wait_duration: None,
-            wait_start: Instant::now(),
+            wait_start: clock.now(),
+            mob_policies: Default::default(),
+            patrol_route: vec![],
+            flight_destination: None,
+            patrol_index: 0,
+            patrol_dwell_start: None,
+            flight_stage_start: clock.now(),
+            mount_retries: 0,
+            flight_given_up: false,
+            motion: MotionEstimator::new(),
+            stuck_count: 0,
+            unreadable_names_logged: false,
+            missing_minimap_logged: false,
         }
     }
 
-    fn start(&mut self, _config: &BotConfig) {}
-    fn update(&mut self, _config: &BotConfig) {}
+    fn start(&mut self, _config: &BotConfig) {
+        self.flight_given_up = false;
+        // Leash the character to where it starts
+        self.movement.schedule(|coordinator| coordinator.odometer_mut().reset());
+    }
+    fn update(&mut self, config: &BotConfig) {
+        let config = config.farming_config();
+        self.patrol_route = config.patrol_route().unwrap_or_else(|err| {
+            slog::error!(self.logger, "Invalid patrol route, circling instead"; "error" => err);
+            vec![]
+        });
+        self.flight_destination = config.flight_destination().unwrap_or_else(|err| {
+            slog::error!(self.logger, "Invalid flight destination, flying straight ahead"; "error" => err);
+            None
+        });
+    }
     fn stop(&mut self, _config: &BotConfig) {
         //self.slots_usage_last_time = [[None; 10]; 9];
     }
@@ -121,30 +261,56 @@ impl<'a> Behavior<'a> for FarmingBehavior<'a> {
                 State::VerifyTarget(_) => false,
                 State::Attacking(_) => false,
                 State::AfterEnemyKill(_) => true,
+                State::ReturningToAnchor => false,
+                State::Flying(_) => false,
             };
             if should_return {
                 return;
             }
         }
 
+        self.update_heading(config, image);
+        if self.is_stuck(image) {
+            slog::debug!(self.logger, "Stuck while searching"; "state" => self.state.name());
+            self.stuck_count += 1;
+            self.unstuck();
+        }
+
         // Check state machine
         self.state = match self.state {
-            State::NoEnemyFound => self.on_no_enemy_found(config),
+            State::NoEnemyFound => self.on_no_enemy_found(config, image),
             State::SearchingForEnemy => self.on_searching_for_enemy(config, image),
-            State::EnemyFound(mob) => self.on_enemy_found(mob),
+            State::EnemyFound(mob) => self.on_enemy_found(mob, image),
             State::VerifyTarget(mob) => self.on_verify_target(config, mob, image),
             State::Attacking(mob) => self.on_attacking(config, mob, image),
             State::AfterEnemyKill(_) => self.after_enemy_kill(frontend_info, config),
+            State::ReturningToAnchor => self.on_returning_to_anchor(config),
+            State::Flying(stage) => self.on_flying(config, stage, image),
         };
 
         frontend_info.set_is_attacking(self.is_attacking);
+        for mob_type in [MobType::Passive, MobType::Aggressive, MobType::Violet] {
+            frontend_info.set_mob_policy_stats(mob_type, self.mob_policy_tracker(mob_type).stats);
+        }
+        frontend_info.set_stuck_count(self.stuck_count);
+    }
+
+    fn annotations(&self) -> FrameAnnotations {
+        FrameAnnotations {
+            state: Some(self.state.name().to_string()),
+            avoided_bounds: self.avoided_bounds
+                .iter()
+                .map(|(bounds, _, _)| *bounds)
+                .collect(),
+            click_point: self.last_click_pos,
+        }
     }
 }
 
 impl FarmingBehavior<'_> {
     fn wait_cooldown(&mut self) -> bool {
         if self.wait_duration.is_some() {
-            if self.wait_start.elapsed() < self.wait_duration.unwrap() {
+            if self.clock.elapsed(self.wait_start) < self.wait_duration.unwrap() {
                 //let remaining = self.wait_duration.unwrap() - self.wait_start.elapsed();
                 //slog::debug!(self.logger, "Waiting for {:?} Remaining {:?}", self.wait_duration.unwrap(), remaining);
                 return true;
@@ -160,12 +326,84 @@ impl FarmingBehavior<'_> {
             if self.wait_duration.is_some() {
                 Some(self.wait_duration.unwrap() + duration)
             } else {
-                self.wait_start = Instant::now();
+                self.wait_start = self.clock.now();
                 Some(duration)
             }
         };
     }
 
+    /// Whether walking around to search for mobs stopped changing the scene. Fights have their
+    /// own obstacle avoidance. Never stuck while input is not sent, e.g. on dry run, since the
+    /// odometer counts the walk the character didn't make.
+    fn is_stuck(&mut self, image: &ImageAnalyzer) -> bool {
+        let is_searching = matches!(
+            self.state,
+            State::NoEnemyFound | State::SearchingForEnemy | State::ReturningToAnchor
+        );
+        match (is_searching && self.input.sends_input(), image.frame()) {
+            (true, Some(frame)) => self.motion.update(frame, self.movement.odometer().travelled()),
+            _ => {
+                self.motion.reset();
+                false
+            }
+        }
+    }
+
+    /// Back off, turn away and jump forward, hoping to get around whatever is in the way.
+    fn unstuck(&mut self) {
+        use crate::movement::prelude::*;
+        play!(self.movement => [
+            HoldKeyFor("S", dur::Fixed(800)),
+            Rotate(rot::Random, dur::Random(250..450)),
+            HoldKeys(vec!["W", "Space"]),
+            Wait(dur::Fixed(1000)),
+            ReleaseKeys(vec!["Space", "W"]),
+        ]);
+        self.motion.reset();
+    }
+
+    /// Read the heading from the minimap, keeping the leash from drifting and learning how
+    /// fast the character turns.
+    fn update_heading(&mut self, config: &FarmingConfig, image: &ImageAnalyzer) {
+        if config.leash_radius() == 0 && !config.use_minimap() {
+            return;
+        }
+        if image.hud().minimap.is_none() {
+            if !self.missing_minimap_logged {
+                self.missing_minimap_logged = true;
+                slog::warn!(
+                    self.logger,
+                    "The HUD layout has no minimap, minimap search and leash heading are disabled"
+                );
+            }
+            return;
+        }
+        if let Some(minimap) = image.minimap() {
+            self.movement.schedule(|coordinator| {
+                coordinator.odometer_mut().fix_heading(minimap.heading);
+            });
+        }
+    }
+
+    fn is_beyond_leash(&self, config: &FarmingConfig) -> bool {
+        let radius = config.leash_radius();
+        radius > 0 && self.movement.odometer().distance() > (radius as f32)
+    }
+
+    fn on_returning_to_anchor(&mut self, config: &FarmingConfig) -> State {
+        let odometer = self.movement.odometer();
+        if odometer.distance() <= (config.leash_radius() as f32) * LEASH_RETURN_RATIO {
+            slog::debug!(self.logger, "Back inside the leash"; "distance" => odometer.distance());
+            return State::SearchingForEnemy;
+        }
+        let movements = Movement::steer_towards(
+            odometer.turn_towards_origin(),
+            odometer.ms_per_degree()
+        );
+        self.movement.schedule(|coordinator| coordinator.play(&movements));
+        State::ReturningToAnchor
+    }
+
     fn update_timestamps(&mut self, config: &FarmingConfig) {
         self.update_pickup_pet(config);
 
@@ -179,7 +417,7 @@ impl FarmingBehavior<'_> {
         let mut result: Vec<(Bounds, Instant, u128)> = vec![];
         for n in 0..self.avoided_bounds.len() {
             let current = self.avoided_bounds[n];
-            if current.1.elapsed().as_millis() < current.2 {
+            if self.clock.elapsed(current.1).as_millis() < current.2 {
                 result.push(current);
             }
         }
@@ -191,14 +429,14 @@ impl FarmingBehavior<'_> {
         if let Some(pickup_pet_slot_index) = config.slot_index(SlotType::PickupPet) {
             if let Some(last_time) = self.last_summon_pet_time {
                 if
-                    last_time.elapsed().as_millis() >
+                    self.clock.elapsed(last_time).as_millis() >
                     (
                         config
                             .get_slot_cooldown(pickup_pet_slot_index.0, pickup_pet_slot_index.1)
                             .unwrap_or(3000) as u128
                     )
                 {
-                    send_slot_eval(self.window, pickup_pet_slot_index.0, pickup_pet_slot_index.1);
+                    self.input.send_slot(pickup_pet_slot_index.0, pickup_pet_slot_index.1);
                     self.last_summon_pet_time = None;
                 }
             }
@@ -215,7 +453,7 @@ impl FarmingBehavior<'_> {
                     .try_into();
                 if let Some(last_time) = last_time {
                     if let Ok(cooldown) = cooldown {
-                        let slot_last_time = last_time.elapsed().as_millis();
+                        let slot_last_time = self.clock.elapsed(last_time).as_millis();
                         if slot_last_time > cooldown {
                             self.slots_usage_last_time[slotbar_index][slot_index] = None;
                         }
@@ -228,7 +466,7 @@ impl FarmingBehavior<'_> {
     fn get_slot_for(
         &mut self,
         config: &FarmingConfig,
-        threshold: Option<u32>,
+        threshold: Option<StatValue>,
         slot_type: SlotType,
         send: bool
     ) -> Option<(usize, usize)> {
@@ -251,9 +489,9 @@ impl FarmingBehavior<'_> {
 
     fn send_slot(&mut self, slot_index: (usize, usize)) {
         // Send keystroke for first slot mapped to pill
-        send_slot_eval(self.window, slot_index.0, slot_index.1);
+        self.input.send_slot(slot_index.0, slot_index.1);
         // Update usage last time
-        self.slots_usage_last_time[slot_index.0][slot_index.1] = Some(Instant::now());
+        self.slots_usage_last_time[slot_index.0][slot_index.1] = Some(self.clock.now());
     }
 
     /// Pickup items on the ground.
@@ -261,19 +499,19 @@ impl FarmingBehavior<'_> {
         let slot = self.get_slot_for(config, None, SlotType::PickupPet, false);
         if let Some(index) = slot {
             if self.last_summon_pet_time.is_none() {
-                send_slot_eval(self.window, index.0, index.1);
-                self.last_summon_pet_time = Some(Instant::now());
+                self.input.send_slot(index.0, index.1);
+                self.last_summon_pet_time = Some(self.clock.now());
             } else {
                 // if pet is already out, just reset it's timer
-                self.last_summon_pet_time = Some(Instant::now());
+                self.last_summon_pet_time = Some(self.clock.now());
             }
         } else {
             let slot = self.get_slot_for(config, None, SlotType::PickupMotion, false);
             if let Some(index) = slot {
                 for _i in 1..10 {
                     // TODO Configurable number of tries
-                    send_slot_eval(self.window, index.0, index.1);
-                    std::thread::sleep(Duration::from_millis(300));
+                    self.input.send_slot(index.0, index.1);
+                    self.clock.sleep(Duration::from_millis(300));
                 }
             }
         }
@@ -283,7 +521,7 @@ impl FarmingBehavior<'_> {
         self.use_party_skills(config);
 
         // Check HP
-        let health_stat = Some(image.client_stats.hp.value);
+        let health_stat = Some(image.client_stats.hp.stat_value());
         if image.client_stats.hp.value > 0 {
             // Use a HealSkill if configured when health is under 85
             let pill = self.get_slot_for(config, health_stat, SlotType::Pill, true);
@@ -299,20 +537,20 @@ impl FarmingBehavior<'_> {
                     if aoe_heal.is_none() {
                         self.get_slot_for(config, health_stat, SlotType::Food, true);
                     } else {
-                        std::thread::sleep(Duration::from_millis(100));
+                        self.clock.sleep(Duration::from_millis(100));
                         self.get_slot_for(config, health_stat, SlotType::AOEHealSkill, true);
-                        std::thread::sleep(Duration::from_millis(100));
+                        self.clock.sleep(Duration::from_millis(100));
                         self.get_slot_for(config, health_stat, SlotType::AOEHealSkill, true);
                     }
                 }
             }
 
             // Check MP
-            let mp_stat = Some(image.client_stats.mp.value);
+            let mp_stat = Some(image.client_stats.mp.stat_value());
             self.get_slot_for(config, mp_stat, SlotType::MpRestorer, true);
 
             // Check FP
-            let fp_stat = Some(image.client_stats.fp.value);
+            let fp_stat = Some(image.client_stats.fp.stat_value());
             self.get_slot_for(config, fp_stat, SlotType::FpRestorer, true);
         }
     }
@@ -326,17 +564,47 @@ impl FarmingBehavior<'_> {
             self.send_slot(slot_index);
         }
     }
-    fn on_no_enemy_found(&mut self, config: &FarmingConfig) -> State {
+    fn on_no_enemy_found(&mut self, config: &FarmingConfig, image: &ImageAnalyzer) -> State {
         if let Some(last_no_ennemy_time) = self.last_no_ennemy_time {
             if
                 config.mobs_timeout() > 0 &&
-                last_no_ennemy_time.elapsed().as_millis() > config.mobs_timeout()
+                self.clock.elapsed(last_no_ennemy_time).as_millis() > config.mobs_timeout()
             {
-                self.window.app_handle().exit(0);
+                // Look for mobs in another area rather than giving up
+                if
+                    config.fly_on_mobs_timeout() &&
+                    config.slot_index(SlotType::Flying).is_some() &&
+                    !self.flight_given_up
+                {
+                    slog::debug!(self.logger, "No mob for too long, flying away");
+                    return self.toggle_flight(config, FlightStage::Mounting);
+                }
+                self.input.exit();
             }
         } else {
-            self.last_no_ennemy_time = Some(Instant::now());
+            self.last_no_ennemy_time = Some(self.clock.now());
         }
+
+        // Head for the closest mob of the minimap rather than searching blindly
+        if config.use_minimap() {
+            let minimap = image.minimap();
+            let nearest = minimap.as_ref().and_then(|map| map.nearest(MinimapDotKind::Mob));
+            if let (Some(minimap), Some(mob)) = (minimap.as_ref(), nearest) {
+                let movements = Movement::steer_towards(
+                    minimap.turn_towards(mob),
+                    self.movement.odometer().ms_per_degree()
+                );
+                self.movement.schedule(|coordinator| coordinator.play(&movements));
+                return State::SearchingForEnemy;
+            }
+        }
+
+        // Walk the patrol route rather than turning around
+        if !self.patrol_route.is_empty() {
+            self.walk_patrol_route();
+            return State::SearchingForEnemy;
+        }
+
         use crate::movement::prelude::*;
         // Try rotating first in order to locate nearby enemies
         if self.rotation_movement_tries < 30 {
@@ -364,6 +632,186 @@ impl FarmingBehavior<'_> {
         State::SearchingForEnemy
     }
 
+    fn patrol_movements(steps: &[PatrolStep]) -> Vec<Movement<'_>> {
+        use crate::movement::prelude::*;
+        steps
+            .iter()
+            .map(|step| {
+                match step {
+                    PatrolStep::Hold(key, duration) =>
+                        HoldKeyFor(key.as_str(), dur::Fixed(*duration)),
+                    PatrolStep::Jump => Jump,
+                    PatrolStep::Wait(duration) => Wait(dur::Fixed(*duration)),
+                }
+            })
+            .collect()
+    }
+
+    /// Head for the current waypoint, or wait there for mobs until it's time for the next one.
+    fn walk_patrol_route(&mut self) {
+        self.patrol_index %= self.patrol_route.len();
+        let waypoint = &self.patrol_route[self.patrol_index];
+        if let Some(dwell_start) = self.patrol_dwell_start {
+            if self.clock.elapsed(dwell_start).as_millis() >= (waypoint.dwell as u128) {
+                self.patrol_dwell_start = None;
+                self.patrol_index = (self.patrol_index + 1) % self.patrol_route.len();
+            }
+            return;
+        }
+
+        let is_reached = match &waypoint.action {
+            WaypointAction::Movements(steps) => {
+                let movements = Self::patrol_movements(steps);
+                self.movement.schedule(|coordinator| coordinator.play(&movements));
+                true
+            }
+            WaypointAction::OdometerTarget { x, y } => {
+                let odometer = self.movement.odometer();
+                if odometer.distance_to((*x, *y)) <= WAYPOINT_ARRIVAL_RADIUS {
+                    true
+                } else {
+                    let movements = Movement::steer_towards(
+                        odometer.turn_towards((*x, *y)),
+                        odometer.ms_per_degree()
+                    );
+                    self.movement.schedule(|coordinator| coordinator.play(&movements));
+                    false
+                }
+            }
+        };
+        if is_reached {
+            self.patrol_dwell_start = Some(self.clock.now());
+        }
+    }
+
+    /// Use the flying slot to mount or dismount, starting `stage`.
+    fn toggle_flight(&mut self, config: &FarmingConfig, stage: FlightStage) -> State {
+        if !matches!(self.state, State::Flying(current) if current == stage) {
+            self.mount_retries = 0;
+        }
+        if let Some((slot_bar_index, slot_index)) = config.slot_index(SlotType::Flying) {
+            self.input.send_slot(slot_bar_index, slot_index);
+        }
+        self.flight_stage_start = self.clock.now();
+        State::Flying(stage)
+    }
+
+    fn on_flying(
+        &mut self,
+        config: &FarmingConfig,
+        stage: FlightStage,
+        image: &ImageAnalyzer
+    ) -> State {
+        let elapsed = self.clock.elapsed(self.flight_stage_start).as_millis();
+        // Trust the flight gauge, or give (un)mounting some time
+        let is_flying = image.is_flying().unwrap_or(match stage {
+            FlightStage::Mounting => elapsed > MOUNT_DURATION,
+            FlightStage::Cruising => true,
+            FlightStage::Dismounting => elapsed <= MOUNT_DURATION,
+        });
+
+        match stage {
+            FlightStage::Mounting => {
+                if is_flying {
+                    // Destinations are relative to where the flight starts
+                    self.movement.schedule(|coordinator| {
+                        let odometer = coordinator.odometer_mut();
+                        odometer.reset();
+                        odometer.set_flying(true);
+                    });
+                    self.flight_stage_start = self.clock.now();
+                    State::Flying(FlightStage::Cruising)
+                } else if elapsed > MOUNT_TIMEOUT {
+                    self.retry_flight(config, FlightStage::Mounting)
+                } else {
+                    State::Flying(FlightStage::Mounting)
+                }
+            }
+            FlightStage::Cruising => {
+                if !is_flying {
+                    // Fell off the board
+                    self.land()
+                } else if self.fly_towards_destination(config, elapsed) {
+                    self.toggle_flight(config, FlightStage::Dismounting)
+                } else {
+                    State::Flying(FlightStage::Cruising)
+                }
+            }
+            FlightStage::Dismounting => {
+                if !is_flying {
+                    self.land()
+                } else if elapsed > MOUNT_TIMEOUT {
+                    self.retry_flight(config, FlightStage::Dismounting)
+                } else {
+                    State::Flying(FlightStage::Dismounting)
+                }
+            }
+        }
+    }
+
+    /// Press the flying slot again after (un)mounting timed out. The HUD may tell wrong, so
+    /// after a few tries give up on flying rather than toggling forever, and farm where the
+    /// character is.
+    fn retry_flight(&mut self, config: &FarmingConfig, stage: FlightStage) -> State {
+        if self.mount_retries >= MAX_MOUNT_RETRIES {
+            slog::warn!(self.logger, "Flying slot doesn't respond, giving up"; "stage" => format!("{:?}", stage));
+            self.flight_given_up = true;
+            return self.land();
+        }
+        let state = self.toggle_flight(config, stage);
+        self.mount_retries += 1;
+        state
+    }
+
+    /// Resume farming around the landing point.
+    fn land(&mut self) -> State {
+        slog::debug!(self.logger, "Landed, resuming farming");
+        self.movement.schedule(|coordinator| {
+            let odometer = coordinator.odometer_mut();
+            odometer.reset();
+            odometer.set_flying(false);
+        });
+        self.last_no_ennemy_time = None;
+        self.rotation_movement_tries = 0;
+        self.patrol_index = 0;
+        self.patrol_dwell_start = None;
+        State::SearchingForEnemy
+    }
+
+    /// Fly a bit further, `true` once the destination is reached.
+    fn fly_towards_destination(&self, config: &FarmingConfig, elapsed: u128) -> bool {
+        use crate::movement::prelude::*;
+        match self.flight_destination.as_ref().map(|waypoint| &waypoint.action) {
+            Some(&WaypointAction::OdometerTarget { x, y }) => {
+                let odometer = self.movement.odometer();
+                if odometer.distance_to((x, y)) <= FLIGHT_ARRIVAL_RADIUS {
+                    return true;
+                }
+                let movements = Movement::steer_towards(
+                    odometer.turn_towards((x, y)),
+                    odometer.ms_per_degree()
+                );
+                self.movement.schedule(|coordinator| coordinator.play(&movements));
+                false
+            }
+            Some(WaypointAction::Movements(steps)) => {
+                let movements = Self::patrol_movements(steps);
+                self.movement.schedule(|coordinator| coordinator.play(&movements));
+                true
+            }
+            // Straight ahead to a new search area
+            None => {
+                if elapsed >= config.flight_duration() {
+                    return true;
+                }
+                play!(self.movement => [
+                    Move(MovementDirection::Forward, dur::Fixed(500)),
+                ]);
+                false
+            }
+        }
+    }
+
     fn move_circle_pattern(&self, rotation_duration: u64) {
         // low rotation duration means big circle, high means little circle
         use crate::movement::prelude::*;
@@ -385,7 +833,30 @@ impl FarmingBehavior<'_> {
         if config.is_stop_fighting() {
             return State::VerifyTarget(Target::default());
         }
+        if self.is_beyond_leash(config) {
+            slog::debug!(self.logger, "Beyond the leash, returning"; "distance" => self.movement.odometer().distance());
+            return State::ReturningToAnchor;
+        }
         let mobs = image.identify_mobs(config);
+        if
+            !self.unreadable_names_logged &&
+            config.has_mob_name_lists() &&
+            !mobs.is_empty() &&
+            !mobs.iter().any(Target::has_readable_name)
+        {
+            self.unreadable_names_logged = true;
+            slog::warn!(
+                self.logger,
+                "Cannot read mob names, the allowed and ignored mobs lists have no effect"
+            );
+        }
+        let mobs = match self.apply_mob_policies(config, image, mobs) {
+            Some(mobs) => mobs,
+            // Moved away from a mob, search again from the new position
+            None => {
+                return State::SearchingForEnemy;
+            }
+        };
         if mobs.is_empty() {
             // Transition to next state
             State::NoEnemyFound
@@ -404,6 +875,7 @@ impl FarmingBehavior<'_> {
                 State::NoEnemyFound
             } else {
                 self.rotation_movement_tries = 0;
+                self.last_no_ennemy_time = None;
                 //slog::debug!(self.logger, "Found mobs"; "mob_type" => mob_type, "mob_count" => mob_list.len());
                 if
                     let Some(mob) = ({
@@ -435,6 +907,73 @@ impl FarmingBehavior<'_> {
         }
     }
 
+    fn mob_policy_tracker(&mut self, mob_type: MobType) -> &mut MobPolicyTracker {
+        &mut self.mob_policies[mob_type as usize]
+    }
+
+    /// Apply the configured policy to each class of mobs. Returns `None` if the character moved
+    /// away from one, otherwise the mobs that may be attacked.
+    fn apply_mob_policies(
+        &mut self,
+        config: &FarmingConfig,
+        image: &ImageAnalyzer,
+        mobs: Vec<Target>
+    ) -> Option<Vec<Target>> {
+        let mut attackable = Vec::with_capacity(mobs.len());
+        let mut avoided = vec![];
+        for mob_type in [MobType::Passive, MobType::Aggressive, MobType::Violet] {
+            let of_type: Vec<Target> = mobs
+                .iter()
+                .filter(|m| m.target_type == TargetType::Mob(mob_type))
+                .copied()
+                .collect();
+            let new_mobs = self.mob_policy_tracker(mob_type).track(&of_type);
+
+            // Violets are only left if their policy is to attack them
+            match config.mob_policy(mob_type) {
+                MobPolicy::Attack => attackable.extend(of_type),
+                MobPolicy::Ignore => {
+                    self.mob_policy_tracker(mob_type).stats.ignored += new_mobs;
+                }
+                MobPolicy::Avoid => avoided.extend(of_type),
+            }
+        }
+
+        if
+            let Some(mob) = image.find_closest_mob(
+                avoided.as_slice(),
+                None,
+                MAX_DISTANCE_FOR_AVOIDANCE,
+                self.logger
+            )
+        {
+            slog::debug!(self.logger, "Moving away from mob"; "bounds" => mob.bounds);
+            self.move_away_from(image, mob);
+            if let TargetType::Mob(mob_type) = mob.target_type {
+                self.mob_policy_tracker(mob_type).stats.avoided += 1;
+            }
+            return None;
+        }
+        Some(attackable)
+    }
+
+    /// Turn away from `mob` and run off.
+    fn move_away_from(&self, image: &ImageAnalyzer, mob: &Target) {
+        use crate::movement::prelude::*;
+
+        let mid_x = image.image_size().map(|(width, _)| width / 2).unwrap_or_default();
+        let direction = match mob.bounds.center().x < mid_x {
+            true => rot::Right,
+            false => rot::Left,
+        };
+        play!(self.movement => [
+            Rotate(direction, dur::Fixed(400)),
+            HoldKeys(vec!["W", "Space"]),
+            Wait(dur::Fixed(1500)),
+            ReleaseKeys(vec!["Space", "W"]),
+        ]);
+    }
+
     fn prioritize_aggro(
         &mut self,
         config: &FarmingConfig,
@@ -451,26 +990,22 @@ impl FarmingBehavior<'_> {
                 .cloned()
                 .collect::<Vec<_>>();
 
-            // Check if there's aggressive mobs otherwise collect passive mobs
+            // Check if there's aggressive mobs otherwise collect the other mobs
             if
                 (mob_list.is_empty() ||
                     (self.last_killed_type == MobType::Aggressive &&
                         mob_list.len() == 1 &&
-                        self.last_kill_time.elapsed().as_millis() < 5000)) &&
+                        self.clock.elapsed(self.last_kill_time).as_millis() < 5000)) &&
                 image.client_stats.hp.value >= config.min_hp_attack()
             {
                 mob_list = mobs
                     .iter()
-                    .filter(|m| m.target_type == TargetType::Mob(MobType::Passive))
+                    .filter(|m| m.target_type != TargetType::Mob(MobType::Aggressive))
                     .cloned()
                     .collect::<Vec<_>>();
             }
         } else {
-            mob_list = mobs
-                .iter()
-                .filter(|m| m.target_type != TargetType::Mob(MobType::Violet)) // removing violets from coordinates
-                .cloned()
-                .collect::<Vec<_>>();
+            mob_list = mobs;
         }
 
         mob_list
@@ -479,21 +1014,35 @@ impl FarmingBehavior<'_> {
     fn avoid_last_click(&mut self) {
         if let Some(point) = self.last_click_pos {
             let marker = Bounds::new(point.x - 1, point.y - 1, 2, 2);
-            self.avoided_bounds.push((marker, Instant::now(), 5000));
+            self.avoided_bounds.push((marker, self.clock.now(), 5000));
         }
     }
 
-    fn on_enemy_found(&mut self, mob: Target) -> State {
+    fn on_enemy_found(&mut self, mob: Target, image: &ImageAnalyzer) -> State {
         // Transform attack coords into local window coords
         let point = mob.get_attack_coords();
 
+        // The character walks up to the mob it clicks, the screen's up being forward
+        if let Some((width, height)) = image.image_size() {
+            let forward = ((height / 2) as f32) - (point.y as f32);
+            let right = (point.x as f32) - ((width / 2) as f32);
+            self.movement.schedule(|coordinator| {
+                coordinator
+                    .odometer_mut()
+                    .displace(forward * METERS_PER_SCREEN_PIXEL, right * METERS_PER_SCREEN_PIXEL);
+            });
+        }
+
         self.last_click_pos = Some(point);
+        if let TargetType::Mob(mob_type) = mob.target_type {
+            self.mob_policy_tracker(mob_type).stats.attacked += 1;
+        }
 
         // Set cursor position and simulate a click
-        eval_mob_click(self.window, point);
+        self.input.mob_click(point);
 
         // Wait a few ms before transitioning state
-        std::thread::sleep(Duration::from_millis(150));
+        self.clock.sleep(Duration::from_millis(150));
         //self.wait(Duration::from_millis(150));
         self.is_attacking = false;
         State::VerifyTarget(mob)
@@ -506,7 +1055,7 @@ impl FarmingBehavior<'_> {
             if let Some(marker) = image.client_stats.target_marker {
                 self.avoided_bounds.push((
                     marker.bounds.grow_by(self.already_attack_count * 10),
-                    Instant::now(),
+                    self.clock.now(),
                     2000,
                 ));
                 self.already_attack_count += 1;
@@ -545,7 +1094,7 @@ impl FarmingBehavior<'_> {
                 ]);
             }
 
-            image.client_stats.target_hp.reset_last_update_time();
+            image.client_stats.target_hp.reset_last_update_time(self.clock.now());
             self.obstacle_avoidance_count += 1;
             false
         } else {
@@ -597,16 +1146,15 @@ impl FarmingBehavior<'_> {
             } else { */
             // engaging the mob
             self.obstacle_avoidance_count = 0;
-            self.last_initial_attack_time = Instant::now();
+            self.last_initial_attack_time = self.clock.now();
             self.is_attacking = true;
             self.already_attack_count = 0;
             /*  } */
         }
 
         if image.client_stats.target_on_screen || image.client_stats.target_is_alive {
-            let last_target_hp_update = image.client_stats.target_hp.last_update_time
-                .unwrap()
-                .elapsed()
+            let last_target_hp_update = self.clock
+                .elapsed(image.client_stats.target_hp.last_update_time.unwrap())
                 .as_millis();
 
             // Obstacle avoidance
@@ -678,7 +1226,7 @@ impl FarmingBehavior<'_> {
         self.after_enemy_kill_debug(frontend_info);
 
         self.stealed_target_count = 0;
-        self.last_kill_time = Instant::now();
+        self.last_kill_time = self.clock.now();
 
         // Pickup items
         self.pickup_items(config);
@@ -688,11 +1236,11 @@ impl FarmingBehavior<'_> {
     }
     fn after_enemy_kill_debug(&mut self, frontend_info: &mut FrontendInfo) {
         // Let's introduce some stats
-        let started_elapsed = self.start_time.elapsed();
+        let started_elapsed = self.clock.elapsed(self.start_time);
         let started_formatted = DateTime::format_time(started_elapsed);
 
-        let elapsed_time_to_kill = self.last_initial_attack_time.elapsed();
-        let elapsed_search_time = self.last_kill_time.elapsed() - elapsed_time_to_kill;
+        let elapsed_time_to_kill = self.clock.elapsed(self.last_initial_attack_time);
+        let elapsed_search_time = self.clock.elapsed(self.last_kill_time) - elapsed_time_to_kill;
 
         let search_time_as_secs = {
             if self.kill_count > 0 {
@@ -732,3 +1280,25 @@ impl FarmingBehavior<'_> {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::MobPolicyTracker;
+    use crate::data::{ Bounds, Target };
+
+    #[test]
+    fn test_mob_policy_tracker() {
+        let mob = |x: u32, y: u32| Target {
+            bounds: Bounds::new(x, y, 60, 10),
+            ..Default::default()
+        };
+        let mut tracker = MobPolicyTracker::default();
+        assert_eq!(tracker.track(&[mob(100, 100), mob(400, 300)]), 2);
+        // The same mobs drifting a little are not counted again
+        assert_eq!(tracker.track(&[mob(110, 95), mob(390, 310)]), 0);
+        assert_eq!(tracker.track(&[mob(115, 95), mob(700, 200)]), 1);
+        assert_eq!(tracker.stats.seen, 2);
+        assert_eq!(tracker.track(&[]), 0);
+        assert_eq!(tracker.track(&[mob(115, 95)]), 1);
+    }
+}
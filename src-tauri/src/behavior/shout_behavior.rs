@@ -1,23 +1,26 @@
 use std::time::Instant;
 
 use guard::guard;
+use rand::rngs::StdRng;
 use slog::Logger;
-use tauri::Window;
 
 use super::Behavior;
 use crate::{
     image_analyzer::ImageAnalyzer,
     ipc::{BotConfig, FrontendInfo, ShoutConfig},
     movement::MovementAccessor,
+    platform::InputSink,
     play,
+    utils::{ Clock, RngSource, RngStream },
 };
 
 #[allow(dead_code)]
 pub struct ShoutBehavior<'a> {
-    rng: rand::rngs::ThreadRng,
+    rng: StdRng,
     logger: &'a Logger,
     movement: &'a MovementAccessor,
-    window: &'a Window,
+    input: &'a dyn InputSink,
+    clock: &'a dyn Clock,
     last_shout_time: Instant,
     shown_messages: Vec<String>,
     shout_interval: u64,
@@ -25,13 +28,20 @@ pub struct ShoutBehavior<'a> {
 }
 
 impl<'a> Behavior<'a> for ShoutBehavior<'a> {
-    fn new(logger: &'a Logger, movement: &'a MovementAccessor, window: &'a Window) -> Self {
+    fn new(
+        logger: &'a Logger,
+        movement: &'a MovementAccessor,
+        input: &'a dyn InputSink,
+        clock: &'a dyn Clock,
+        rng: &RngSource
+    ) -> Self {
         Self {
             logger,
             movement,
-            window,
-            rng: rand::thread_rng(),
-            last_shout_time: Instant::now(),
+            input,
+            clock,
+            rng: rng.stream(RngStream::Shout),
+            last_shout_time: clock.now(),
             shown_messages: Vec::new(),
             shout_interval: 30000,
             message_iter: None,
@@ -73,10 +83,7 @@ impl ShoutBehavior<'_> {
         use crate::movement::prelude::*;
 
         // Return early if time since last shout is less than shout interval
-        if Instant::now()
-            .duration_since(self.last_shout_time)
-            .as_millis()
-            < self.shout_interval as u128
+        if self.clock.elapsed(self.last_shout_time).as_millis() < self.shout_interval as u128
         {
             return;
         }
@@ -113,6 +120,6 @@ impl ShoutBehavior<'_> {
         ]);
 
         // Update last shout time
-        self.last_shout_time = Instant::now();
+        self.last_shout_time = self.clock.now();
     }
 }
@@ -1,17 +1,17 @@
 use std::time::{ Duration, Instant };
 
 use slog::Logger;
-use tauri::Window;
 
 use super::Behavior;
 
 use crate::{
-    data::Point,
+    data::{ MinimapDotKind, Point },
     image_analyzer::ImageAnalyzer,
-    ipc::{ BotConfig, FrontendInfo, SlotType, SupportConfig },
-    movement::{ prelude::*, MovementAccessor },
-    platform::{ eval_simple_click, send_slot_eval },
+    ipc::{ BotConfig, FrontendInfo, SlotType, StatValue, SupportConfig },
+    movement::{ prelude::*, Movement, MovementAccessor },
+    platform::InputSink,
     play,
+    utils::{ Clock, RngSource },
 };
 const HEAL_SKILL_CAST_TIME: u64 = 2000;
 const BUFF_CAST_TIME: u64 = 2500;
@@ -19,7 +19,8 @@ const AOE_SKILL_CAST_TIME: u64 = 100;
 pub struct SupportBehavior<'a> {
     logger: &'a Logger,
     movement: &'a MovementAccessor,
-    window: &'a Window,
+    input: &'a dyn InputSink,
+    clock: &'a dyn Clock,
     self_buff_usage_last_time: [[Option<Instant>; 10]; 9],
     slots_usage_last_time: [[Option<Instant>; 10]; 9],
     last_jump_time: Instant,
@@ -37,28 +38,38 @@ pub struct SupportBehavior<'a> {
     target_buffing: bool,
     //is_on_flight: bool,
     buff_counter: u32,
+    /// Whether the missing minimap of the HUD layout was already reported
+    missing_minimap_logged: bool,
 }
 
 impl<'a> Behavior<'a> for SupportBehavior<'a> {
-    fn new(logger: &'a Logger, movement: &'a MovementAccessor, window: &'a Window) -> Self {
+    fn new(
+        logger: &'a Logger,
+        movement: &'a MovementAccessor,
+        input: &'a dyn InputSink,
+        clock: &'a dyn Clock,
+        _rng: &RngSource
+    ) -> Self {
         Self {
             logger,
             movement,
-            window,
+            input,
+            clock,
             self_buff_usage_last_time: [[None; 10]; 9],
             slots_usage_last_time: [[None; 10]; 9],
-            last_jump_time: Instant::now(),
+            last_jump_time: clock.now(),
             avoid_obstacle_direction: "D".to_owned(),
             is_waiting_for_revive: false,
             last_far_from_target: None,
             last_target_distance: None,
             wait_duration: None,
-            wait_start: Instant::now(),
+            wait_start: clock.now(),
             has_target: false,
             self_buffing: false,
             target_buffing: false,
             buff_counter: 0,
             //is_on_flight: false,
+            missing_minimap_logged: false,
         }
     }
 
@@ -70,7 +81,7 @@ impl<'a> Behavior<'a> for SupportBehavior<'a> {
         self.has_target = false;
         self.self_buffing = false;
         self.wait_duration = None;
-        self.wait_start = Instant::now();
+        self.wait_start = self.clock.now();
         slog::debug!(self.logger, "SupportBehavior stopped");
     }
 
@@ -150,7 +161,7 @@ impl<'a> Behavior<'a> for SupportBehavior<'a> {
 impl SupportBehavior<'_> {
     fn random_camera_movement(&mut self) {
         //add movement every minute to try to avoid bot detection
-        if self.last_jump_time.elapsed().as_millis() > 10000 {
+        if self.clock.elapsed(self.last_jump_time).as_millis() > 10000 {
             use crate::movement::prelude::*;
             play!(self.movement => [
                 // Rotate in random direction for a random duration
@@ -159,7 +170,7 @@ impl SupportBehavior<'_> {
                 Wait(dur::Fixed(50)),
             ]);
 
-            self.last_jump_time = Instant::now();
+            self.last_jump_time = self.clock.now();
         }
     }
     fn get_target_distance(&mut self, image: &mut ImageAnalyzer) -> Option<i32> {
@@ -185,11 +196,39 @@ impl SupportBehavior<'_> {
             if self.avoid_obstacle_direction == "D" { "A".to_owned() } else { "D".to_owned() }
         };
     }
+    /// Without a target marker in sight, walk towards the closest party member of the
+    /// minimap, or circle around.
+    fn search_leader(&mut self, config: &SupportConfig, image: &ImageAnalyzer) {
+        if
+            config.use_minimap() &&
+            image.hud().minimap.is_none() &&
+            !self.missing_minimap_logged
+        {
+            self.missing_minimap_logged = true;
+            slog::warn!(self.logger, "The HUD layout has no minimap, minimap search is disabled");
+        }
+        if config.use_minimap() {
+            if let Some(minimap) = image.minimap() {
+                self.movement.schedule(|coordinator| {
+                    coordinator.odometer_mut().fix_heading(minimap.heading);
+                });
+                if let Some(leader) = minimap.nearest(MinimapDotKind::PartyMember) {
+                    let movements = Movement::steer_towards(
+                        minimap.turn_towards(leader),
+                        self.movement.odometer().ms_per_degree()
+                    );
+                    self.movement.schedule(|coordinator| coordinator.play(&movements));
+                    return;
+                }
+            }
+        }
+        self.move_circle_pattern();
+    }
     fn is_target_in_range(&mut self, config: &SupportConfig, image: &mut ImageAnalyzer) -> bool {
         let distance = self.get_target_distance(image);
         if let Some(distance) = distance {
             if distance == 9999 {
-                self.move_circle_pattern();
+                self.search_leader(config, image);
                 return false;
             }
 
@@ -200,14 +239,14 @@ impl SupportBehavior<'_> {
                     } else {
                         if let Some(last_far_from_target) = self.last_far_from_target {
                             if
-                                last_far_from_target.elapsed().as_millis() > 3000 &&
+                                self.clock.elapsed(last_far_from_target).as_millis() > 3000 &&
                                 last_target_distance < distance
                             {
-                                self.last_far_from_target = Some(Instant::now());
+                                self.last_far_from_target = Some(self.clock.now());
                                 self.move_circle_pattern();
                             }
                         } else {
-                            self.last_far_from_target = Some(Instant::now());
+                            self.last_far_from_target = Some(self.clock.now());
                         }
                     }
                 }
@@ -262,7 +301,7 @@ impl SupportBehavior<'_> {
     }
     fn wait_cooldown(&mut self) -> bool {
         if self.wait_duration.is_some() {
-            if self.wait_start.elapsed() < self.wait_duration.unwrap() {
+            if self.clock.elapsed(self.wait_start) < self.wait_duration.unwrap() {
                 //let remaining = self.wait_duration.unwrap() - self.wait_start.elapsed();
                 //slog::debug!(self.logger, "Waiting for {:?} Remaining {:?}", self.wait_duration.unwrap(), remaining);
                 return true;
@@ -278,7 +317,7 @@ impl SupportBehavior<'_> {
             if self.wait_duration.is_some() {
                 Some(self.wait_duration.unwrap() + duration)
             } else {
-                self.wait_start = Instant::now();
+                self.wait_start = self.clock.now();
                 Some(duration)
             }
         };
@@ -308,15 +347,15 @@ impl SupportBehavior<'_> {
             // Open party menu
             PressKey("P"),
         ]);
-        std::thread::sleep(Duration::from_millis(150));
+        self.clock.sleep(Duration::from_millis(150));
         let point = Point::new(213, 440); //moving to the "position of the party window
-        eval_simple_click(self.window, point);
+        self.input.click(point);
         play!(self.movement => [
         PressKey("Z"),
         Wait(dur::Fixed(10)),
         PressKey("P"),
         ]);
-        std::thread::sleep(Duration::from_millis(500));
+        self.clock.sleep(Duration::from_millis(500));
     }
 
     fn follow_target(&mut self) {
@@ -337,7 +376,7 @@ impl SupportBehavior<'_> {
                     .try_into();
                 if let Some(last_time) = last_time {
                     if let Ok(cooldown) = cooldown {
-                        let slot_last_time = last_time.elapsed().as_millis();
+                        let slot_last_time = self.clock.elapsed(last_time).as_millis();
                         if slot_last_time > cooldown {
                             self.slots_usage_last_time[slotbar_index][slot_index] = None;
                         }
@@ -353,7 +392,7 @@ impl SupportBehavior<'_> {
                     .try_into();
                 if let Some(last_time) = last_time {
                     if let Ok(cooldown) = cooldown {
-                        let slot_last_time = last_time.elapsed().as_millis();
+                        let slot_last_time = self.clock.elapsed(last_time).as_millis();
                         if slot_last_time > cooldown {
                             self.self_buff_usage_last_time[slotbar_index][slot_index] = None;
                         }
@@ -366,7 +405,7 @@ impl SupportBehavior<'_> {
     fn get_slot_for(
         &mut self,
         config: &SupportConfig,
-        threshold: Option<u32>,
+        threshold: Option<StatValue>,
         slot_type: SlotType,
         send: bool,
         last_slots_usage: Option<[[Option<Instant>; 10]; 9]>
@@ -392,12 +431,12 @@ impl SupportBehavior<'_> {
 
     fn send_slot(&mut self, slot_index: (usize, usize), is_self_buff: bool) {
         // Send keystroke for first slot mapped to pill
-        send_slot_eval(self.window, slot_index.0, slot_index.1);
+        self.input.send_slot(slot_index.0, slot_index.1);
         // Update usage last time
         if is_self_buff {
-            self.self_buff_usage_last_time[slot_index.0][slot_index.1] = Some(Instant::now());
+            self.self_buff_usage_last_time[slot_index.0][slot_index.1] = Some(self.clock.now());
         } else {
-            self.slots_usage_last_time[slot_index.0][slot_index.1] = Some(Instant::now());
+            self.slots_usage_last_time[slot_index.0][slot_index.1] = Some(self.clock.now());
         }
     }
 
@@ -412,7 +451,7 @@ impl SupportBehavior<'_> {
     }
 
     fn check_self_restorations(&mut self, config: &SupportConfig, image: &mut ImageAnalyzer) {
-        let health_stat = Some(image.client_stats.hp.value);
+        let health_stat = Some(image.client_stats.hp.stat_value());
         // Use a HealSkill if configured when health is under 85
         let pill = self.get_slot_for(config, health_stat, SlotType::Pill, true, None);
         if pill.is_none() {
@@ -428,9 +467,9 @@ impl SupportBehavior<'_> {
                 if aoe_heal.is_none() {
                     self.get_slot_for(config, health_stat, SlotType::Food, true, None);
                 } else {
-                    std::thread::sleep(Duration::from_millis(AOE_SKILL_CAST_TIME));
+                    self.clock.sleep(Duration::from_millis(AOE_SKILL_CAST_TIME));
                     self.get_slot_for(config, health_stat, SlotType::AOEHealSkill, true, None);
-                    std::thread::sleep(Duration::from_millis(AOE_SKILL_CAST_TIME));
+                    self.clock.sleep(Duration::from_millis(AOE_SKILL_CAST_TIME));
                     self.get_slot_for(config, health_stat, SlotType::AOEHealSkill, true, None);
                 }
             } else {
@@ -442,18 +481,18 @@ impl SupportBehavior<'_> {
             }
 
             // Check MP
-            let mp_stat = Some(image.client_stats.mp.value);
+            let mp_stat = Some(image.client_stats.mp.stat_value());
             self.get_slot_for(config, mp_stat, SlotType::MpRestorer, true, None);
 
             // Check FP
 
-            let fp_stat = Some(image.client_stats.fp.value);
+            let fp_stat = Some(image.client_stats.fp.stat_value());
             self.get_slot_for(config, fp_stat, SlotType::FpRestorer, true, None);
         }
     }
 
     fn check_target_restorations(&mut self, config: &SupportConfig, image: &mut ImageAnalyzer) {
-        let target_health_stat = Some(image.client_stats.target_hp.value);
+        let target_health_stat = Some(image.client_stats.target_hp.stat_value());
         // Use a HealSkill if configured when health is under 85
 
         let heal = self.get_slot_for(config, target_health_stat, SlotType::HealSkill, true, None);
@@ -467,9 +506,9 @@ impl SupportBehavior<'_> {
             );
             if aoe_heal.is_some() {
                 self.get_slot_for(config, target_health_stat, SlotType::AOEHealSkill, true, None);
-                std::thread::sleep(Duration::from_millis(100));
+                self.clock.sleep(Duration::from_millis(100));
                 self.get_slot_for(config, target_health_stat, SlotType::AOEHealSkill, true, None);
-                std::thread::sleep(Duration::from_millis(100));
+                self.clock.sleep(Duration::from_millis(100));
             }
         } else {
             //self.wait(Duration::from_millis(HEAL_SKILL_CAST_TIME));
@@ -1,16 +1,23 @@
 use slog::Logger;
-use tauri::Window;
 
 use crate::{
+    debug_frames::FrameAnnotations,
     image_analyzer::ImageAnalyzer,
     ipc::{BotConfig, FrontendInfo},
     movement::MovementAccessor,
+    platform::InputSink,
+    utils::{Clock, RngSource},
 };
 
 pub trait Behavior<'a> {
     /// Runs on initialization
-    fn new(logger: &'a Logger, movement_accessor: &'a MovementAccessor, window: &'a Window)
-        -> Self;
+    fn new(
+        logger: &'a Logger,
+        movement_accessor: &'a MovementAccessor,
+        input: &'a dyn InputSink,
+        clock: &'a dyn Clock,
+        rng: &RngSource,
+    ) -> Self;
 
     /// Runs on activation
     fn start(&mut self, config: &BotConfig);
@@ -30,4 +37,9 @@ pub trait Behavior<'a> {
         config: &BotConfig,
         analyzer: &mut ImageAnalyzer,
     );
+
+    /// What the last iteration decided, drawn on debug frames
+    fn annotations(&self) -> FrameAnnotations {
+        FrameAnnotations::default()
+    }
 }
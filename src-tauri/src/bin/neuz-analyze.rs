@@ -0,0 +1,356 @@
+//! Run the mob and stat detection on screenshots and print the results as JSON, one line per
+//! screenshot. Used to tune profiles and triage screenshots without starting the app.
+//!
+//! The binary only needs the detection of the `app` library, so it builds without the `app`
+//! feature and thus without Tauri and libscreenshot: `cargo run --no-default-features --bin
+//! neuz-analyze -- <SCREENSHOT>`.
+
+use std::{env, fs, path::PathBuf, process};
+
+use app::{
+    data::{Bounds, MobType, StatInfo, Target, TargetType},
+    image_analyzer::{ImageAnalyzer, MobColors},
+    ipc::FarmingConfig,
+    layout::HudLayout,
+    ocr::GlyphAtlas,
+    replay::FrameReplay,
+};
+use serde::Serialize;
+use serde_json::{json, Value};
+use slog::Logger;
+
+const USAGE: &str = "\
+Usage: neuz-analyze [OPTIONS] <SCREENSHOT|DIR>...
+
+Options:
+  --config <FILE>          Bot config (.botconfig_*) or farming config to take the mob settings from
+  --layout <FILE>          HUD layout to find the stat bars with
+  --<option> <VALUE>       Override a farming config option, e.g. `--passive-tolerence 5`,
+                           `--violet-mobs-colors 182,144,146` or `--color-match-mode Hsv`
+  --pretty                 Pretty print the JSON
+  --glyphs                 Print the glyphs of every mob name plate in the glyph atlas format
+                           instead, to add them to `assets/ocr/flyff_ui_font.txt`
+  -h, --help               Print this help";
+
+/// Farming config options which can be overridden from the command line
+const FARMING_OPTIONS: [&str; 14] = [
+    "passive_mobs_colors",
+    "passive_tolerence",
+    "aggressive_mobs_colors",
+    "aggressive_tolerence",
+    "violet_mobs_colors",
+    "violet_tolerence",
+    "min_mobs_name_width",
+    "max_mobs_name_width",
+    "mobs_name_gap_x",
+    "mobs_name_gap_y",
+    "color_match_mode",
+    "passive_color_match_mode",
+    "aggressive_color_match_mode",
+    "violet_color_match_mode",
+];
+
+#[derive(Debug, Serialize)]
+struct MobOutput {
+    #[serde(rename = "type")]
+    mob_type: Option<MobType>,
+    bounds: Bounds,
+    name: Option<String>,
+    level: Option<u32>,
+}
+
+impl From<&Target> for MobOutput {
+    fn from(target: &Target) -> Self {
+        Self {
+            mob_type: match target.target_type {
+                TargetType::Mob(mob_type) => Some(mob_type),
+                _ => None,
+            },
+            bounds: target.bounds,
+            name: target.name.map(|name| name.to_string()),
+            level: target.level,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct BarOutput {
+    /// Fill of the bar in percent
+    value: u32,
+    /// Exact values read from the bar text
+    current: Option<u32>,
+    max: Option<u32>,
+}
+
+impl From<&StatInfo> for BarOutput {
+    fn from(stat: &StatInfo) -> Self {
+        Self {
+            value: stat.value,
+            current: stat.current,
+            max: stat.max,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct FrameOutput {
+    file: String,
+    width: u32,
+    height: u32,
+    mobs: Vec<MobOutput>,
+    target_marker: Option<Bounds>,
+    target_distance: Option<i32>,
+    hp: BarOutput,
+    mp: BarOutput,
+    fp: BarOutput,
+    target_hp: BarOutput,
+    target_mp: BarOutput,
+    is_alive: String,
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct Args {
+    help: bool,
+    config: Option<PathBuf>,
+    layout: Option<PathBuf>,
+    overrides: Vec<(String, Value)>,
+    pretty: bool,
+    glyphs: bool,
+    screenshots: Vec<PathBuf>,
+}
+
+/// Parse the command line arguments, without the program name.
+fn parse_args(raw: impl IntoIterator<Item = String>) -> Result<Args, String> {
+    let mut args = Args::default();
+    let mut raw = raw.into_iter();
+    while let Some(arg) = raw.next() {
+        let mut value = |name: &str| raw.next().ok_or(format!("missing value for {}", name));
+        match arg.as_str() {
+            "-h" | "--help" => {
+                args.help = true;
+                return Ok(args);
+            }
+            "--config" => args.config = Some(PathBuf::from(value(&arg)?)),
+            "--layout" => args.layout = Some(PathBuf::from(value(&arg)?)),
+            "--pretty" => args.pretty = true,
+            "--glyphs" => args.glyphs = true,
+            _ => match arg.strip_prefix("--") {
+                Some(option) => {
+                    let key = option.replace('-', "_");
+                    if !FARMING_OPTIONS.contains(&key.as_str()) {
+                        return Err(format!("unknown option {}", arg));
+                    }
+                    let value = parse_option_value(&value(&arg)?)
+                        .ok_or(format!("invalid value for {}", arg))?;
+                    args.overrides.push((key, value));
+                }
+                None => args.screenshots.push(PathBuf::from(arg)),
+            },
+        }
+    }
+    if args.screenshots.is_empty() {
+        return Err("no screenshot given".to_string());
+    }
+    Ok(args)
+}
+
+/// A number, a color as `r,g,b`, or a name like `Hsv`.
+fn parse_option_value(value: &str) -> Option<Value> {
+    if value.starts_with(char::is_alphabetic) {
+        Some(json!(value.trim()))
+    } else if value.contains(',') {
+        let channels = value
+            .split(',')
+            .map(|channel| channel.trim().parse::<u8>().ok())
+            .collect::<Option<Vec<_>>>()?;
+        (channels.len() == 3).then(|| json!(channels))
+    } else {
+        value.trim().parse::<u32>().ok().map(|number| json!(number))
+    }
+}
+
+/// Read the farming config from a bot config or a bare farming config, then apply overrides.
+fn load_farming_config(args: &Args) -> Result<FarmingConfig, String> {
+    let mut config = match &args.config {
+        Some(path) => {
+            let text = fs::read_to_string(path).map_err(|err| err.to_string())?;
+            let mut config: Value = serde_json::from_str(&text).map_err(|err| err.to_string())?;
+            match config.get_mut("farming_config") {
+                Some(farming_config) => farming_config.take(),
+                None => config,
+            }
+        }
+        None => json!({}),
+    };
+    for (key, value) in args.overrides.iter() {
+        config[key.as_str()] = value.clone();
+    }
+    serde_json::from_value(config).map_err(|err| format!("invalid farming config: {}", err))
+}
+
+fn run(args: Args) -> Result<(), String> {
+    let config = load_farming_config(&args)?;
+    let logger = Logger::root(slog::Discard, slog::o!());
+
+    let mut analyzer = ImageAnalyzer::headless();
+    analyzer.set_mob_colors(MobColors::from_config(&config));
+    if let Some(path) = &args.layout {
+        analyzer.set_layout(HudLayout::from_file(path)?);
+    }
+
+    let mut files = vec![];
+    for path in args.screenshots.iter() {
+        if path.is_dir() {
+            let replay = FrameReplay::from_dir(path).map_err(|err| err.to_string())?;
+            files.extend(replay.frames().iter().cloned());
+        } else {
+            files.push(path.clone());
+        }
+    }
+
+    let mut error = None;
+    FrameReplay::from_files(files)
+        .run(&mut analyzer, &logger, |path, analyzer| {
+            if args.glyphs {
+                let plates = analyzer
+                    .frame()
+                    .map(|frame| frame.name_plates(&config))
+                    .unwrap_or_default();
+                for (index, plate) in plates.iter().enumerate() {
+                    let bounds = plate.to_bounds();
+                    println!(
+                        "// {} name plate {} at {},{}\n{}",
+                        path.to_string_lossy(),
+                        index,
+                        bounds.x,
+                        bounds.y,
+                        GlyphAtlas::flyff_ui().cut_glyphs(plate)
+                    );
+                }
+                return;
+            }
+            let stats = &analyzer.client_stats;
+            let (width, height) = analyzer.image_size().unwrap_or_default();
+            let output = FrameOutput {
+                file: path.to_string_lossy().to_string(),
+                width,
+                height,
+                mobs: analyzer
+                    .identify_mobs(&config)
+                    .iter()
+                    .map(MobOutput::from)
+                    .collect(),
+                target_marker: stats.target_marker.map(|marker| marker.bounds),
+                target_distance: stats.target_distance,
+                hp: (&stats.hp).into(),
+                mp: (&stats.mp).into(),
+                fp: (&stats.fp).into(),
+                target_hp: (&stats.target_hp).into(),
+                target_mp: (&stats.target_mp).into(),
+                is_alive: format!("{:?}", stats.is_alive),
+            };
+            let json = match args.pretty {
+                true => serde_json::to_string_pretty(&output),
+                false => serde_json::to_string(&output),
+            };
+            match json {
+                Ok(json) => println!("{}", json),
+                Err(err) => error = Some(err.to_string()),
+            }
+        })
+        .map_err(|err| err.to_string())?;
+
+    error.map_or(Ok(()), Err)
+}
+
+fn main() {
+    let result = parse_args(env::args().skip(1)).and_then(|args| match args.help {
+        true => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        false => run(args),
+    });
+    if let Err(err) = result {
+        eprintln!("neuz-analyze: {}\n\n{}", err, USAGE);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf};
+
+    use serde_json::json;
+
+    use super::{load_farming_config, parse_args, parse_option_value, Args};
+
+    fn args(line: &str) -> Result<Args, String> {
+        parse_args(line.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let parsed = args("--config bot.json --passive-tolerence 5 --pretty a.png shots").unwrap();
+        assert!(!parsed.glyphs);
+        assert_eq!(parsed.config, Some(PathBuf::from("bot.json")));
+        assert_eq!(
+            parsed.overrides,
+            vec![("passive_tolerence".to_string(), json!(5))]
+        );
+        assert!(parsed.pretty);
+        assert_eq!(
+            parsed.screenshots,
+            vec![PathBuf::from("a.png"), PathBuf::from("shots")]
+        );
+
+        assert!(args("--help").unwrap().help);
+        assert!(args("--glyphs a.png").unwrap().glyphs);
+        assert!(args("a.png --unknown-option 1").is_err());
+        assert!(args("a.png --passive-tolerence").is_err());
+        assert!(args("a.png --passive-tolerence 1,2").is_err());
+        assert!(args("--pretty").is_err());
+    }
+
+    #[test]
+    fn test_parse_option_value() {
+        assert_eq!(parse_option_value("12"), Some(json!(12)));
+        assert_eq!(
+            parse_option_value("182, 144,146"),
+            Some(json!([182, 144, 146]))
+        );
+        assert_eq!(parse_option_value("Hsv"), Some(json!("Hsv")));
+        assert_eq!(parse_option_value("182,144"), None);
+        assert_eq!(parse_option_value("300,0,0"), None);
+        assert_eq!(parse_option_value("-1"), None);
+    }
+
+    #[test]
+    fn test_load_farming_config() {
+        let path = env::temp_dir().join("neuz_analyze_test.botconfig");
+        fs::write(
+            &path,
+            json!({ "farming_config": { "passive_tolerence": 3, "aggressive_tolerence": 7 } })
+                .to_string(),
+        )
+        .unwrap();
+        let parsed = Args {
+            config: Some(path.clone()),
+            overrides: vec![("passive_tolerence".to_string(), json!(5))],
+            ..Args::default()
+        };
+        let config = load_farming_config(&parsed);
+        fs::remove_file(&path).unwrap();
+
+        let config = config.unwrap();
+        assert_eq!(config.passive_tolerence(), 5);
+        assert_eq!(config.aggressive_tolerence(), 7);
+
+        assert!(load_farming_config(&Args::default()).is_ok());
+        let invalid = Args {
+            overrides: vec![("passive_tolerence".to_string(), json!("many"))],
+            ..Args::default()
+        };
+        assert!(load_farming_config(&invalid).is_err());
+    }
+}
@@ -1,5 +1,5 @@
 mod target;
-pub use self::target::{MobType, Target, TargetType};
+pub use self::target::{MobName, MobType, Target, TargetType};
 
 mod bounds;
 pub use self::bounds::Bounds;
@@ -8,10 +8,14 @@ mod point;
 pub use self::point::Point;
 
 mod point_cloud;
-pub use self::point_cloud::{point_selector, PointCloud};
+pub use self::point_cloud::{Cluster, Neighbourhood, PointCloud};
+
+mod minimap;
+pub use self::minimap::{Minimap, MinimapDot, MinimapDotKind};
 
 mod stats_info;
 pub use self::stats_info::ClientStats;
 pub use self::stats_info::AliveState;
+pub use self::stats_info::{StatInfo, StatusBarConfig, StatusBarKind};
 
 mod pixel_detection;
@@ -0,0 +1,176 @@
+use super::{Bounds, Neighbourhood, Point, PointCloud};
+
+/// Dots smaller than this are noise
+const MIN_DOT_PIXELS: usize = 2;
+
+/// What a dot on the minimap stands for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MinimapDotKind {
+    Mob,
+    PartyMember,
+}
+
+/// A dot drawn on the minimap around the player.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MinimapDot {
+    pub kind: MinimapDotKind,
+    /// Offset from the player in minimap pixels, `x` to the east and `y` to the south
+    pub offset: (i32, i32),
+}
+
+impl MinimapDot {
+    /// Distance from the player in minimap pixels.
+    pub fn distance(&self) -> f32 {
+        (self.offset.0 as f32).hypot(self.offset.1 as f32)
+    }
+
+    /// Direction from the player in degrees clockwise from north.
+    pub fn bearing(&self) -> f32 {
+        bearing(self.offset.0 as f32, self.offset.1 as f32)
+    }
+}
+
+/// Where the player looks and what's around, as read from the minimap.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Minimap {
+    pub bounds: Bounds,
+    /// Position of the player arrow in the frame
+    pub player: Point,
+    /// Direction the player arrow points to, in degrees clockwise from north
+    pub heading: f32,
+    pub dots: Vec<MinimapDot>,
+}
+
+impl Minimap {
+    /// Read the minimap from the pixels of the player arrow and of the dots. `None` without
+    /// an arrow, e.g. when the minimap is closed.
+    pub fn from_clouds(
+        bounds: Bounds,
+        arrow: &PointCloud,
+        mobs: &PointCloud,
+        party_members: &PointCloud,
+    ) -> Option<Self> {
+        if arrow.len() < MIN_DOT_PIXELS {
+            return None;
+        }
+
+        // The tip is the part of the arrow furthest from its center
+        let (center_x, center_y) = centroid(arrow.as_ref().iter());
+        let distance =
+            |point: &&Point| (point.x as f32 - center_x).hypot(point.y as f32 - center_y);
+        let tip_distance = arrow
+            .as_ref()
+            .iter()
+            .map(|point| distance(&point))
+            .fold(0.0, f32::max);
+        let (tip_x, tip_y) = centroid(
+            arrow
+                .as_ref()
+                .iter()
+                .filter(|point| distance(point) >= tip_distance - 1.0),
+        );
+        let player = Point::new(center_x.round() as u32, center_y.round() as u32);
+
+        let dots = [
+            (MinimapDotKind::Mob, mobs),
+            (MinimapDotKind::PartyMember, party_members),
+        ]
+        .into_iter()
+        .flat_map(|(kind, cloud)| {
+            cloud
+                .cluster_connected(Neighbourhood::new(2, 2), MIN_DOT_PIXELS)
+                .into_iter()
+                .map(move |cluster| {
+                    let (x, y) = centroid(cluster.points.as_ref().iter());
+                    MinimapDot {
+                        kind,
+                        offset: ((x - center_x).round() as i32, (y - center_y).round() as i32),
+                    }
+                })
+        })
+        .collect();
+
+        Some(Self {
+            bounds,
+            player,
+            heading: bearing(tip_x - center_x, tip_y - center_y),
+            dots,
+        })
+    }
+
+    /// The closest dot of a kind.
+    pub fn nearest(&self, kind: MinimapDotKind) -> Option<&MinimapDot> {
+        self.dots
+            .iter()
+            .filter(|dot| dot.kind == kind)
+            .min_by(|a, b| a.distance().total_cmp(&b.distance()))
+    }
+
+    /// Degrees to turn to face `dot`, negative to the left.
+    pub fn turn_towards(&self, dot: &MinimapDot) -> f32 {
+        (dot.bearing() - self.heading + 540.0).rem_euclid(360.0) - 180.0
+    }
+}
+
+fn centroid<'a>(points: impl Iterator<Item = &'a Point>) -> (f32, f32) {
+    let (mut x, mut y, mut count) = (0.0, 0.0, 0.0);
+    for point in points {
+        x += point.x as f32;
+        y += point.y as f32;
+        count += 1.0;
+    }
+    (x / count, y / count)
+}
+
+/// Degrees clockwise from north of the offset `(x, y)`, with `y` pointing south.
+fn bearing(x: f32, y: f32) -> f32 {
+    x.atan2(-y).to_degrees().rem_euclid(360.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::{Bounds, Minimap, MinimapDotKind, Point, PointCloud};
+
+    #[test]
+    fn test_read_minimap() {
+        // An arrow pointing east at (50, 50), a mob north-east and one south, a party member
+        // to the west
+        let mut arrow = vec![];
+        for x in 45..=55 {
+            let half_width = (55 - x) / 3;
+            for y in (50 - half_width)..=(50 + half_width) {
+                arrow.push(Point::new(x, y));
+            }
+        }
+        let dot = |x: u32, y: u32| vec![Point::new(x, y), Point::new(x + 1, y)];
+        let mobs = PointCloud::new([dot(70, 30), dot(49, 90)].concat());
+        let party_members = PointCloud::new(dot(20, 50));
+
+        let minimap = Minimap::from_clouds(
+            Bounds::new(0, 0, 100, 100),
+            &PointCloud::new(arrow),
+            &mobs,
+            &party_members,
+        )
+        .unwrap();
+        assert!((minimap.heading - 90.0).abs() < 1.0);
+        assert_eq!(minimap.dots.len(), 3);
+
+        let mob = minimap.nearest(MinimapDotKind::Mob).unwrap();
+        assert!((mob.bearing() - 45.0).abs() < 5.0);
+        // North-east is to the left when looking east
+        assert!((minimap.turn_towards(mob) + 45.0).abs() < 5.0);
+
+        let leader = minimap.nearest(MinimapDotKind::PartyMember).unwrap();
+        assert!((minimap.turn_towards(leader).abs() - 180.0).abs() < 5.0);
+
+        // Without an arrow the minimap is closed
+        assert!(Minimap::from_clouds(
+            Bounds::new(0, 0, 100, 100),
+            &PointCloud::default(),
+            &mobs,
+            &party_members,
+        )
+        .is_none());
+    }
+}
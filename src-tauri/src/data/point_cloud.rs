@@ -1,16 +1,49 @@
+use std::collections::HashMap;
+
 use super::{Bounds, Point};
 
-pub mod point_selector {
-    use crate::data::Point;
+/// Maximum distance between two connected points of a cluster, per axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Neighbourhood {
+    pub x: u32,
+    pub y: u32,
+}
 
-    /// Select points on x axis
-    pub fn x_axis(point: &Point) -> u32 {
-        point.x
+impl Neighbourhood {
+    pub const fn new(x: u32, y: u32) -> Self {
+        Self { x, y }
     }
 
-    /// Select points on y axis
-    pub fn y_axis(point: &Point) -> u32 {
-        point.y
+    /// Check whether two points are direct neighbours.
+    #[inline]
+    pub fn connects(&self, a: &Point, b: &Point) -> bool {
+        a.x.abs_diff(b.x) <= self.x && a.y.abs_diff(b.y) <= self.y
+    }
+}
+
+/// A cluster of connected points.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cluster {
+    pub points: PointCloud,
+    pub bounds: Bounds,
+}
+
+impl Cluster {
+    fn new(points: PointCloud) -> Self {
+        let bounds = points.to_bounds();
+        Self { points, bounds }
+    }
+
+    /// Number of pixels in the cluster.
+    pub fn pixel_count(&self) -> usize {
+        self.points.len()
+    }
+
+    /// Share of the bounding box covered by pixels of the cluster, `0.0..=1.0`.
+    #[allow(dead_code)]
+    pub fn density(&self) -> f32 {
+        let area = (self.bounds.w as usize + 1) * (self.bounds.h as usize + 1);
+        self.pixel_count() as f32 / area as f32
     }
 }
 
@@ -38,20 +71,8 @@ impl PointCloud {
         self.points.is_empty()
     }
 
-    fn sort_by<Selector>(&mut self, selector: Selector)
-    where
-        Selector: Fn(&Point) -> u32,
-    {
-        self.points.sort_by_key(|point| selector(point));
-    }
-
-    fn sorted_by<Selector>(&self, selector: Selector) -> Self
-    where
-        Selector: Fn(&Point) -> u32,
-    {
-        let mut copy = self.clone();
-        copy.sort_by(selector);
-        copy
+    pub fn len(&self) -> usize {
+        self.points.len()
     }
 
     pub fn to_bounds(&self) -> Bounds {
@@ -70,41 +91,67 @@ impl PointCloud {
         }
     }
 
-    pub fn cluster_by_distance<F>(&self, distance: u32, selector: F) -> Vec<PointCloud>
-    where
-        F: Fn(&Point) -> u32,
-    {
-        // Sort points by axis selector
-        let points = self.sorted_by(&selector);
-        let mut clusters = vec![PointCloud::default()];
-
-        // Iterate over points and build clusters
-        for point in points {
-            // Fill default cluster first
-            let last_cluster = clusters.last_mut().unwrap();
-            if last_cluster.is_empty() {
-                last_cluster.push(point);
-                continue;
-            }
+    /// Group points into connected components: two points belong to the same cluster if
+    /// they're linked by a chain of points that are neighbours of each other.
+    /// Clusters with less than `min_points` points are dropped as noise.
+    /// Clusters are ordered from top to bottom, then left to right.
+    pub fn cluster_connected(&self, neighbourhood: Neighbourhood, min_points: usize) -> Vec<Cluster> {
+        // Bucket points into grid cells of the neighbourhood size, so neighbours of a point
+        // can only be found in the surrounding cells
+        let cell_w = neighbourhood.x + 1;
+        let cell_h = neighbourhood.y + 1;
+        let cell_of = |point: &Point| (point.x / cell_w, point.y / cell_h);
+        let mut grid: HashMap<(u32, u32), Vec<usize>> = HashMap::new();
+        for (index, point) in self.points.iter().enumerate() {
+            grid.entry(cell_of(point)).or_default().push(index);
+        }
 
-            // Select coordinates of last pointer in active cluster and current point
-            let last_coord = selector(last_cluster.points.last().unwrap());
-            let curr_coord = selector(&point);
+        // Union-find over point indices
+        let mut parents: Vec<usize> = (0..self.points.len()).collect();
+        fn find(parents: &mut [usize], mut index: usize) -> usize {
+            while parents[index] != index {
+                parents[index] = parents[parents[index]];
+                index = parents[index];
+            }
+            index
+        }
 
-            // Decide whether to include the point in the current cluster or create a new one
-            if curr_coord.abs_diff(last_coord) <= distance {
-                last_cluster.push(point);
-            } else {
-                clusters.push(PointCloud::default());
-                clusters.last_mut().unwrap().push(point);
+        for (index, point) in self.points.iter().enumerate() {
+            let (cell_x, cell_y) = cell_of(point);
+            for neighbour_x in cell_x.saturating_sub(1)..=cell_x + 1 {
+                for neighbour_y in cell_y.saturating_sub(1)..=cell_y + 1 {
+                    let candidates = match grid.get(&(neighbour_x, neighbour_y)) {
+                        Some(candidates) => candidates,
+                        None => continue,
+                    };
+                    for &other in candidates {
+                        if other <= index || !neighbourhood.connects(point, &self.points[other]) {
+                            continue;
+                        }
+                        let root_a = find(&mut parents, index);
+                        let root_b = find(&mut parents, other);
+                        if root_a != root_b {
+                            parents[root_b] = root_a;
+                        }
+                    }
+                }
             }
         }
 
-        // Return clusters or empty vector if no clusters were created
-        match &clusters[..] {
-            [n] if n.points.is_empty() => Vec::default(),
-            _ => clusters,
+        // Collect components
+        let mut components: HashMap<usize, PointCloud> = HashMap::new();
+        for (index, point) in self.points.iter().enumerate() {
+            let root = find(&mut parents, index);
+            components.entry(root).or_default().push(*point);
         }
+
+        let mut clusters = components
+            .into_values()
+            .filter(|points| points.len() >= min_points.max(1))
+            .map(Cluster::new)
+            .collect::<Vec<_>>();
+        clusters.sort_by_key(|cluster| (cluster.bounds.y, cluster.bounds.x));
+        clusters
     }
 }
 
@@ -139,20 +186,41 @@ where
 
 #[cfg(test)]
 mod tests {
-    use crate::data::{point_selector, Bounds, Point, PointCloud};
+    use crate::data::{Bounds, Neighbourhood, Point, PointCloud};
 
     #[test]
-    fn test_cluster_by_distance() {
-        let distance = 5;
-        let points: Vec<Point> = [(0, 0), (9, 1), (5, 1), (15, 5), (17, 3)]
-            .into_iter()
-            .map(|x| x.into())
-            .collect();
-        let cloud = PointCloud::new(points);
-        let clusters = cloud.cluster_by_distance(distance, point_selector::x_axis);
+    fn test_cluster_connected() {
+        // Two names side by side with a 20px gap, the first one has a 6px gap between glyphs
+        let mut points = vec![];
+        for x in (0..10).chain(16..30) {
+            points.push((x, 0));
+            points.push((x, 1));
+        }
+        for x in 50..60 {
+            points.push((x, 1));
+        }
+        // A stray pixel
+        points.push((100, 40));
+
+        let clusters = PointCloud::from(points).cluster_connected(Neighbourhood::new(8, 2), 2);
         assert_eq!(clusters.len(), 2);
-        assert_eq!(clusters[0], PointCloud::from([(0, 0), (5, 1), (9, 1)]));
-        assert_eq!(clusters[1], PointCloud::from([(15, 5), (17, 3)]));
+        assert_eq!(clusters[0].bounds.x, 0);
+        assert_eq!(clusters[0].bounds.w, 29);
+        assert_eq!(clusters[0].pixel_count(), 48);
+        assert_eq!(clusters[1].bounds.x, 50);
+        assert_eq!(clusters[1].bounds.w, 9);
+        assert_eq!(clusters[1].pixel_count(), 10);
+        assert_eq!(clusters[1].density(), 1.0);
+
+        // Order along an axis doesn't matter: a diagonal chain is a single cluster
+        let cloud = PointCloud::from([(0, 0), (10, 10), (5, 5), (15, 15)]);
+        let clusters = cloud.cluster_connected(Neighbourhood::new(5, 5), 1);
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters[0].density(), 4.0 / 256.0);
+
+        assert!(PointCloud::default()
+            .cluster_connected(Neighbourhood::new(5, 5), 1)
+            .is_empty());
     }
 
     #[test]
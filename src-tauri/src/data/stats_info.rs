@@ -1,10 +1,19 @@
-use std::{ fmt, time::Instant };
+use std::{ fmt, sync::Arc, time::Instant };
 
 use slog::Logger;
-use tauri::Window;
 
-use super::{ PointCloud, Target };
-use crate::{ image_analyzer::{ Color, ImageAnalyzer }, platform::{ eval_send_key, KeyMode } };
+use super::{ Bounds, PointCloud, Target };
+use crate::{
+    image_analyzer::{ BarColors, Color, Frame },
+    ipc::StatValue,
+    layout::HudRegions,
+    ocr::GlyphAtlas,
+    platform::{ InputSink, KeyMode },
+    utils::Clock,
+};
+
+/// Rows above and below a bar that may still contain its text
+const STAT_TEXT_PADDING: u32 = 4;
 
 #[derive(Debug, Default, Clone, Copy)]
 pub enum StatusBarKind {
@@ -35,6 +44,13 @@ impl fmt::Display for StatusBarKind {
     }
 }
 
+impl StatusBarKind {
+    /// Whether the bar shows its exact `current/max` value as text.
+    pub fn has_text(&self) -> bool {
+        matches!(self, StatusBarKind::Hp | StatusBarKind::Mp | StatusBarKind::Fp)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ClientStats {
     pub has_tray_open: bool,
@@ -51,18 +67,24 @@ pub struct ClientStats {
     pub target_distance: Option<i32>,
     pub is_alive: AliveState,
     pub stat_try_not_detected_count: i32,
-    window: Window,
+    /// Whether the missing exact HP/MP/FP values were already reported
+    unreadable_numbers_logged: bool,
+    input: Option<Arc<dyn InputSink>>,
+    clock: Arc<dyn Clock>,
 }
 impl ClientStats {
-    pub fn new(window: Window) -> Self {
+    /// Create stats tracking for a client. Without `input` the stat tray is never reopened,
+    /// which is what replayed frames need.
+    pub fn new(input: Option<Arc<dyn InputSink>>, clock: Arc<dyn Clock>) -> Self {
+        let now = clock.now();
         Self {
             has_tray_open: false,
-            hp: StatInfo::new(0, 100, StatusBarKind::Hp, None),
-            mp: StatInfo::new(0, 100, StatusBarKind::Mp, None),
-            fp: StatInfo::new(0, 100, StatusBarKind::Fp, None),
+            hp: StatInfo::new(0, 100, StatusBarKind::Hp, None, now),
+            mp: StatInfo::new(0, 100, StatusBarKind::Mp, None, now),
+            fp: StatInfo::new(0, 100, StatusBarKind::Fp, None, now),
             is_alive: AliveState::StatsTrayClosed,
-            target_hp: StatInfo::new(0, 0, StatusBarKind::TargetHP, None),
-            target_mp: StatInfo::new(0, 0, StatusBarKind::TargetMP, None),
+            target_hp: StatInfo::new(0, 0, StatusBarKind::TargetHP, None, now),
+            target_mp: StatInfo::new(0, 0, StatusBarKind::TargetMP, None, now),
             target_is_mover: false,
             target_is_npc: false,
             target_is_alive: false,
@@ -71,18 +93,21 @@ impl ClientStats {
             target_distance: None,
 
             stat_try_not_detected_count: 0,
-            window,
+            unreadable_numbers_logged: false,
+            input,
+            clock,
         }
     }
 
     // update all bars values at once
-    pub fn update(&mut self, image: &ImageAnalyzer, _logger: &Logger) {
+    pub fn update(&mut self, frame: &Frame, logger: &Logger) {
+        let now = self.clock.now();
         let _should_debug = [
-            self.hp.update_value(image),
-            self.mp.update_value(image),
-            self.fp.update_value(image),
-            self.target_hp.update_value(image),
-            self.target_mp.update_value(image),
+            self.hp.update_value(frame, now),
+            self.mp.update_value(frame, now),
+            self.fp.update_value(frame, now),
+            self.target_hp.update_value(frame, now),
+            self.target_mp.update_value(frame, now),
         ];
         self.has_tray_open = self.detect_stat_tray();
         self.is_alive = {
@@ -94,19 +119,26 @@ impl ClientStats {
                 AliveState::Dead
             }
         };
+        if self.has_tray_open && self.hp.current.is_none() && !self.unreadable_numbers_logged {
+            self.unreadable_numbers_logged = true;
+            slog::warn!(
+                logger,
+                "Cannot read exact stat values, absolute slot thresholds are estimated from the bars"
+            );
+        }
         self.target_is_npc = self.target_hp.value == 100 && self.target_mp.value == 0;
         self.target_is_mover = self.target_mp.value > 0;
         self.target_is_alive = self.target_hp.value > 0;
-        let blue_target = image.identify_target_marker(true);
+        let blue_target = frame.identify_target_marker(true);
         let target = if blue_target.is_some() {
             blue_target
         } else {
-            image.identify_target_marker(false)
+            frame.identify_target_marker(false)
         };
         self.target_marker = target;
         self.target_on_screen = target.is_some();
         if self.target_on_screen {
-            self.target_distance = Some(image.get_target_marker_distance(target.unwrap()));
+            self.target_distance = Some(frame.get_target_marker_distance(target.unwrap()));
         } else {
             self.target_distance = None;
         }
@@ -119,6 +151,17 @@ impl ClientStats {
         }*/
     }
 
+    /// Take over stats published by the frame pipeline. Update times reset since the last
+    /// frame are kept if they are more recent than the published ones.
+    pub fn replace_with(&mut self, mut newer: ClientStats) {
+        newer.hp.keep_later_update(&self.hp);
+        newer.mp.keep_later_update(&self.mp);
+        newer.fp.keep_later_update(&self.fp);
+        newer.target_hp.keep_later_update(&self.target_hp);
+        newer.target_mp.keep_later_update(&self.target_mp);
+        *self = newer;
+    }
+
     // Detect whether we can read or not stat_tray and open it if needed
     pub fn detect_stat_tray(&mut self) -> bool {
         // Since HP/MP/FP are 0 we know bar should be hidden
@@ -128,7 +171,9 @@ impl ClientStats {
                 self.stat_try_not_detected_count = 0;
 
                 // Try to open char stat tray
-                eval_send_key(&self.window, "T", KeyMode::Press);
+                if let Some(input) = &self.input {
+                    input.send_key("T", KeyMode::Press);
+                }
             }
             false
         } else {
@@ -157,6 +202,10 @@ pub struct StatInfo {
     pub stat_kind: StatusBarKind,
     pub last_value: u32,
     pub last_update_time: Option<Instant>,
+    /// Exact value read from the bar text, if it was readable
+    pub current: Option<u32>,
+    /// Maximum read from the last readable bar text
+    pub max: Option<u32>,
 }
 
 impl PartialEq for StatInfo {
@@ -176,53 +225,64 @@ impl StatInfo {
         max_w: u32,
         value: u32,
         stat_kind: StatusBarKind,
-        image: Option<&ImageAnalyzer>
+        frame: Option<&Frame>,
+        now: Instant
     ) -> Self {
         let mut res = Self {
             max_w,
             value,
             stat_kind,
-            last_update_time: Some(Instant::now()),
+            last_update_time: Some(now),
             last_value: 100,
+            current: None,
+            max: None,
         };
-        if let Some(image) = image {
-            res.update_value(image);
+        if let Some(frame) = frame {
+            res.update_value(frame, now);
         }
 
         res
     }
 
-    pub fn reset_last_update_time(&mut self) {
-        self.last_update_time = Some(Instant::now());
+    pub fn reset_last_update_time(&mut self, now: Instant) {
+        self.last_update_time = Some(now);
     }
 
-    pub fn update_value(&mut self, image: &ImageAnalyzer) -> bool {
-        let status_bar_config: StatusBarConfig = self.stat_kind.into();
-        let recv = image.pixel_detection(
-            status_bar_config.refs,
-            status_bar_config.min_x,
-            status_bar_config.min_y,
-            status_bar_config.max_x,
-            status_bar_config.max_y,
-            Some(2)
-        );
+    fn keep_later_update(&mut self, other: &StatInfo) {
+        self.last_update_time = self.last_update_time.max(other.last_update_time);
+    }
 
-        // Receive points from channel
-        let cloud = {
-            let mut cloud = PointCloud::default();
-            while let Ok(point) = recv.recv() {
-                cloud.push(point);
-            }
-            cloud
-        };
+    /// Update the value from `frame`, captured at `now`.
+    pub fn update_value(&mut self, frame: &Frame, now: Instant) -> bool {
+        let status_bar_config = StatusBarConfig::from_layout(
+            self.stat_kind,
+            frame.hud(),
+            &frame.bar_colors()
+        );
 
         // Calculate bounds
-        let bounds = cloud.to_bounds();
+        let bounds = frame.scan().status_bar(self.stat_kind).to_bounds();
 
         // Recalculate value tracking info
         let updated_max_w = bounds.w.max(self.max_w);
-        let value_frac = (bounds.w as f32) / (updated_max_w as f32);
-        let updated_value = ((value_frac * 100_f32) as u32).max(0).min(100);
+
+        // Prefer the exact numbers, the bar width is only accurate to a pixel
+        let numbers = match self.stat_kind.has_text() && bounds.w > 0 {
+            true => self.read_numbers(frame, &status_bar_config, bounds.y, bounds.h, updated_max_w),
+            false => None,
+        };
+        self.current = numbers.map(|(current, _)| current);
+        if let Some((_, max)) = numbers {
+            self.max = Some(max);
+        }
+
+        let updated_value = match numbers {
+            Some((current, max)) => ((current as u64) * 100 / (max as u64)) as u32,
+            None => {
+                let value_frac = (bounds.w as f32) / (updated_max_w as f32);
+                ((value_frac * 100_f32) as u32).max(0).min(100)
+            }
+        };
 
         let (old_max_w, old_value) = (self.max_w, self.value);
 
@@ -231,12 +291,58 @@ impl StatInfo {
         }
         if updated_value != old_value {
             self.value = updated_value;
-            self.last_update_time = Some(Instant::now());
+            self.last_update_time = Some(now);
             true
         } else {
             false
         }
     }
+
+    /// Value as compared against slot thresholds.
+    pub fn stat_value(&self) -> StatValue {
+        StatValue {
+            percent: self.value,
+            current: self.current,
+            max: self.max,
+        }
+    }
+
+    /// Read the `current/max` text drawn over the bar found at rows `y..=y + h`.
+    fn read_numbers(
+        &self,
+        frame: &Frame,
+        config: &StatusBarConfig,
+        y: u32,
+        h: u32,
+        max_w: u32
+    ) -> Option<(u32, u32)> {
+        let (min_x, max_x) = (config.min_x, (config.min_x + max_w + 1).min(config.max_x));
+        let (min_y, max_y) = (y.saturating_sub(STAT_TEXT_PADDING), y + h + STAT_TEXT_PADDING);
+        let cloud = PointCloud::new(
+            frame
+                .scan()
+                .stat_text.as_ref()
+                .iter()
+                .filter(|point| {
+                    point.x >= min_x && point.x < max_x && point.y >= min_y && point.y <= max_y
+                })
+                .copied()
+                .collect::<Vec<_>>()
+        );
+
+        parse_stat_text(&GlyphAtlas::flyff_ui().read_text(&cloud))
+    }
+}
+
+/// Parse bar text like `1234/5678` into the current and maximum value.
+pub fn parse_stat_text(text: &str) -> Option<(u32, u32)> {
+    let (current, max) = text.split_once('/')?;
+    let current = current.trim().parse::<u32>().ok()?;
+    let max = max.trim().parse::<u32>().ok()?;
+    match max > 0 && current <= max {
+        true => Some((current, max)),
+        false => None,
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -249,91 +355,29 @@ pub struct StatusBarConfig {
 }
 
 impl StatusBarConfig {
-    pub fn new(colors: [[u8; 3]; 4]) -> Self {
+    pub fn new(colors: [[u8; 3]; 4], region: Bounds) -> Self {
         Self {
             refs: colors
                 .iter()
                 .map(|v| Color::new(v[0], v[1], v[2]))
                 .collect(),
-            ..Default::default()
+            min_x: region.x,
+            min_y: region.y,
+            max_x: region.x + region.w,
+            max_y: region.y + region.h,
         }
     }
-}
 
-impl From<StatusBarKind> for StatusBarConfig {
-    fn from(kind: StatusBarKind) -> Self {
+    /// Colors of a bar and the region it is searched in.
+    pub fn from_layout(kind: StatusBarKind, hud: &HudRegions, colors: &BarColors) -> Self {
         use StatusBarKind::*;
 
-        match kind {
-            Hp => {
-                StatusBarConfig::new([
-                    [174, 18, 55],
-                    [188, 24, 62],
-                    [204, 30, 70],
-                    [220, 36, 78],
-                ])
-            }
-
-            Mp =>
-                StatusBarConfig::new([
-                    [20, 84, 196],
-                    [36, 132, 220],
-                    [44, 164, 228],
-                    [56, 188, 232],
-                ]),
-            Fp => {
-                StatusBarConfig::new([
-                    [45, 230, 29],
-                    [28, 172, 28],
-                    [44, 124, 52],
-                    [20, 146, 20],
-                ])
-            }
-
-            TargetHP => {
-                let mut target_hp_bar = StatusBarConfig::new([
-                    [174, 18, 55],
-                    [188, 24, 62],
-                    [204, 30, 70],
-                    [220, 36, 78],
-                ]);
-                target_hp_bar.min_x = 300;
-                target_hp_bar.min_y = 30;
-
-                target_hp_bar.max_x = 550;
-                target_hp_bar.max_y = 60;
-
-                target_hp_bar
-            }
-
-            TargetMP => {
-                let mut target_mp_bar = StatusBarConfig::new([
-                    [20, 84, 196],
-                    [36, 132, 220],
-                    [44, 164, 228],
-                    [56, 188, 232],
-                ]);
-                target_mp_bar.min_x = 300;
-                target_mp_bar.min_y = 50;
-
-                target_mp_bar.max_x = 550;
-                target_mp_bar.max_y = 60;
-
-                target_mp_bar
-            }
-        }
-    }
-}
-
-impl Default for StatusBarConfig {
-    fn default() -> Self {
-        Self {
-            max_x: 225,
-            max_y: 110,
-            min_x: 105,
-            min_y: 30,
-            refs: vec![],
-        }
+        let region = match kind {
+            Hp | Mp | Fp => hud.stat_tray,
+            TargetHP => hud.target_hp_bar,
+            TargetMP => hud.target_mp_bar,
+        };
+        StatusBarConfig::new(colors.of(kind), region)
     }
 }
 
@@ -343,3 +387,18 @@ impl PartialEq for StatusBarConfig {
         self.max_x == other.max_x
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::parse_stat_text;
+
+    #[test]
+    fn test_parse_stat_text() {
+        assert_eq!(parse_stat_text("617/1000"), Some((617, 1000)));
+        assert_eq!(parse_stat_text("0 / 83"), Some((0, 83)));
+        assert_eq!(parse_stat_text("617/?000"), None);
+        assert_eq!(parse_stat_text("84/83"), None);
+        assert_eq!(parse_stat_text("0/0"), None);
+        assert_eq!(parse_stat_text("1000"), None);
+    }
+}
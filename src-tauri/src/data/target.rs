@@ -1,6 +1,13 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
 use super::{Bounds, Point};
+use crate::ocr::UNKNOWN_CHAR;
+
+const MOB_NAME_MAX_LEN: usize = 31;
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum MobType {
     Passive,
     Aggressive,
@@ -14,14 +21,64 @@ pub enum TargetType {
     TargetMarker,
 }
 
+/// Name read from a mob name plate.
+/// Stored inline so `Target` stays `Copy`, longer names are truncated.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct MobName {
+    len: u8,
+    bytes: [u8; MOB_NAME_MAX_LEN],
+}
+
+impl MobName {
+    pub fn new(name: &str) -> Self {
+        let mut len = name.len().min(MOB_NAME_MAX_LEN);
+        while !name.is_char_boundary(len) {
+            len -= 1;
+        }
+        let mut bytes = [0; MOB_NAME_MAX_LEN];
+        bytes[..len].copy_from_slice(&name.as_bytes()[..len]);
+        Self {
+            len: len as u8,
+            bytes,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        std::str::from_utf8(&self.bytes[..self.len as usize]).unwrap_or_default()
+    }
+}
+
+impl fmt::Debug for MobName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl fmt::Display for MobName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
 /// A target in 2D space.
 #[derive(Debug, Clone, Copy, Default)]
 pub struct Target {
     pub target_type: TargetType,
     pub bounds: Bounds,
+    /// Name read from the name plate, if it could be read
+    pub name: Option<MobName>,
+    /// Level read from the name plate, if it could be read
+    pub level: Option<u32>,
 }
 
 impl Target {
+    /// Whether every character of the name plate could be read.
+    pub fn has_readable_name(&self) -> bool {
+        self.name
+            .map(|name| !name.as_str().contains(UNKNOWN_CHAR))
+            .unwrap_or(false)
+    }
+
     /// Get the approximated attack coordinates.
     pub fn get_attack_coords(&self) -> Point {
         let point = self.bounds.get_lowest_center_point();
@@ -0,0 +1,263 @@
+//! Annotated copies of analyzed frames, to see what the bot detected and decided.
+
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+use guard::guard;
+use image::{Rgba, RgbaImage};
+use slog::Logger;
+
+use crate::{
+    data::{Bounds, MobType, Point, Target, TargetType},
+    image_analyzer::{Frame, ImageAnalyzer},
+    ipc::FarmingConfig,
+    ocr::GlyphAtlas,
+};
+
+const PASSIVE_COLOR: Rgba<u8> = Rgba([0, 220, 0, 255]);
+const AGGRESSIVE_COLOR: Rgba<u8> = Rgba([255, 40, 40, 255]);
+const VIOLET_COLOR: Rgba<u8> = Rgba([200, 60, 255, 255]);
+const AVOIDED_COLOR: Rgba<u8> = Rgba([255, 160, 0, 255]);
+const TARGET_MARKER_COLOR: Rgba<u8> = Rgba([0, 220, 255, 255]);
+const HUD_COLOR: Rgba<u8> = Rgba([60, 120, 255, 255]);
+const IGNORE_AREA_COLOR: Rgba<u8> = Rgba([110, 110, 110, 255]);
+const CLICK_COLOR: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const TEXT_COLOR: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const TEXT_BACKGROUND: Rgba<u8> = Rgba([0, 0, 0, 255]);
+
+/// What a behavior decided on the last frame, drawn on top of the detections.
+#[derive(Debug, Default, Clone)]
+pub struct FrameAnnotations {
+    /// Name of the current state
+    pub state: Option<String>,
+    /// Areas where no mob is searched right now
+    pub avoided_bounds: Vec<Bounds>,
+    /// Where the last attack was clicked
+    pub click_point: Option<Point>,
+}
+
+/// Draw the detections of `frame` and the `annotations` of a behavior on a copy of the frame.
+pub fn annotate_frame(
+    frame: &Frame,
+    mobs: &[Target],
+    target_marker: Option<&Target>,
+    annotations: &FrameAnnotations,
+) -> RgbaImage {
+    let mut image = frame.image().clone();
+
+    let hud = frame.hud();
+    for area in hud.mob_ignore_areas.iter() {
+        draw_rect(&mut image, area, IGNORE_AREA_COLOR, 1);
+    }
+    for bar in [hud.stat_tray, hud.target_hp_bar, hud.target_mp_bar]
+        .into_iter()
+        .chain(hud.minimap)
+        .chain(hud.flight_gauge)
+    {
+        draw_rect(&mut image, &bar, HUD_COLOR, 1);
+    }
+    for bounds in annotations.avoided_bounds.iter() {
+        draw_rect(&mut image, bounds, AVOIDED_COLOR, 1);
+    }
+    for mob in mobs {
+        let color = match mob.target_type {
+            TargetType::Mob(MobType::Aggressive) => AGGRESSIVE_COLOR,
+            TargetType::Mob(MobType::Violet) => VIOLET_COLOR,
+            _ => PASSIVE_COLOR,
+        };
+        draw_rect(&mut image, &mob.bounds, color, 2);
+    }
+    if let Some(marker) = target_marker {
+        draw_rect(
+            &mut image,
+            &marker.bounds.grow_by(4),
+            TARGET_MARKER_COLOR,
+            2,
+        );
+    }
+    if let Some(point) = annotations.click_point {
+        draw_cross(&mut image, point, CLICK_COLOR);
+    }
+    if let Some(state) = &annotations.state {
+        draw_text(&mut image, Point::new(4, 4), state);
+    }
+
+    image
+}
+
+/// Outline `bounds` with lines `thickness` pixels wide, clipped to the image.
+fn draw_rect(image: &mut RgbaImage, bounds: &Bounds, color: Rgba<u8>, thickness: u32) {
+    let right = bounds.x + bounds.w;
+    let bottom = bounds.y + bounds.h;
+    for offset in 0..thickness {
+        for x in bounds.x..=right {
+            put_pixel(image, x, bounds.y + offset, color);
+            put_pixel(image, x, bottom.saturating_sub(offset), color);
+        }
+        for y in bounds.y..=bottom {
+            put_pixel(image, bounds.x + offset, y, color);
+            put_pixel(image, right.saturating_sub(offset), y, color);
+        }
+    }
+}
+
+fn draw_cross(image: &mut RgbaImage, point: Point, color: Rgba<u8>) {
+    for offset in 0..=6 {
+        put_pixel(image, (point.x + offset).saturating_sub(3), point.y, color);
+        put_pixel(image, point.x, (point.y + offset).saturating_sub(3), color);
+    }
+}
+
+/// Write `text` at twice the size of the UI font, on a dark background.
+fn draw_text(image: &mut RgbaImage, pos: Point, text: &str) {
+    const SCALE: u32 = 2;
+    const PADDING: u32 = 2;
+
+    let atlas = GlyphAtlas::flyff_ui();
+    let (pixels, width) = atlas.render_text(text);
+    for y in 0..(atlas.height() * SCALE + PADDING * 2) {
+        for x in 0..(width * SCALE + PADDING * 2) {
+            put_pixel(image, pos.x + x, pos.y + y, TEXT_BACKGROUND);
+        }
+    }
+    for (x, y) in pixels {
+        for dy in 0..SCALE {
+            for dx in 0..SCALE {
+                let x = pos.x + PADDING + x * SCALE + dx;
+                let y = pos.y + PADDING + y * SCALE + dy;
+                put_pixel(image, x, y, TEXT_COLOR);
+            }
+        }
+    }
+}
+
+#[inline]
+fn put_pixel(image: &mut RgbaImage, x: u32, y: u32, color: Rgba<u8>) {
+    if x < image.width() && y < image.height() {
+        image.put_pixel(x, y, color);
+    }
+}
+
+/// Saves annotated frames to a directory, every few frames or when asked to.
+pub struct DebugFrameExporter {
+    dir: PathBuf,
+    /// Export every this many frames, never if 0
+    interval: u32,
+    frames_since_export: u32,
+    is_requested: Arc<AtomicBool>,
+    logger: Logger,
+}
+
+impl DebugFrameExporter {
+    pub fn new(dir: PathBuf, logger: Logger) -> Self {
+        Self {
+            dir,
+            interval: 0,
+            frames_since_export: 0,
+            is_requested: Arc::new(AtomicBool::new(false)),
+            logger,
+        }
+    }
+
+    pub fn set_interval(&mut self, interval: u32) {
+        self.interval = interval;
+    }
+
+    /// A flag which makes the next frame be exported once it's set.
+    pub fn request_flag(&self) -> Arc<AtomicBool> {
+        self.is_requested.clone()
+    }
+
+    /// Count a frame and tell whether it should be exported.
+    pub fn should_export(&mut self) -> bool {
+        self.frames_since_export += 1;
+        let is_due = self.interval > 0 && self.frames_since_export >= self.interval;
+        if self.is_requested.swap(false, Ordering::Relaxed) || is_due {
+            self.frames_since_export = 0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Annotate the current frame of `analyzer` and save it in the background.
+    pub fn export(
+        &self,
+        frame_id: u64,
+        analyzer: &ImageAnalyzer,
+        config: &FarmingConfig,
+        annotations: &FrameAnnotations,
+    ) {
+        guard!(let Some(frame) = analyzer.frame() else { return; });
+        // Name plates read for the behavior are kept by the frame, so this doesn't read them again
+        let mobs = frame.identify_mobs(config);
+        let target_marker = analyzer.client_stats.target_marker.as_ref();
+        let image = annotate_frame(frame, &mobs, target_marker, annotations);
+
+        let dir = self.dir.clone();
+        let logger = self.logger.clone();
+        thread::spawn(move || {
+            let path = dir.join(format!("frame_{:06}.png", frame_id));
+            let result = fs::create_dir_all(&dir)
+                .map_err(|err| err.to_string())
+                .and_then(|_| image.save(&path).map_err(|err| err.to_string()));
+            match result {
+                Ok(_) => {
+                    slog::debug!(logger, "Saved debug frame"; "path" => path.to_string_lossy().to_string())
+                }
+                Err(err) => slog::error!(logger, "Failed to save debug frame"; "error" => err),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use crate::{
+        data::{Bounds, MobType, Point, Target, TargetType},
+        debug_frames::{annotate_frame, FrameAnnotations, AGGRESSIVE_COLOR, CLICK_COLOR},
+        image_analyzer::{BarColors, Frame, MobColors},
+        layout::HudLayout,
+    };
+
+    #[test]
+    fn test_annotate_frame() {
+        let black = Rgba([0, 0, 0, 255]);
+        let image = RgbaImage::from_pixel(800, 600, black);
+        let frame = Frame::new(
+            image,
+            &HudLayout::default(),
+            1.0,
+            MobColors::default(),
+            BarColors::default(),
+        );
+        let mob = Target {
+            target_type: TargetType::Mob(MobType::Aggressive),
+            bounds: Bounds::new(300, 200, 40, 10),
+            ..Default::default()
+        };
+        let annotations = FrameAnnotations {
+            state: Some("Attacking".to_string()),
+            avoided_bounds: vec![],
+            click_point: Some(Point::new(500, 400)),
+        };
+
+        let annotated = annotate_frame(&frame, &[mob], None, &annotations);
+        assert_eq!(*annotated.get_pixel(300, 205), AGGRESSIVE_COLOR);
+        assert_eq!(*annotated.get_pixel(320, 205), black);
+        assert_eq!(*annotated.get_pixel(500, 400), CLICK_COLOR);
+        // The state is written into the top left corner
+        assert!((0..40).any(|x| *annotated.get_pixel(x, 10) != black));
+        // The frame itself is left as is
+        assert_eq!(*frame.image().get_pixel(300, 205), black);
+    }
+}
@@ -0,0 +1,382 @@
+//! Recent frames and decisions of the bot, saved when something went wrong.
+
+use std::{
+    collections::VecDeque,
+    fs,
+    io::Write,
+    panic,
+    path::{Path, PathBuf},
+    sync::{Arc, Weak},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use parking_lot::{const_mutex, Mutex};
+use serde::Serialize;
+use slog::Logger;
+
+use crate::{
+    data::{Bounds, ClientStats, Point},
+    image_analyzer::{Frame, ImageBuffer},
+    platform::{InputAction, InputSink, KeyMode},
+};
+
+/// Frames kept for a dump
+const MAX_FRAMES: usize = 30;
+/// Stats, state changes and inputs kept for a dump
+const MAX_EVENTS: usize = 2000;
+/// How long a dump waits for the buffers, in case it runs while they're locked by a panic
+const LOCK_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Recorder of the running bot, dumped by the panic hook
+static PANIC_RECORDER: Mutex<Option<Weak<FlightRecorder>>> = const_mutex(None);
+
+/// The values of `ClientStats` worth looking at after the fact.
+#[derive(Debug, Clone, Serialize)]
+pub struct StatsSnapshot {
+    hp: u32,
+    mp: u32,
+    fp: u32,
+    target_hp: u32,
+    target_mp: u32,
+    is_alive: String,
+    target_on_screen: bool,
+    target_is_mover: bool,
+    target_is_npc: bool,
+    target_is_alive: bool,
+    target_marker: Option<Bounds>,
+    target_distance: Option<i32>,
+}
+
+impl From<&ClientStats> for StatsSnapshot {
+    fn from(stats: &ClientStats) -> Self {
+        Self {
+            hp: stats.hp.value,
+            mp: stats.mp.value,
+            fp: stats.fp.value,
+            target_hp: stats.target_hp.value,
+            target_mp: stats.target_mp.value,
+            is_alive: format!("{:?}", stats.is_alive),
+            target_on_screen: stats.target_on_screen,
+            target_is_mover: stats.target_is_mover,
+            target_is_npc: stats.target_is_npc,
+            target_is_alive: stats.target_is_alive,
+            target_marker: stats.target_marker.map(|marker| marker.bounds),
+            target_distance: stats.target_distance,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum FlightEventKind {
+    Stats {
+        frame_id: u64,
+        stats: StatsSnapshot,
+    },
+    State {
+        from: Option<String>,
+        to: Option<String>,
+    },
+    Input {
+        action: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct FlightEvent {
+    /// Unix time in milliseconds
+    timestamp: u64,
+    #[serde(flatten)]
+    kind: FlightEventKind,
+}
+
+#[derive(Debug)]
+struct RecordedFrame {
+    id: u64,
+    image: Arc<ImageBuffer>,
+}
+
+/// Keeps the last frames, stats, state changes and inputs of a session in ring buffers, and
+/// writes them to a folder on death, disconnect or panic.
+#[derive(Debug)]
+pub struct FlightRecorder {
+    dir: PathBuf,
+    max_frames: usize,
+    max_events: usize,
+    frames: Mutex<VecDeque<RecordedFrame>>,
+    events: Mutex<VecDeque<FlightEvent>>,
+    state: Mutex<Option<String>>,
+    logger: Logger,
+}
+
+impl FlightRecorder {
+    /// Create a recorder which dumps into subfolders of `dir`.
+    pub fn new(dir: PathBuf, logger: Logger) -> Self {
+        Self::with_capacity(dir, logger, MAX_FRAMES, MAX_EVENTS)
+    }
+
+    pub fn with_capacity(
+        dir: PathBuf,
+        logger: Logger,
+        max_frames: usize,
+        max_events: usize,
+    ) -> Self {
+        Self {
+            dir,
+            max_frames,
+            max_events,
+            frames: Mutex::new(VecDeque::with_capacity(max_frames)),
+            events: Mutex::new(VecDeque::with_capacity(max_events)),
+            state: Mutex::new(None),
+            logger,
+        }
+    }
+
+    /// Dump the recorder of the running bot whenever a thread panics, before the default
+    /// panic output. Installed once when the app starts.
+    pub fn install_panic_hook() {
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let recorder = PANIC_RECORDER
+                .try_lock_for(LOCK_TIMEOUT)
+                .and_then(|recorder| recorder.as_ref().and_then(Weak::upgrade));
+            if let Some(recorder) = recorder {
+                recorder.dump_and_log("panic");
+            }
+            default_hook(info);
+        }));
+    }
+
+    /// Make this the recorder dumped on panic, instead of the one of the last bot start.
+    pub fn dump_on_panic(self: &Arc<Self>) {
+        *PANIC_RECORDER.lock() = Some(Arc::downgrade(self));
+    }
+
+    pub fn record_frame(&self, id: u64, frame: &Frame) {
+        let mut frames = self.frames.lock();
+        if frames.len() == self.max_frames {
+            frames.pop_front();
+        }
+        frames.push_back(RecordedFrame {
+            id,
+            image: frame.shared_image(),
+        });
+    }
+
+    pub fn record_stats(&self, frame_id: u64, stats: &ClientStats) {
+        self.push_event(FlightEventKind::Stats {
+            frame_id,
+            stats: stats.into(),
+        });
+    }
+
+    /// Record the state of the behavior, if it changed since the last call.
+    pub fn record_state(&self, state: Option<&str>) {
+        let mut last_state = self.state.lock();
+        if last_state.as_deref() == state {
+            return;
+        }
+        let from = last_state.take();
+        *last_state = state.map(str::to_string);
+        self.push_event(FlightEventKind::State {
+            from,
+            to: state.map(str::to_string),
+        });
+    }
+
+    pub fn record_input(&self, action: &InputAction) {
+        self.push_event(FlightEventKind::Input {
+            action: action.to_string(),
+        });
+    }
+
+    fn push_event(&self, kind: FlightEventKind) {
+        let mut events = self.events.lock();
+        if events.len() == self.max_events {
+            events.pop_front();
+        }
+        events.push_back(FlightEvent {
+            timestamp: unix_millis(),
+            kind,
+        });
+    }
+
+    /// Write everything recorded so far into a new folder named after the time and `reason`.
+    pub fn dump(&self, reason: &str) -> Result<PathBuf, String> {
+        let dir = self
+            .dir
+            .join(format!("{}_{}", unix_millis() / 1000, reason));
+        fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+
+        let events = self
+            .events
+            .try_lock_for(LOCK_TIMEOUT)
+            .map(|events| events.iter().cloned().collect::<Vec<_>>())
+            .unwrap_or_default();
+        write_events(&dir.join("events.jsonl"), &events)?;
+
+        if let Some(frames) = self.frames.try_lock_for(LOCK_TIMEOUT) {
+            for frame in frames.iter() {
+                frame
+                    .image
+                    .save(dir.join(format!("frame_{:06}.png", frame.id)))
+                    .map_err(|err| err.to_string())?;
+            }
+        }
+
+        Ok(dir)
+    }
+
+    /// Dump and log where to, or why it failed.
+    pub fn dump_and_log(&self, reason: &str) {
+        match self.dump(reason) {
+            Ok(dir) => {
+                slog::info!(self.logger, "Saved flight recorder"; "reason" => reason, "dir" => dir.to_string_lossy().to_string())
+            }
+            Err(err) => {
+                slog::error!(self.logger, "Failed to save flight recorder"; "reason" => reason, "error" => err)
+            }
+        }
+    }
+}
+
+fn write_events(path: &Path, events: &[FlightEvent]) -> Result<(), String> {
+    let mut file = fs::File::create(path).map_err(|err| err.to_string())?;
+    for event in events {
+        let line = serde_json::to_string(event).map_err(|err| err.to_string())?;
+        writeln!(file, "{}", line).map_err(|err| err.to_string())?;
+    }
+    Ok(())
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Records all input passed on to another sink. Kept behind `DryRunInputSink`, so input
+/// skipped on dry run isn't recorded as sent.
+#[derive(Debug)]
+pub struct RecordedInputSink {
+    inner: Arc<dyn InputSink>,
+    recorder: Arc<FlightRecorder>,
+}
+
+impl RecordedInputSink {
+    pub fn new(inner: Arc<dyn InputSink>, recorder: Arc<FlightRecorder>) -> Self {
+        Self { inner, recorder }
+    }
+}
+
+impl InputSink for RecordedInputSink {
+    fn send_key(&self, key: &str, mode: KeyMode) {
+        self.recorder
+            .record_input(&InputAction::Key(key.to_string(), mode));
+        self.inner.send_key(key, mode);
+    }
+
+    fn send_slot(&self, slot_bar_index: usize, slot_index: usize) {
+        self.recorder
+            .record_input(&InputAction::Slot(slot_bar_index, slot_index));
+        self.inner.send_slot(slot_bar_index, slot_index);
+    }
+
+    fn mob_click(&self, pos: Point) {
+        self.recorder.record_input(&InputAction::MobClick(pos));
+        self.inner.mob_click(pos);
+    }
+
+    fn click(&self, pos: Point) {
+        self.recorder.record_input(&InputAction::Click(pos));
+        self.inner.click(pos);
+    }
+
+    fn send_message(&self, text: &str) {
+        self.recorder
+            .record_input(&InputAction::Message(text.to_string()));
+        self.inner.send_message(text);
+    }
+
+    fn exit(&self) {
+        self.recorder.record_input(&InputAction::Exit);
+        self.inner.exit();
+    }
+
+    fn sends_input(&self) -> bool {
+        self.inner.sends_input()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, sync::Arc};
+
+    use image::{Rgba, RgbaImage};
+    use slog::Logger;
+
+    use crate::{
+        data::ClientStats,
+        flight_recorder::{FlightRecorder, RecordedInputSink},
+        image_analyzer::{BarColors, Frame, MobColors},
+        layout::HudLayout,
+        platform::{InputSink, RecordingInputSink},
+        utils::SystemClock,
+    };
+
+    #[test]
+    fn test_flight_recorder_dump() {
+        let dir = std::env::temp_dir().join(format!("neuz_flight_{}", std::process::id()));
+        let recorder = Arc::new(FlightRecorder::with_capacity(
+            dir.clone(),
+            Logger::root(slog::Discard, slog::o!()),
+            2,
+            4,
+        ));
+        let input = RecordedInputSink::new(Arc::new(RecordingInputSink::new()), recorder.clone());
+        let stats = ClientStats::new(None, SystemClock::shared());
+
+        for id in 1..=3 {
+            let image = RgbaImage::from_pixel(20, 10, Rgba([0, 0, 0, 255]));
+            recorder.record_frame(
+                id,
+                &Frame::new(
+                    image,
+                    &HudLayout::default(),
+                    1.0,
+                    MobColors::default(),
+                    BarColors::default(),
+                ),
+            );
+        }
+        recorder.record_state(Some("SearchingForEnemy"));
+        recorder.record_state(Some("SearchingForEnemy"));
+        recorder.record_stats(3, &stats);
+        input.send_slot(0, 1);
+        recorder.record_state(Some("EnemyFound"));
+        input.exit();
+
+        let dump = recorder.dump("death").unwrap();
+        let mut files = fs::read_dir(&dump)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        files.sort();
+        assert_eq!(
+            files,
+            vec!["events.jsonl", "frame_000002.png", "frame_000003.png"]
+        );
+
+        // Only the last events are kept, oldest first
+        let events = fs::read_to_string(dump.join("events.jsonl")).unwrap();
+        let kinds = events
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["kind"].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(kinds, vec!["stats", "input", "state", "input"]);
+        assert!(events.contains(r#""from":"SearchingForEnemy","to":"EnemyFound""#));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,20 +1,67 @@
 use std::{
-    sync::mpsc::{sync_channel, Receiver},
+    path::Path,
+    sync::Arc,
     time::Instant,
 };
 
 //use libscreenshot::shared::Area;
-use libscreenshot::{ImageBuffer, WindowCaptureProvider};
-use rayon::iter::{ParallelBridge, ParallelIterator};
+#[cfg(feature = "app")]
+use libscreenshot::WindowCaptureProvider;
 use slog::Logger;
+#[cfg(feature = "app")]
 use tauri::Window;
 
 use crate::{
-    data::{point_selector, Bounds, ClientStats, MobType, Point, PointCloud, Target, TargetType},
-    ipc::FarmingConfig,
-    platform::{IGNORE_AREA_BOTTOM, IGNORE_AREA_TOP},
-    utils::Timer,
+    data::{
+        Bounds, ClientStats, Minimap, MobName, MobType, Neighbourhood, PointCloud, StatusBarKind,
+        Target, TargetType,
+    },
+    ipc::{BotConfig, ColorMatchMode, FarmingConfig},
+    layout::{HudLayout, HudRegions},
+    ocr::{parse_name_level, GlyphAtlas},
+    utils::{SystemClock, Timer},
 };
+#[cfg(feature = "app")]
+use crate::{pipeline::AnalyzedFrame, platform::InputSink, utils::Clock};
+
+mod calibration;
+mod color_match;
+mod frame;
+mod frame_scanner;
+mod motion;
+pub use self::calibration::calibrate;
+pub use self::color_match::{ColorReference, PixelColor};
+pub use self::frame::Frame;
+pub use self::frame_scanner::{Detector, DetectorId, FrameScanner, ScanResult};
+pub use self::motion::{MotionEstimator, SceneThumbnail};
+
+#[cfg(test)]
+mod golden_tests;
+
+/// A captured frame, the buffer type `libscreenshot` captures into
+pub type ImageBuffer = image::RgbaImage;
+
+/// Glyphs of a name are a few pixels apart, separate names or lines are further away
+pub const MOB_NAME_NEIGHBOURHOOD: Neighbourhood = Neighbourhood::new(12, 4);
+/// Tolerance of the target marker colors
+const TARGET_MARKER_TOLERANCE: u8 = 5;
+/// Tolerance of the stat bar colors
+const STATUS_BAR_TOLERANCE: u8 = 2;
+/// Color of the `current/max` text drawn over the stat tray bars
+const STAT_TEXT_COLOR: [u8; 3] = [255, 255, 255];
+const STAT_TEXT_TOLERANCE: u8 = 30;
+/// Colors of the player arrow and of the dots drawn on the minimap. Not checked against
+/// captures yet, which is why the default layout has no minimap.
+const MINIMAP_ARROW_COLOR: [u8; 3] = [255, 216, 0];
+const MINIMAP_MOB_COLOR: [u8; 3] = [255, 48, 48];
+const MINIMAP_PARTY_COLOR: [u8; 3] = [64, 200, 255];
+const MINIMAP_TOLERANCE: u8 = 30;
+/// Color of the gauge shown while flying. Not checked against captures yet, which is why the
+/// default layout has no flight gauge and (un)mounting is timed instead.
+const FLIGHT_GAUGE_COLOR: [u8; 3] = [96, 176, 255];
+const FLIGHT_GAUGE_TOLERANCE: u8 = 20;
+/// Pixels of the gauge needed to be sure the character flies
+const FLIGHT_GAUGE_MIN_PIXELS: usize = 20;
 
 #[derive(Debug, Clone, Copy, Default)]
 pub struct Color {
@@ -27,26 +74,325 @@ impl Color {
     }
 }
 
-#[derive(Debug, Clone)]
+/// Reference colors and tolerances of mob names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MobColors {
+    pub passive: ([u8; 3], u8),
+    pub aggressive: ([u8; 3], u8),
+    pub violet: ([u8; 3], u8),
+    /// Match modes by `MobType`
+    pub modes: [ColorMatchMode; 3],
+}
+
+impl Default for MobColors {
+    fn default() -> Self {
+        Self::from_config(&FarmingConfig::default())
+    }
+}
+
+impl MobColors {
+    pub fn from_config(config: &FarmingConfig) -> Self {
+        let color = |wrapped: [Option<u8>; 3], default: [u8; 3]| {
+            [
+                wrapped[0].unwrap_or(default[0]),
+                wrapped[1].unwrap_or(default[1]),
+                wrapped[2].unwrap_or(default[2]),
+            ]
+        };
+        Self {
+            passive: (
+                color(config.passive_mobs_colors(), [234, 234, 149]),
+                config.passive_tolerence(),
+            ),
+            aggressive: (
+                color(config.aggressive_mobs_colors(), [179, 23, 23]),
+                config.aggressive_tolerence(),
+            ),
+            violet: (
+                color(config.violet_mobs_colors(), [182, 144, 146]),
+                config.violet_tolerence(),
+            ),
+            modes: [MobType::Passive, MobType::Aggressive, MobType::Violet]
+                .map(|mob_type| config.color_match_mode(mob_type)),
+        }
+    }
+
+    /// Color and tolerance of a type of mobs.
+    pub fn of(&self, mob_type: MobType) -> ([u8; 3], u8) {
+        match mob_type {
+            MobType::Passive => self.passive,
+            MobType::Aggressive => self.aggressive,
+            MobType::Violet => self.violet,
+        }
+    }
+
+    pub fn mode(&self, mob_type: MobType) -> ColorMatchMode {
+        self.modes[mob_type as usize]
+    }
+}
+
+/// Reference colors of the stat bars, four shades each. The bars of the target use the HP
+/// and MP shades.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BarColors {
+    pub hp: [[u8; 3]; 4],
+    pub mp: [[u8; 3]; 4],
+    pub fp: [[u8; 3]; 4],
+}
+
+impl Default for BarColors {
+    fn default() -> Self {
+        Self::from_config(&BotConfig::default())
+    }
+}
+
+impl BarColors {
+    pub fn from_config(config: &BotConfig) -> Self {
+        Self {
+            hp: config.hp_bar_colors().unwrap_or([
+                [174, 18, 55],
+                [188, 24, 62],
+                [204, 30, 70],
+                [220, 36, 78],
+            ]),
+            mp: config.mp_bar_colors().unwrap_or([
+                [20, 84, 196],
+                [36, 132, 220],
+                [44, 164, 228],
+                [56, 188, 232],
+            ]),
+            fp: config.fp_bar_colors().unwrap_or([
+                [45, 230, 29],
+                [28, 172, 28],
+                [44, 124, 52],
+                [20, 146, 20],
+            ]),
+        }
+    }
+
+    /// Shades of a bar.
+    pub fn of(&self, kind: StatusBarKind) -> [[u8; 3]; 4] {
+        match kind {
+            StatusBarKind::Hp | StatusBarKind::TargetHP => self.hp,
+            StatusBarKind::Mp | StatusBarKind::TargetMP => self.mp,
+            StatusBarKind::Fp => self.fp,
+        }
+    }
+}
+
+/// Pixels of mob names by mob type.
+#[derive(Debug, Clone, Default)]
+pub struct MobClouds {
+    pub colors: MobColors,
+    pub passive: PointCloud,
+    pub aggressive: PointCloud,
+    pub violet: PointCloud,
+}
+
+/// Everything found by the single scan of a frame.
+#[derive(Debug, Clone, Default)]
+pub struct FrameScan {
+    pub hp: PointCloud,
+    pub mp: PointCloud,
+    pub fp: PointCloud,
+    pub target_hp: PointCloud,
+    pub target_mp: PointCloud,
+    /// Text drawn over the stat tray bars
+    pub stat_text: PointCloud,
+    pub blue_marker: PointCloud,
+    pub red_marker: PointCloud,
+    pub mobs: MobClouds,
+    /// Pixels of the player arrow and of the dots on the minimap, empty without a minimap
+    pub minimap_arrow: PointCloud,
+    pub minimap_mobs: PointCloud,
+    pub minimap_party: PointCloud,
+    /// Pixels of the flight gauge, empty when not flying
+    pub flight_gauge: PointCloud,
+}
+
+impl FrameScan {
+    pub fn status_bar(&self, kind: StatusBarKind) -> &PointCloud {
+        match kind {
+            StatusBarKind::Hp => &self.hp,
+            StatusBarKind::Mp => &self.mp,
+            StatusBarKind::Fp => &self.fp,
+            StatusBarKind::TargetHP => &self.target_hp,
+            StatusBarKind::TargetMP => &self.target_mp,
+        }
+    }
+}
+
+#[derive(Debug)]
 pub struct ImageAnalyzer {
-    image: Option<ImageBuffer>,
+    frame: Option<Frame>,
     pub window_id: u64,
     pub client_stats: ClientStats,
+    layout: HudLayout,
+    /// DPI scale of the client window
+    scale_factor: f64,
+    /// Mob colors searched by the frame scan
+    mob_colors: MobColors,
+    /// Stat bar colors searched by the frame scan
+    bar_colors: BarColors,
 }
 
 impl ImageAnalyzer {
-    pub fn new(window: &Window) -> Self {
+    #[cfg(feature = "app")]
+    pub fn new(window: &Window, input: Arc<dyn InputSink>, clock: Arc<dyn Clock>) -> Self {
         Self {
             window_id: 0,
-            image: None,
-            client_stats: ClientStats::new(window.to_owned()),
+            frame: None,
+            client_stats: ClientStats::new(Some(input), clock),
+            layout: HudLayout::default(),
+            scale_factor: window.scale_factor().unwrap_or(1.0),
+            mob_colors: MobColors::default(),
+            bar_colors: BarColors::default(),
         }
     }
 
-    pub fn image_is_some(&self) -> bool {
-        self.image.is_some()
+    /// Create an analyzer that isn't bound to any client window, e.g. to replay recorded frames.
+    pub fn headless() -> Self {
+        Self {
+            window_id: 0,
+            frame: None,
+            client_stats: ClientStats::new(None, SystemClock::shared()),
+            layout: HudLayout::default(),
+            scale_factor: 1.0,
+            mob_colors: MobColors::default(),
+            bar_colors: BarColors::default(),
+        }
+    }
+
+    /// Create an analyzer around an already captured frame, without any client window.
+    #[allow(dead_code)]
+    pub fn from_image(image: ImageBuffer) -> Self {
+        let mut analyzer = Self::headless();
+        analyzer.set_image(image);
+        analyzer
     }
 
+    /// Create an analyzer from a saved screenshot.
+    #[allow(dead_code)]
+    pub fn from_file<P>(path: P) -> image::ImageResult<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Ok(Self::from_image(image::open(path)?.to_rgba8()))
+    }
+
+    /// Replace the current frame, e.g. with the next frame of a recorded session.
+    pub fn set_image(&mut self, image: impl Into<Arc<ImageBuffer>>) {
+        self.frame = Some(Frame::new(
+            image,
+            &self.layout,
+            self.scale_factor,
+            self.mob_colors,
+            self.bar_colors,
+        ));
+    }
+
+    /// The current frame, if one was captured.
+    pub fn frame(&self) -> Option<&Frame> {
+        self.frame.as_ref()
+    }
+
+    /// Move the current frame out of the analyzer.
+    pub fn take_frame(&mut self) -> Option<Frame> {
+        self.frame.take()
+    }
+
+    /// Size of the current frame as `(width, height)`.
+    pub fn image_size(&self) -> Option<(u32, u32)> {
+        self.frame.as_ref().map(Frame::size)
+    }
+
+    /// Use another HUD layout profile for all following frames.
+    pub fn set_layout(&mut self, layout: HudLayout) {
+        self.layout = layout;
+        self.rebuild_frame();
+    }
+
+    /// Search mob names with these colors in the following frame scans.
+    pub fn set_mob_colors(&mut self, mob_colors: MobColors) {
+        if mob_colors != self.mob_colors {
+            self.mob_colors = mob_colors;
+            self.rebuild_frame();
+        }
+    }
+
+    /// Search the stat bars with these colors in the following frame scans.
+    pub fn set_bar_colors(&mut self, bar_colors: BarColors) {
+        if bar_colors != self.bar_colors {
+            self.bar_colors = bar_colors;
+            self.rebuild_frame();
+        }
+    }
+
+    /// Apply changed settings to the current frame. Its buffer is moved, not copied.
+    fn rebuild_frame(&mut self) {
+        if let Some(frame) = self.frame.take() {
+            self.set_image(frame.into_image());
+        }
+    }
+
+    /// Use a frame and the stats analyzed by the frame pipeline.
+    #[cfg(feature = "app")]
+    pub fn set_analyzed_frame(&mut self, analyzed: AnalyzedFrame) {
+        self.client_stats.replace_with(analyzed.client_stats);
+        self.frame = Some(analyzed.frame);
+    }
+
+    /// Update the client stats from the current frame.
+    pub fn update_stats(&mut self, logger: &Logger) {
+        if let Some(frame) = self.frame.as_ref() {
+            self.client_stats.update(frame, logger);
+        }
+    }
+
+    fn add_mob_detectors(
+        scanner: &mut FrameScanner,
+        colors: MobColors,
+        hud: &HudRegions,
+    ) -> [DetectorId; 3] {
+        // Mob types are checked in this order, a pixel only belongs to the first match
+        [MobType::Passive, MobType::Aggressive, MobType::Violet].map(|mob_type| {
+            let ([r, g, b], tolerance) = colors.of(mob_type);
+            scanner.add(
+                Detector::new(vec![Color::new(r, g, b)], tolerance)
+                    .matching(colors.mode(mob_type))
+                    // Avoid detecting the stat bars and the minimap as monsters
+                    .ignoring(hud.mob_ignore_areas.iter().chain(&hud.minimap).copied().collect())
+                    .exclusive(),
+            )
+        })
+    }
+
+    fn take_mob_clouds(
+        result: &mut ScanResult,
+        [passive, aggressive, violet]: [DetectorId; 3],
+        colors: MobColors,
+    ) -> MobClouds {
+        MobClouds {
+            colors,
+            passive: result.take(passive),
+            aggressive: result.take(aggressive),
+            violet: result.take(violet),
+        }
+    }
+
+    /// HUD regions of the current frame. Without a frame the layout's reference size is used.
+    pub fn hud(&self) -> HudRegions {
+        match self.frame.as_ref() {
+            Some(frame) => frame.hud().clone(),
+            None => self.layout.resolve(
+                self.layout.reference_width,
+                self.layout.reference_height,
+                self.scale_factor,
+            ),
+        }
+    }
+
+    #[cfg(feature = "app")]
     pub fn capture_window(&mut self, logger: &Logger) {
         let _timer = Timer::start_new("capture_window");
         if self.window_id == 0 {
@@ -55,364 +401,108 @@ impl ImageAnalyzer {
 
         if let Some(provider) = libscreenshot::get_window_capture_provider() {
             if let Ok(image) = provider.capture_window(self.window_id) {
-                self.image = Some(image);
+                self.set_image(image);
             } else {
                 slog::warn!(logger, "Failed to capture window"; "window_id" => self.window_id);
             }
         }
     }
 
-    pub fn pixel_detection(
-        &self,
-        colors: Vec<Color>,
-        min_x: u32,
-        min_y: u32,
-        mut max_x: u32,
-        mut max_y: u32,
-        tolerence: Option<u8>,
-    ) -> Receiver<Point> {
-        let (snd, recv) = sync_channel::<Point>(4096);
-        let image = self.image.as_ref().unwrap();
-
-        if max_x == 0 {
-            max_x = image.width();
-        }
-
-        if max_y == 0 {
-            max_y = image.height();
-        }
-
-        image
-            .enumerate_rows()
-            .par_bridge()
-            .for_each(move |(y, row)| {
-                // Skip this row if it's in an ignored area
-                let image_height = image.height();
-                #[allow(clippy::absurd_extreme_comparisons)] // not always 0 (macOS)
-                if y <= IGNORE_AREA_TOP
-                    || y > image_height
-                        .checked_sub(IGNORE_AREA_BOTTOM)
-                        .unwrap_or(image_height)
-                    || y > IGNORE_AREA_TOP + max_y
-                    || y > max_y
-                    || y < min_y
-                {
-                    return;
-                }
-
-                // Loop over columns
-                'outer: for (x, _, px) in row {
-                    if px.0[3] != 255 || x >= max_x {
-                        return;
-                    } else if x < min_x {
-                        continue;
-                    }
-
-                    for ref_color in colors.iter() {
-                        // Check if the pixel matches any of the reference colors
-                        if Self::pixel_matches(&px.0, &ref_color.refs, tolerence.unwrap_or(5)) {
-                            // #[allow(dropping_copy_types)]
-                            // drop(snd.try_send(Point::new(x, y)));
-                            // drop(snd.send(Point::new(x, y)));
-                            #[allow(dropping_copy_types)]
-                            drop(snd.try_send(Point::new(x, y)).map_err(|err| {
-                                eprintln!("Error sending data: {}", err);
-                            }));
-
-                            // Continue to next column
-                            continue 'outer;
-                        }
-                    }
-                }
-            });
-        recv
-    }
-
     fn merge_cloud_into_mobs(
-        config: Option<&FarmingConfig>,
         cloud: &PointCloud,
         mob_type: TargetType, //ignore_size: bool,
+        neighbourhood: Neighbourhood,
     ) -> Vec<Target> {
         let _timer = Timer::start_new("merge_cloud_into_mobs");
 
-        // Max merge distance
-        let max_distance_x: u32 = 50;
-        let max_distance_y: u32 = 3;
-
-        // Cluster coordinates in x-direction
-        let x_clusters = cloud.cluster_by_distance(max_distance_x, point_selector::x_axis);
-
-        let mut xy_clusters = Vec::default();
-        for x_cluster in x_clusters {
-            // Cluster current x-cluster coordinates in y-direction
-            let local_y_clusters =
-                x_cluster.cluster_by_distance(max_distance_y, point_selector::y_axis);
-            // Extend final xy-clusters with local y-clusters
-            xy_clusters.extend(local_y_clusters.into_iter());
-        }
-
-        // Create mobs from clusters
-        xy_clusters
+        // Create mobs from connected clusters
+        cloud
+            .cluster_connected(neighbourhood, 1)
             .into_iter()
-            .map(|cluster| Target {
-                target_type: mob_type,
-                bounds: cluster.to_bounds(),
-            })
-            .filter(|mob| {
-                if let Some(config) = config {
-                    // Filter out small clusters (likely to cause misclicks)
-                    mob.bounds.w > config.min_mobs_name_width() &&
-                        // Filter out huge clusters (likely to be Violet Magician Troupe)
-                        mob.bounds.w < config.max_mobs_name_width()
-                } else {
-                    true
+            .map(|cluster| {
+                // Read name plates of mobs
+                let (name, level) = match mob_type {
+                    TargetType::Mob(_) => {
+                        parse_name_level(&GlyphAtlas::flyff_ui().read_text(&cluster.points))
+                    }
+                    TargetType::TargetMarker => (None, None),
+                };
+                Target {
+                    target_type: mob_type,
+                    bounds: cluster.bounds,
+                    name: name.map(|name| MobName::new(&name)),
+                    level,
                 }
             })
             .collect()
     }
 
-    /// Check if pixel `c` matches reference pixel `r` with the given `tolerance`.
-    #[inline(always)]
-    fn pixel_matches(c: &[u8; 4], r: &[u8; 3], tolerance: u8) -> bool {
-        let matches_inner = |a: u8, b: u8| match (a, b) {
-            (a, b) if a == b => true,
-            (a, b) if a > b => a.saturating_sub(b) <= tolerance,
-            (a, b) if a < b => b.saturating_sub(a) <= tolerance,
-            _ => false,
-        };
-        let perm = [(c[0], r[0]), (c[1], r[1]), (c[2], r[2])];
-        perm.iter().all(|&(a, b)| matches_inner(a, b))
+    /// Whether the size and name of `mob` pass the filters of `config`.
+    fn is_mob_allowed(config: &FarmingConfig, mob: &Target) -> bool {
+        // Filter out small clusters (likely to cause misclicks)
+        mob.bounds.w > config.min_mobs_name_width() &&
+            // Filter out huge clusters (likely to be Violet Magician Troupe)
+            mob.bounds.w < config.max_mobs_name_width() &&
+            // Filter by name
+            config.is_mob_name_allowed(mob.name.as_ref().map(MobName::as_str))
     }
 
     pub fn identify_mobs(&self, config: &FarmingConfig) -> Vec<Target> {
-        let _timer = Timer::start_new("identify_mobs");
-
-        // Create collections for passive and aggro mobs
-        let mut mob_coords_pas: Vec<Point> = Vec::default();
-        let mut mob_coords_agg: Vec<Point> = Vec::default();
-        let mut mob_coords_violet: Vec<Point> = Vec::default();
-
-        // Reference colors
-        let ref_color_pas_wrapped: [Option<u8>; 3] = config.passive_mobs_colors(); // Passive mobs 234, 234, 149
-        let ref_color_agg_wrapped: [Option<u8>; 3] = config.aggressive_mobs_colors(); // Aggro mobs 179, 23, 23
-
-        let ref_color_violet_wrapped: [Option<u8>; 3] = config.violet_mobs_colors(); // Aggro mobs 179, 23, 23
-        let ref_color_pas: [u8; 3] = [
-            ref_color_pas_wrapped[0].unwrap_or(234),
-            ref_color_pas_wrapped[1].unwrap_or(234),
-            ref_color_pas_wrapped[2].unwrap_or(149),
-        ];
-        let ref_color_agg: [u8; 3] = [
-            ref_color_agg_wrapped[0].unwrap_or(179),
-            ref_color_agg_wrapped[1].unwrap_or(23),
-            ref_color_agg_wrapped[2].unwrap_or(23),
-        ];
-        let ref_color_violet: [u8; 3] = [
-            ref_color_violet_wrapped[0].unwrap_or(182),
-            ref_color_violet_wrapped[1].unwrap_or(144),
-            ref_color_violet_wrapped[2].unwrap_or(146),
-        ];
-
-        // Collect pixel clouds
-        struct MobPixel(u32, u32, TargetType);
-        let (snd, recv) = sync_channel::<MobPixel>(4096);
-        let image = self.image.as_ref().unwrap();
-        image
-            .enumerate_rows()
-            .par_bridge()
-            .for_each(move |(y, row)| {
-                #[allow(clippy::absurd_extreme_comparisons)] // not always 0 (macOS)
-                if y <= IGNORE_AREA_TOP || y > image.height() - IGNORE_AREA_BOTTOM {
-                    return;
-                }
-                for (x, _, px) in row {
-                    if px.0[3] != 255 {
-                        return;
-                    } else if x <= 250 && y <= 110 {
-                        // avoid detect the health bar as a monster
-                        continue;
-                    }
-                    if Self::pixel_matches(&px.0, &ref_color_pas, config.passive_tolerence()) {
-                        drop(snd.send(MobPixel(x, y, TargetType::Mob(MobType::Passive))));
-                    } else if Self::pixel_matches(
-                        &px.0,
-                        &ref_color_agg,
-                        config.aggressive_tolerence(),
-                    ) {
-                        drop(snd.send(MobPixel(x, y, TargetType::Mob(MobType::Aggressive))));
-                    } else if Self::pixel_matches(
-                        &px.0,
-                        &ref_color_violet,
-                        config.violet_tolerence(),
-                    ) {
-                        drop(snd.send(MobPixel(x, y, TargetType::Mob(MobType::Violet))));
-                    }
-                }
-            });
-        while let Ok(px) = recv.recv() {
-            match px.2 {
-                TargetType::Mob(MobType::Passive) => mob_coords_pas.push(Point::new(px.0, px.1)),
-                TargetType::Mob(MobType::Aggressive) => mob_coords_agg.push(Point::new(px.0, px.1)),
-                TargetType::Mob(MobType::Violet) => mob_coords_violet.push(Point::new(px.0, px.1)),
-                _ => unreachable!(),
-            }
-        }
-
-        // Categorize mobs
-        let mobs_pas = Self::merge_cloud_into_mobs(
-            Some(config),
-            &PointCloud::new(mob_coords_pas),
-            TargetType::Mob(MobType::Passive),
-        );
-        let mobs_agg = Self::merge_cloud_into_mobs(
-            Some(config),
-            &PointCloud::new(mob_coords_agg),
-            TargetType::Mob(MobType::Aggressive),
-        );
-        let _mobs_violet = Self::merge_cloud_into_mobs(
-            Some(config),
-            &PointCloud::new(mob_coords_violet),
-            TargetType::Mob(MobType::Violet),
-        );
-
-        // Return all mobs
-        Vec::from_iter(mobs_agg.into_iter().chain(mobs_pas))
+        self.frame
+            .as_ref()
+            .map(|frame| frame.identify_mobs(config))
+            .unwrap_or_default()
     }
 
-    pub fn identify_target_marker(&self, blue_target: bool) -> Option<Target> {
-        let _timer = Timer::start_new("identify_target_marker");
-        let mut coords = Vec::default();
-
-        // Reference color
-        let ref_color: Color = {
-            if blue_target {
-                // Color::new(164, 180, 226) //A4B4E1 -- blueish
-                Color::new(131, 148, 205) //A4B4E1 -- blueish - more center of the arrow
-            } else {
-                Color::new(246, 90, 106) //F65A6A -- redish
-            }
-            //changed the Original colors (below), because they don't work in Azria
-            // if !blank_target {
-            //     Color::new(246, 90, 106) //F65A6A
-            // } else {
-            //     Color::new(164, 180, 226) //A4B4E1
-            // }
-        };
-
-        // Collect pixel clouds
-        let recv = self.pixel_detection(vec![ref_color], 0, 0, 0, 0, None);
-
-        // // Receive points from channel
-        while let Ok(point) = recv.recv() {
-            coords.push(point);
+    fn target_marker_color(blue_target: bool) -> Color {
+        if blue_target {
+            // Color::new(164, 180, 226) //A4B4E1 -- blueish
+            Color::new(131, 148, 205) //A4B4E1 -- blueish - more center of the arrow
+        } else {
+            Color::new(246, 90, 106) //F65A6A -- redish
         }
-
-        // // Receive points from channel
-        // loop {
-        //     match recv.recv() {
-        //         Ok(point) => {
-        //             coords.push(point);
-        //         }
-        //         Err(std::sync::mpsc::RecvError) => {
-        //             eprintln!("Error receiving data, channel is closed");
-        //             // Channel is closed, break the loop
-        //             break;
-        //         }
-        //         Err(err) => {
-        //             eprintln!("Error receiving data: {}", err);
-        //             // Handle other errors if needed
-        //         }
-        //     }
+        //changed the Original colors (below), because they don't work in Azria
+        // if !blank_target {
+        //     Color::new(246, 90, 106) //F65A6A
+        // } else {
+        //     Color::new(164, 180, 226) //A4B4E1
         // }
+    }
 
-        // Identify target marker entities
-        let target_markers =
-            Self::merge_cloud_into_mobs(None, &PointCloud::new(coords), TargetType::TargetMarker);
-
-        if !blue_target && target_markers.is_empty() {
-            return self.identify_target_marker(true);
-        }
-
-        // Find biggest target marker
-        target_markers.into_iter().max_by_key(|x| x.bounds.size())
+    pub fn identify_target_marker(&self, blue_target: bool) -> Option<Target> {
+        self.frame
+            .as_ref()
+            .and_then(|frame| frame.identify_target_marker(blue_target))
     }
-    pub fn get_target_marker_distance(&self, target: Target) -> i32 {
-        let image = self.image.as_ref().unwrap();
 
-        // Calculate middle point of player
-        let mid_x = (image.width() / 2) as i32;
-        let mid_y = (image.height() / 2) as i32;
+    /// Whether the current frame shows the flight gauge, `None` without a frame or if the
+    /// layout doesn't know where it is.
+    pub fn is_flying(&self) -> Option<bool> {
+        self.frame.as_ref().and_then(Frame::is_flying)
+    }
 
-        // Calculate 2D euclidian distances to player
-        let point = target.bounds.get_lowest_center_point();
+    /// Player heading and nearby dots of the current frame's minimap.
+    pub fn minimap(&self) -> Option<Minimap> {
+        self.frame.as_ref().and_then(Frame::minimap)
+    }
 
-        (((mid_x - (point.x as i32)).pow(2) + (mid_y - (point.y as i32)).pow(2)) as f64).sqrt()
-            as i32
+    pub fn get_target_marker_distance(&self, target: Target) -> i32 {
+        self.frame
+            .as_ref()
+            .expect("No frame captured")
+            .get_target_marker_distance(target)
     }
+
     /// Distance: `[0..=500]`
     pub fn find_closest_mob<'a>(
         &self,
         mobs: &'a [Target],
-        //avoid_bounds: Option<&Bounds>,
         avoid_list: Option<&Vec<(Bounds, Instant, u128)>>,
         max_distance: i32,
-        _logger: &Logger,
+        logger: &Logger,
     ) -> Option<&'a Target> {
-        let _timer = Timer::start_new("find_closest_mob");
-
-        let image = self.image.as_ref().unwrap();
-
-        // Calculate middle point of player
-        let mid_x = (image.width() / 2) as i32;
-        let mid_y = (image.height() / 2) as i32;
-
-        // Calculate 2D euclidian distances to player
-        let mut distances = Vec::default();
-        for mob in mobs {
-            let point = mob.get_attack_coords();
-            let distance = (((mid_x - (point.x as i32)).pow(2) + (mid_y - (point.y as i32)).pow(2))
-                as f64)
-                .sqrt() as i32;
-            distances.push((mob, distance));
-        }
-
-        // Sort by distance
-        distances.sort_by_key(|&(_, distance)| distance);
-
-        // Remove mobs that are too far away
-        distances = distances
-            .into_iter()
-            .filter(|&(_, distance)| distance <= max_distance)
-            .collect();
-
-        if let Some(avoided_bounds) = avoid_list {
-            // Try finding closest mob that's not the mob to be avoided
-            if let Some((mob, _distance)) = distances.iter().find(|(mob, _distance)| {
-                //*distance > 55
-                let coords = mob.get_attack_coords();
-                let mut result = true;
-                for avoided_item in avoided_bounds {
-                    if avoided_item.0.contains_point(&coords) {
-                        //slog::debug!(logger, ""; "Avoided bounds" => avoided_item.0);
-                        result = false;
-                        break;
-                    }
-                }
-                result // && *distance > 20
-                       // let coords = mob.name_bounds.get_lowest_center_point();
-                       // !avoid_bounds.grow_by(100).contains_point(&coords) && *distance > 200
-            }) {
-                Some(mob)
-            } else {
-                None
-            }
-        } else {
-            // Return closest mob
-            if let Some((mob, _distance)) = distances.first() {
-                Some(*mob)
-            } else {
-                None
-            }
-        }
+        self.frame.as_ref().and_then(|frame| {
+            frame.find_closest_mob(mobs, avoid_list, max_distance, logger)
+        })
     }
 }
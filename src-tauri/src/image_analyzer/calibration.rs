@@ -0,0 +1,296 @@
+//! Reference colors derived from regions of a frame which the user marked as a mob name or a
+//! stat bar.
+
+use std::collections::HashMap;
+
+use crate::{
+    data::{Bounds, Point},
+    image_analyzer::{ColorReference, Frame, ImageBuffer, PixelColor, STATUS_BAR_TOLERANCE},
+    ipc::{CalibrationRequest, CalibrationResult, ColorMatchMode, HistogramBin},
+};
+
+/// Channel values falling into the same histogram bin of mob names
+const MOB_BIN_SIZE: u8 = 8;
+/// Largest tolerance proposed for mob names
+const MAX_MOB_TOLERANCE: u8 = 40;
+/// How many matched name pixels one false positive elsewhere in the frame outweighs
+const FALSE_POSITIVE_COST: i64 = 4;
+/// Number of shades searched per bar
+const BAR_SHADES: usize = 4;
+/// Colors listed in the histogram of a result
+const HISTOGRAM_LEN: usize = 8;
+
+/// Pixels of the marked regions falling into one bin, and how often the bin occurs elsewhere.
+#[derive(Debug, Default, Clone, Copy)]
+struct Bin {
+    count: u32,
+    outside: u32,
+    sum: [u64; 3],
+}
+
+impl Bin {
+    fn mean(&self) -> [u8; 3] {
+        self.sum.map(|sum| (sum / self.count.max(1) as u64) as u8)
+    }
+
+    /// Frequent in the marked regions and rare elsewhere.
+    fn score(&self) -> u64 {
+        let count = self.count as u64;
+        count * count / (count + self.outside as u64).max(1)
+    }
+}
+
+/// Propose colors for the target of `request` from the pixels of its regions. `None` if the
+/// regions don't cover any pixel of the frame.
+///
+/// Tolerances of mob names are in the unit of the match mode the frame searches names with.
+pub fn calibrate(frame: &Frame, request: &CalibrationRequest) -> Option<CalibrationResult> {
+    let image = frame.image();
+    let regions = &request.regions;
+    let (bin_size, mode) = match request.target.mob_type() {
+        Some(mob_type) => (MOB_BIN_SIZE, frame.mob_colors().mode(mob_type)),
+        // Bars are drawn with exact shades
+        None => (1, ColorMatchMode::Rgb),
+    };
+    let bins = histogram(image, regions, bin_size);
+    if bins.is_empty() {
+        return None;
+    }
+
+    let mut ranked = bins.values().copied().collect::<Vec<_>>();
+    ranked.sort_by_key(|bin| std::cmp::Reverse(bin.score()));
+    let (colors, tolerance) = match request.target.is_bar() {
+        true => (pick_shades(&ranked), STATUS_BAR_TOLERANCE),
+        false => {
+            let color = ranked[0].mean();
+            let reference = ColorReference::new(color, mode);
+            (vec![color], pick_tolerance(image, regions, reference))
+        }
+    };
+    let references = colors
+        .iter()
+        .map(|color| ColorReference::new(*color, mode))
+        .collect::<Vec<_>>();
+    let (matches, false_positives) = count_matches(image, regions, &references, tolerance);
+
+    let mut frequent = bins.values().copied().collect::<Vec<_>>();
+    frequent.sort_by_key(|bin| std::cmp::Reverse(bin.count));
+    Some(CalibrationResult {
+        target: request.target,
+        colors,
+        tolerance,
+        matches,
+        false_positives,
+        histogram: frequent
+            .iter()
+            .take(HISTOGRAM_LEN)
+            .map(|bin| HistogramBin {
+                color: bin.mean(),
+                count: bin.count,
+            })
+            .collect(),
+    })
+}
+
+/// Call `f` with every pixel of `image` and whether it's inside one of `regions`.
+fn for_each_pixel(image: &ImageBuffer, regions: &[Bounds], mut f: impl FnMut([u8; 3], bool)) {
+    for (x, y, px) in image.enumerate_pixels() {
+        let point = Point::new(x, y);
+        let is_marked = regions.iter().any(|region| region.contains_point(&point));
+        f([px[0], px[1], px[2]], is_marked);
+    }
+}
+
+/// Largest difference between the channels of two colors, as compared against tolerances.
+fn distance(a: [u8; 3], b: [u8; 3]) -> u8 {
+    ColorReference::Rgb(a).distance(&PixelColor::new(&b)) as u8
+}
+
+/// Bins of the colors in `regions`, counting their occurrences outside as well.
+fn histogram(image: &ImageBuffer, regions: &[Bounds], bin_size: u8) -> HashMap<[u8; 3], Bin> {
+    let key = |color: [u8; 3]| color.map(|channel| channel / bin_size);
+
+    let mut bins: HashMap<[u8; 3], Bin> = HashMap::new();
+    for_each_pixel(image, regions, |color, is_marked| {
+        if is_marked {
+            let bin = bins.entry(key(color)).or_default();
+            bin.count += 1;
+            for (sum, channel) in bin.sum.iter_mut().zip(color) {
+                *sum += channel as u64;
+            }
+        }
+    });
+    for_each_pixel(image, regions, |color, is_marked| {
+        if !is_marked {
+            if let Some(bin) = bins.get_mut(&key(color)) {
+                bin.outside += 1;
+            }
+        }
+    });
+    bins
+}
+
+/// The best ranked shades, skipping those already matched by a better one.
+fn pick_shades(ranked: &[Bin]) -> Vec<[u8; 3]> {
+    let mut shades: Vec<[u8; 3]> = vec![];
+    for bin in ranked.iter().filter(|bin| bin.score() > 0) {
+        let color = bin.mean();
+        if shades
+            .iter()
+            .all(|shade| distance(*shade, color) > STATUS_BAR_TOLERANCE)
+        {
+            shades.push(color);
+        }
+        if shades.len() == BAR_SHADES {
+            break;
+        }
+    }
+    shades
+}
+
+/// The tolerance around `reference` gaining the most marked pixels for the false positives it
+/// adds.
+fn pick_tolerance(image: &ImageBuffer, regions: &[Bounds], reference: ColorReference) -> u8 {
+    let mut marked = [0_i64; MAX_MOB_TOLERANCE as usize + 1];
+    let mut outside = [0_i64; MAX_MOB_TOLERANCE as usize + 1];
+    for_each_pixel(image, regions, |px, is_marked| {
+        let distance = reference.distance(&PixelColor::new(&px));
+        if distance <= MAX_MOB_TOLERANCE as u32 {
+            match is_marked {
+                true => marked[distance as usize] += 1,
+                false => outside[distance as usize] += 1,
+            }
+        }
+    });
+
+    let (mut best, mut best_score) = (0, i64::MIN);
+    let (mut matches, mut false_positives) = (0, 0);
+    for tolerance in 0..=MAX_MOB_TOLERANCE {
+        matches += marked[tolerance as usize];
+        false_positives += outside[tolerance as usize];
+        let score = matches - FALSE_POSITIVE_COST * false_positives;
+        if score > best_score {
+            (best, best_score) = (tolerance, score);
+        }
+    }
+    best
+}
+
+/// Pixels matching any of `references` inside and outside of `regions`.
+fn count_matches(
+    image: &ImageBuffer,
+    regions: &[Bounds],
+    references: &[ColorReference],
+    tolerance: u8,
+) -> (u32, u32) {
+    let (mut matches, mut false_positives) = (0, 0);
+    for_each_pixel(image, regions, |px, is_marked| {
+        let px = PixelColor::new(&px);
+        if references
+            .iter()
+            .any(|reference| reference.matches(&px, tolerance))
+        {
+            match is_marked {
+                true => matches += 1,
+                false => false_positives += 1,
+            }
+        }
+    });
+    (matches, false_positives)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use crate::{
+        data::Bounds,
+        image_analyzer::{calibrate, BarColors, Frame, MobColors},
+        ipc::{BotConfig, CalibrationRequest, CalibrationTarget},
+        layout::HudLayout,
+    };
+
+    fn frame(image: RgbaImage) -> Frame {
+        Frame::new(
+            image,
+            &HudLayout::default(),
+            1.0,
+            MobColors::default(),
+            BarColors::default(),
+        )
+    }
+
+    #[test]
+    fn test_calibrate_mob_name() {
+        // A gradient background with a name drawn in two close shades, and two stray pixels
+        // of the name color elsewhere
+        let mut image =
+            RgbaImage::from_fn(200, 100, |x, y| Rgba([x as u8, (y * 2) as u8, 60, 255]));
+        for x in (50..80).step_by(2) {
+            for y in 42..48 {
+                let px = match y % 3 {
+                    0 => [220, 222, 140, 255],
+                    _ => [226, 228, 143, 255],
+                };
+                image.put_pixel(x, y, Rgba(px));
+            }
+        }
+        image.put_pixel(10, 10, Rgba([224, 226, 141, 255]));
+        image.put_pixel(190, 90, Rgba([224, 226, 141, 255]));
+
+        let request = CalibrationRequest {
+            frame_id: 0,
+            target: CalibrationTarget::PassiveMob,
+            regions: vec![Bounds::new(48, 40, 34, 10)],
+        };
+        let result = calibrate(&frame(image), &request).unwrap();
+        assert_eq!(result.colors.len(), 1);
+        assert!(result.colors[0][0] >= 216 && result.colors[0][0] <= 231);
+        // Both shades of the name are matched, on every other column
+        assert_eq!(result.matches, 15 * 6);
+        assert_eq!(result.false_positives, 2);
+
+        let mut config = BotConfig::default();
+        result.apply_to(&mut config);
+        let colors = MobColors::from_config(config.farming_config());
+        assert_eq!(colors.passive, (result.colors[0], result.tolerance));
+    }
+
+    #[test]
+    fn test_calibrate_bar() {
+        let shades = [[170, 20, 50], [190, 25, 60], [205, 30, 70], [225, 35, 80]];
+        let mut image = RgbaImage::from_pixel(200, 100, Rgba([10, 10, 10, 255]));
+        for x in 20..120 {
+            for (row, [r, g, b]) in shades.iter().enumerate() {
+                image.put_pixel(x, 30 + row as u32, Rgba([*r, *g, *b, 255]));
+            }
+        }
+        image.put_pixel(150, 80, Rgba([190, 25, 60, 255]));
+
+        let request = CalibrationRequest {
+            frame_id: 0,
+            target: CalibrationTarget::HpBar,
+            regions: vec![Bounds::new(18, 28, 104, 7)],
+        };
+        let result = calibrate(&frame(image), &request).unwrap();
+        let mut colors = result.colors.clone();
+        colors.sort();
+        assert_eq!(colors, shades.to_vec());
+        assert_eq!(result.matches, 400);
+        assert_eq!(result.false_positives, 1);
+        // The background around the bar is the most frequent color
+        assert_eq!(result.histogram.len(), 5);
+        assert_eq!(result.histogram[0].color, [10, 10, 10]);
+
+        let mut config = BotConfig::default();
+        result.apply_to(&mut config);
+        assert_eq!(BarColors::from_config(&config).hp.to_vec(), result.colors);
+
+        let outside = CalibrationRequest {
+            frame_id: 0,
+            target: CalibrationTarget::HpBar,
+            regions: vec![Bounds::new(500, 500, 10, 10)],
+        };
+        assert!(calibrate(&frame(RgbaImage::new(20, 20)), &outside).is_none());
+    }
+}
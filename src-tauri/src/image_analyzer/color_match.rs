@@ -0,0 +1,186 @@
+use std::{cell::OnceCell, sync::OnceLock};
+
+use crate::ipc::ColorMatchMode;
+
+/// Reference colors below this saturation have no meaningful hue
+const ACHROMATIC_SATURATION: u8 = 32;
+/// Pixels darker than this never match in HSV, their hue and saturation are mostly noise
+const MIN_HSV_VALUE: u8 = 24;
+
+/// A pixel of the frame, converted to each color space at most once however many references
+/// it's compared against.
+#[derive(Debug)]
+pub struct PixelColor {
+    rgb: [u8; 3],
+    hsv: OnceCell<(u8, u8, u8)>,
+    lab: OnceCell<[f32; 3]>,
+}
+
+impl PixelColor {
+    /// The color of an RGB or RGBA pixel.
+    pub fn new(px: &[u8]) -> Self {
+        Self {
+            rgb: [px[0], px[1], px[2]],
+            hsv: OnceCell::new(),
+            lab: OnceCell::new(),
+        }
+    }
+
+    fn hsv(&self) -> (u8, u8, u8) {
+        *self.hsv.get_or_init(|| to_hsv(self.rgb))
+    }
+
+    fn lab(&self) -> [f32; 3] {
+        *self.lab.get_or_init(|| to_lab(self.rgb))
+    }
+}
+
+/// A reference color, converted once for the comparisons of a `ColorMatchMode`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorReference {
+    Rgb([u8; 3]),
+    /// Hue and saturation, both scaled to `0..=255`
+    Hsv {
+        hue: u8,
+        saturation: u8,
+    },
+    Lab([f32; 3]),
+}
+
+impl ColorReference {
+    pub fn new(color: [u8; 3], mode: ColorMatchMode) -> Self {
+        match mode {
+            ColorMatchMode::Rgb => ColorReference::Rgb(color),
+            ColorMatchMode::Hsv => {
+                let (hue, saturation, _) = to_hsv(color);
+                ColorReference::Hsv { hue, saturation }
+            }
+            ColorMatchMode::Lab => ColorReference::Lab(to_lab(color)),
+        }
+    }
+
+    /// How far `px` is from the reference, in the unit of the tolerances of its mode:
+    /// - RGB: largest difference of a channel
+    /// - HSV: largest difference of hue and saturation, ignoring brightness
+    /// - Lab: perceptual distance (CIE76 ΔE), rounded
+    pub fn distance(&self, px: &PixelColor) -> u32 {
+        match self {
+            ColorReference::Rgb(color) => (0..3)
+                .map(|i| px.rgb[i].abs_diff(color[i]) as u32)
+                .max()
+                .unwrap_or(0),
+            ColorReference::Hsv { hue, saturation } => {
+                let (px_hue, px_saturation, value) = px.hsv();
+                if value < MIN_HSV_VALUE {
+                    return u32::MAX;
+                }
+                let saturation_distance = px_saturation.abs_diff(*saturation) as u32;
+                if *saturation < ACHROMATIC_SATURATION {
+                    return saturation_distance;
+                }
+                // Hue wraps around
+                let hue_distance = px_hue.abs_diff(*hue) as u32;
+                saturation_distance.max(hue_distance.min(256 - hue_distance))
+            }
+            ColorReference::Lab(lab) => {
+                let px = px.lab();
+                let squared = (0..3).map(|i| (px[i] - lab[i]).powi(2)).sum::<f32>();
+                squared.sqrt().round() as u32
+            }
+        }
+    }
+
+    #[inline(always)]
+    pub fn matches(&self, px: &PixelColor, tolerance: u8) -> bool {
+        self.distance(px) <= tolerance as u32
+    }
+}
+
+/// Hue, saturation and value of an RGB color, all scaled to `0..=255`.
+fn to_hsv([r, g, b]: [u8; 3]) -> (u8, u8, u8) {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = (max - min) as f32;
+    if max == 0 || delta == 0.0 {
+        return (0, 0, max);
+    }
+
+    let (r, g, b) = (r as f32, g as f32, b as f32);
+    let sixths = if max as f32 == r {
+        (g - b) / delta
+    } else if max as f32 == g {
+        (b - r) / delta + 2.0
+    } else {
+        (r - g) / delta + 4.0
+    };
+    let hue = (sixths / 6.0).rem_euclid(1.0) * 256.0;
+    let saturation = delta * 255.0 / max as f32;
+    ((hue as u32 % 256) as u8, saturation.round() as u8, max)
+}
+
+/// CIE L*a*b* of an sRGB color, with a D65 white point.
+fn to_lab(color: [u8; 3]) -> [f32; 3] {
+    static LINEAR: OnceLock<[f32; 256]> = OnceLock::new();
+    let linear = LINEAR.get_or_init(|| {
+        let mut table = [0.0; 256];
+        for (value, linear) in table.iter_mut().enumerate() {
+            let value = value as f32 / 255.0;
+            *linear = match value <= 0.04045 {
+                true => value / 12.92,
+                false => ((value + 0.055) / 1.055).powf(2.4),
+            };
+        }
+        table
+    });
+    let [r, g, b] = color.map(|channel| linear[channel as usize]);
+
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+    let f = |t: f32| match t > 0.008856 {
+        true => t.cbrt(),
+        false => 7.787 * t + 16.0 / 116.0,
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        image_analyzer::color_match::{ColorReference, PixelColor},
+        ipc::ColorMatchMode,
+    };
+
+    #[test]
+    fn test_color_match_modes() {
+        let name = [234, 234, 149];
+        // The same name on a darker night map
+        let darker = [164, 164, 104];
+        let grass = [60, 140, 50];
+
+        let rgb = ColorReference::new(name, ColorMatchMode::Rgb);
+        assert!(rgb.matches(&PixelColor::new(&[230, 238, 149, 255]), 5));
+        assert!(!rgb.matches(&PixelColor::new(&darker), 10));
+
+        let hsv = ColorReference::new(name, ColorMatchMode::Hsv);
+        assert!(hsv.matches(&PixelColor::new(&darker), 5));
+        assert!(!hsv.matches(&PixelColor::new(&grass), 20));
+        assert!(!hsv.matches(&PixelColor::new(&[0, 0, 0]), 255));
+
+        let lab = ColorReference::new(name, ColorMatchMode::Lab);
+        assert_eq!(lab.distance(&PixelColor::new(&name)), 0);
+        assert!(lab.distance(&PixelColor::new(&[230, 234, 151])) <= 2);
+        assert!(lab.distance(&PixelColor::new(&darker)) < lab.distance(&PixelColor::new(&grass)));
+
+        // Pixels are only converted to the color spaces they're compared in
+        let px = PixelColor::new(&darker);
+        assert!(hsv.matches(&px, 5) && rgb.distance(&px) > 5);
+        assert!(px.hsv.get().is_some());
+        assert!(px.lab.get().is_none());
+
+        // Hues wrap around from red to red
+        let red = ColorReference::new([200, 10, 20], ColorMatchMode::Hsv);
+        assert!(red.matches(&PixelColor::new(&[200, 20, 10]), 10));
+    }
+}
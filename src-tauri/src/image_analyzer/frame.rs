@@ -0,0 +1,390 @@
+use std::{
+    sync::{Arc, OnceLock},
+    time::Instant,
+};
+
+use slog::Logger;
+
+use crate::{
+    data::{
+        Bounds, Minimap, MobType, Neighbourhood, PointCloud, StatusBarConfig, StatusBarKind,
+        Target, TargetType,
+    },
+    image_analyzer::{
+        BarColors, Color, Detector, FrameScan, FrameScanner, ImageAnalyzer, ImageBuffer, MobClouds,
+        MobColors, FLIGHT_GAUGE_COLOR, FLIGHT_GAUGE_MIN_PIXELS, FLIGHT_GAUGE_TOLERANCE,
+        MINIMAP_ARROW_COLOR, MINIMAP_MOB_COLOR, MINIMAP_PARTY_COLOR, MINIMAP_TOLERANCE,
+        MOB_NAME_NEIGHBOURHOOD, STATUS_BAR_TOLERANCE, STAT_TEXT_COLOR, STAT_TEXT_TOLERANCE,
+        TARGET_MARKER_TOLERANCE,
+    },
+    ipc::FarmingConfig,
+    layout::{HudLayout, HudRegions},
+    utils::Timer,
+};
+
+/// A captured frame together with everything derived from its pixels alone.
+///
+/// Frames are moved through the pipeline, never cloned: the buffer is shared with whoever
+/// keeps it for later (e.g. the flight recorder), and state that outlives a frame (like
+/// `ClientStats`) is kept elsewhere.
+#[derive(Debug)]
+pub struct Frame {
+    image: Arc<ImageBuffer>,
+    hud: HudRegions,
+    /// Mob colors searched by the frame scan
+    mob_colors: MobColors,
+    /// Stat bar colors searched by the frame scan
+    bar_colors: BarColors,
+    /// Scan of the frame, made on first use
+    scan: OnceLock<FrameScan>,
+    /// Mobs of the scan with their name plates read, made on first use, and the neighbourhood
+    /// their glyphs were joined with
+    mobs: OnceLock<(Neighbourhood, Vec<Target>)>,
+}
+
+impl Frame {
+    pub fn new(
+        image: impl Into<Arc<ImageBuffer>>,
+        layout: &HudLayout,
+        scale_factor: f64,
+        mob_colors: MobColors,
+        bar_colors: BarColors,
+    ) -> Self {
+        let image = image.into();
+        let hud = layout.resolve(image.width(), image.height(), scale_factor);
+        Self {
+            image,
+            hud,
+            mob_colors,
+            bar_colors,
+            scan: OnceLock::new(),
+            mobs: OnceLock::new(),
+        }
+    }
+
+    pub fn image(&self) -> &ImageBuffer {
+        &self.image
+    }
+
+    /// The buffer, to keep it around without copying it.
+    pub fn shared_image(&self) -> Arc<ImageBuffer> {
+        self.image.clone()
+    }
+
+    /// A frame around the same buffer and settings, which scans it again if needed. Used to
+    /// keep a frame for later without copying its pixels.
+    pub fn share(&self) -> Frame {
+        Self {
+            image: self.image.clone(),
+            hud: self.hud.clone(),
+            mob_colors: self.mob_colors,
+            bar_colors: self.bar_colors,
+            scan: OnceLock::new(),
+            mobs: OnceLock::new(),
+        }
+    }
+
+    /// Give back the buffer, e.g. to build a frame with other settings around it.
+    pub fn into_image(self) -> Arc<ImageBuffer> {
+        self.image
+    }
+
+    /// Size of the frame as `(width, height)`.
+    pub fn size(&self) -> (u32, u32) {
+        (self.image.width(), self.image.height())
+    }
+
+    /// HUD regions resolved for the size of this frame.
+    pub fn hud(&self) -> &HudRegions {
+        &self.hud
+    }
+
+    pub fn mob_colors(&self) -> MobColors {
+        self.mob_colors
+    }
+
+    pub fn bar_colors(&self) -> BarColors {
+        self.bar_colors
+    }
+
+    /// All detectors run in a single pass over the frame the first time this is called.
+    pub fn scan(&self) -> &FrameScan {
+        self.scan.get_or_init(|| self.scan_frame())
+    }
+
+    fn scan_frame(&self) -> FrameScan {
+        let mut scanner = FrameScanner::new();
+
+        let status_bar = |scanner: &mut FrameScanner, kind: StatusBarKind| {
+            let config = StatusBarConfig::from_layout(kind, &self.hud, &self.bar_colors);
+            scanner.add(
+                Detector::new(config.refs, STATUS_BAR_TOLERANCE).in_region(Bounds::new(
+                    config.min_x,
+                    config.min_y,
+                    config.max_x - config.min_x,
+                    config.max_y - config.min_y,
+                )),
+            )
+        };
+        let hp = status_bar(&mut scanner, StatusBarKind::Hp);
+        let mp = status_bar(&mut scanner, StatusBarKind::Mp);
+        let fp = status_bar(&mut scanner, StatusBarKind::Fp);
+        let target_hp = status_bar(&mut scanner, StatusBarKind::TargetHP);
+        let target_mp = status_bar(&mut scanner, StatusBarKind::TargetMP);
+        let [r, g, b] = STAT_TEXT_COLOR;
+        let stat_text = scanner.add(
+            Detector::new(vec![Color::new(r, g, b)], STAT_TEXT_TOLERANCE)
+                .in_region(self.hud.stat_tray),
+        );
+        let blue_marker = scanner.add(Detector::new(
+            vec![ImageAnalyzer::target_marker_color(true)],
+            TARGET_MARKER_TOLERANCE,
+        ));
+        let red_marker = scanner.add(Detector::new(
+            vec![ImageAnalyzer::target_marker_color(false)],
+            TARGET_MARKER_TOLERANCE,
+        ));
+        let mob_detectors =
+            ImageAnalyzer::add_mob_detectors(&mut scanner, self.mob_colors, &self.hud);
+        let minimap_detectors = self.hud.minimap.map(|bounds| {
+            let detector = |[r, g, b]: [u8; 3], region: Bounds| {
+                Detector::new(vec![Color::new(r, g, b)], MINIMAP_TOLERANCE).in_region(region)
+            };
+            // The player arrow always stays in the middle of the minimap
+            let center = Bounds::new(
+                bounds.x + bounds.w / 3,
+                bounds.y + bounds.h / 3,
+                bounds.w / 3,
+                bounds.h / 3,
+            );
+            [
+                scanner.add(detector(MINIMAP_ARROW_COLOR, center)),
+                scanner.add(detector(MINIMAP_MOB_COLOR, bounds)),
+                scanner.add(detector(MINIMAP_PARTY_COLOR, bounds)),
+            ]
+        });
+        let [r, g, b] = FLIGHT_GAUGE_COLOR;
+        let flight_gauge = self.hud.flight_gauge.map(|bounds| {
+            scanner.add(
+                Detector::new(vec![Color::new(r, g, b)], FLIGHT_GAUGE_TOLERANCE).in_region(bounds),
+            )
+        });
+
+        let mut result = scanner.scan(&self.image);
+        let [minimap_arrow, minimap_mobs, minimap_party] = minimap_detectors
+            .map(|ids| ids.map(|id| result.take(id)))
+            .unwrap_or_default();
+        FrameScan {
+            hp: result.take(hp),
+            mp: result.take(mp),
+            fp: result.take(fp),
+            target_hp: result.take(target_hp),
+            target_mp: result.take(target_mp),
+            stat_text: result.take(stat_text),
+            blue_marker: result.take(blue_marker),
+            red_marker: result.take(red_marker),
+            mobs: ImageAnalyzer::take_mob_clouds(&mut result, mob_detectors, self.mob_colors),
+            minimap_arrow,
+            minimap_mobs,
+            minimap_party,
+            flight_gauge: flight_gauge.map(|id| result.take(id)).unwrap_or_default(),
+        }
+    }
+
+    /// Whether the flight gauge is shown, `None` if the layout doesn't know where it is.
+    pub fn is_flying(&self) -> Option<bool> {
+        self.hud.flight_gauge?;
+        Some(self.scan().flight_gauge.len() >= FLIGHT_GAUGE_MIN_PIXELS)
+    }
+
+    /// Player heading and nearby dots, `None` if the minimap isn't in the layout or is closed.
+    pub fn minimap(&self) -> Option<Minimap> {
+        let scan = self.scan();
+        Minimap::from_clouds(
+            self.hud.minimap?,
+            &scan.minimap_arrow,
+            &scan.minimap_mobs,
+            &scan.minimap_party,
+        )
+    }
+
+    /// Scan the frame for mob names only.
+    fn scan_mobs(&self, colors: MobColors) -> MobClouds {
+        let mut scanner = FrameScanner::new();
+        let detectors = ImageAnalyzer::add_mob_detectors(&mut scanner, colors, &self.hud);
+        ImageAnalyzer::take_mob_clouds(&mut scanner.scan(&self.image), detectors, colors)
+    }
+
+    /// Mobs passing the filters of `config`. Name plates are read once per frame, whoever
+    /// asks for the mobs, unless the config searches other colors than the frame scan.
+    pub fn identify_mobs(&self, config: &FarmingConfig) -> Vec<Target> {
+        let _timer = Timer::start_new("identify_mobs");
+
+        let colors = MobColors::from_config(config);
+        let neighbourhood = config.mobs_name_neighbourhood();
+        let rescanned;
+        let mobs = match self.mobs_of_scan(colors, neighbourhood) {
+            Some(mobs) => mobs,
+            None => {
+                rescanned = Self::read_mobs(&self.scan_mobs(colors), neighbourhood);
+                &rescanned
+            }
+        };
+        mobs.iter()
+            .filter(|mob| ImageAnalyzer::is_mob_allowed(config, mob))
+            .copied()
+            .collect()
+    }
+
+    /// Pixels of every name plate found with the colors and gap of `config`, unfiltered.
+    pub fn name_plates(&self, config: &FarmingConfig) -> Vec<PointCloud> {
+        let clouds = self.scan_mobs(MobColors::from_config(config));
+        [clouds.passive, clouds.aggressive, clouds.violet]
+            .iter()
+            .flat_map(|cloud| cloud.cluster_connected(config.mobs_name_neighbourhood(), 1))
+            .map(|cluster| cluster.points)
+            .collect()
+    }
+
+    /// Mobs of the frame scan, unless the config searches other colors or joins glyphs
+    /// differently than on first use.
+    fn mobs_of_scan(
+        &self,
+        colors: MobColors,
+        neighbourhood: Neighbourhood,
+    ) -> Option<&Vec<Target>> {
+        if colors != self.scan().mobs.colors {
+            return None;
+        }
+        let (read_with, mobs) = self.mobs.get_or_init(|| {
+            (
+                neighbourhood,
+                Self::read_mobs(&self.scan().mobs, neighbourhood),
+            )
+        });
+        (*read_with == neighbourhood).then_some(mobs)
+    }
+
+    /// Mobs of `clouds` with their name plates read, aggressive mobs first.
+    fn read_mobs(clouds: &MobClouds, neighbourhood: Neighbourhood) -> Vec<Target> {
+        // Categorize mobs
+        let mobs_pas = ImageAnalyzer::merge_cloud_into_mobs(
+            &clouds.passive,
+            TargetType::Mob(MobType::Passive),
+            neighbourhood,
+        );
+        let mobs_agg = ImageAnalyzer::merge_cloud_into_mobs(
+            &clouds.aggressive,
+            TargetType::Mob(MobType::Aggressive),
+            neighbourhood,
+        );
+        let mobs_violet = ImageAnalyzer::merge_cloud_into_mobs(
+            &clouds.violet,
+            TargetType::Mob(MobType::Violet),
+            neighbourhood,
+        );
+
+        // Return all mobs
+        Vec::from_iter(mobs_agg.into_iter().chain(mobs_pas).chain(mobs_violet))
+    }
+
+    pub fn identify_target_marker(&self, blue_target: bool) -> Option<Target> {
+        let _timer = Timer::start_new("identify_target_marker");
+        let scan = self.scan();
+        let cloud = match blue_target {
+            true => &scan.blue_marker,
+            false => &scan.red_marker,
+        };
+
+        // Identify target marker entities
+        let target_markers = ImageAnalyzer::merge_cloud_into_mobs(
+            cloud,
+            TargetType::TargetMarker,
+            MOB_NAME_NEIGHBOURHOOD,
+        );
+
+        if !blue_target && target_markers.is_empty() {
+            return self.identify_target_marker(true);
+        }
+
+        // Find biggest target marker
+        target_markers.into_iter().max_by_key(|x| x.bounds.size())
+    }
+
+    pub fn get_target_marker_distance(&self, target: Target) -> i32 {
+        // Calculate middle point of player
+        let mid_x = (self.image.width() / 2) as i32;
+        let mid_y = (self.image.height() / 2) as i32;
+
+        // Calculate 2D euclidian distances to player
+        let point = target.bounds.get_lowest_center_point();
+
+        (((mid_x - (point.x as i32)).pow(2) + (mid_y - (point.y as i32)).pow(2)) as f64).sqrt()
+            as i32
+    }
+
+    /// Distance: `[0..=500]`
+    pub fn find_closest_mob<'a>(
+        &self,
+        mobs: &'a [Target],
+        //avoid_bounds: Option<&Bounds>,
+        avoid_list: Option<&Vec<(Bounds, Instant, u128)>>,
+        max_distance: i32,
+        _logger: &Logger,
+    ) -> Option<&'a Target> {
+        let _timer = Timer::start_new("find_closest_mob");
+
+        // Calculate middle point of player
+        let mid_x = (self.image.width() / 2) as i32;
+        let mid_y = (self.image.height() / 2) as i32;
+
+        // Calculate 2D euclidian distances to player
+        let mut distances = Vec::default();
+        for mob in mobs {
+            let point = mob.get_attack_coords();
+            let distance = (((mid_x - (point.x as i32)).pow(2) + (mid_y - (point.y as i32)).pow(2))
+                as f64)
+                .sqrt() as i32;
+            distances.push((mob, distance));
+        }
+
+        // Sort by distance
+        distances.sort_by_key(|&(_, distance)| distance);
+
+        // Remove mobs that are too far away
+        distances = distances
+            .into_iter()
+            .filter(|&(_, distance)| distance <= max_distance)
+            .collect();
+
+        if let Some(avoided_bounds) = avoid_list {
+            // Try finding closest mob that's not the mob to be avoided
+            if let Some((mob, _distance)) = distances.iter().find(|(mob, _distance)| {
+                //*distance > 55
+                let coords = mob.get_attack_coords();
+                let mut result = true;
+                for avoided_item in avoided_bounds {
+                    if avoided_item.0.contains_point(&coords) {
+                        //slog::debug!(logger, ""; "Avoided bounds" => avoided_item.0);
+                        result = false;
+                        break;
+                    }
+                }
+                result // && *distance > 20
+                       // let coords = mob.name_bounds.get_lowest_center_point();
+                       // !avoid_bounds.grow_by(100).contains_point(&coords) && *distance > 200
+            }) {
+                Some(mob)
+            } else {
+                None
+            }
+        } else {
+            // Return closest mob
+            if let Some((mob, _distance)) = distances.first() {
+                Some(*mob)
+            } else {
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use rayon::prelude::*;
+
+use crate::{
+    data::{Bounds, Point, PointCloud},
+    image_analyzer::{Color, ColorReference, ImageBuffer, PixelColor},
+    ipc::ColorMatchMode,
+    platform::{IGNORE_AREA_BOTTOM, IGNORE_AREA_TOP},
+    utils::Timer,
+};
+
+/// Handle of a detector registered with a `FrameScanner`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DetectorId(usize);
+
+/// Finds the pixels of some reference colors, optionally inside a region of the frame.
+#[derive(Debug, Clone)]
+pub struct Detector {
+    colors: Vec<Color>,
+    /// `colors` as compared by the match mode
+    references: Vec<ColorReference>,
+    tolerance: u8,
+    region: Option<Bounds>,
+    ignore_areas: Vec<Bounds>,
+    exclusive: bool,
+}
+
+impl Detector {
+    /// Match `colors` channel by channel, see `ColorMatchMode::Rgb`.
+    pub fn new(colors: Vec<Color>, tolerance: u8) -> Self {
+        Self {
+            references: Self::references(&colors, ColorMatchMode::Rgb),
+            colors,
+            tolerance,
+            region: None,
+            ignore_areas: vec![],
+            exclusive: false,
+        }
+    }
+
+    /// Compare pixels against the colors in another way, the tolerance is in its unit.
+    pub fn matching(mut self, mode: ColorMatchMode) -> Self {
+        self.references = Self::references(&self.colors, mode);
+        self
+    }
+
+    fn references(colors: &[Color], mode: ColorMatchMode) -> Vec<ColorReference> {
+        colors
+            .iter()
+            .map(|color| ColorReference::new(color.refs, mode))
+            .collect()
+    }
+
+    /// Only search inside `region` instead of the whole frame.
+    pub fn in_region(mut self, region: Bounds) -> Self {
+        self.region = Some(region);
+        self
+    }
+
+    /// Skip pixels inside any of `areas`.
+    pub fn ignoring(mut self, areas: Vec<Bounds>) -> Self {
+        self.ignore_areas = areas;
+        self
+    }
+
+    /// A pixel is claimed by the first exclusive detector matching it, in registration order.
+    pub fn exclusive(mut self) -> Self {
+        self.exclusive = true;
+        self
+    }
+
+    fn covers_row(&self, y: u32) -> bool {
+        self.region
+            .map(|region| y >= region.y && y <= region.y + region.h)
+            .unwrap_or(true)
+    }
+
+    fn covers(&self, point: &Point) -> bool {
+        self.region
+            .map(|region| region.contains_point(point))
+            .unwrap_or(true)
+            && !self.ignore_areas.iter().any(|area| area.contains_point(point))
+    }
+
+    fn matches(&self, px: &PixelColor) -> bool {
+        self.references
+            .iter()
+            .any(|reference| reference.matches(px, self.tolerance))
+    }
+}
+
+/// Runs any number of detectors over a frame in a single pass.
+#[derive(Debug, Clone, Default)]
+pub struct FrameScanner {
+    detectors: Vec<Detector>,
+}
+
+/// Points found by each detector of a scan.
+#[derive(Debug, Clone, Default)]
+pub struct ScanResult {
+    clouds: Vec<PointCloud>,
+}
+
+impl ScanResult {
+    pub fn cloud(&self, id: DetectorId) -> &PointCloud {
+        &self.clouds[id.0]
+    }
+
+    pub fn take(&mut self, id: DetectorId) -> PointCloud {
+        std::mem::take(&mut self.clouds[id.0])
+    }
+}
+
+impl FrameScanner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, detector: Detector) -> DetectorId {
+        self.detectors.push(detector);
+        DetectorId(self.detectors.len() - 1)
+    }
+
+    /// Classify every pixel of `image` once. Rows hidden behind the window frame or the
+    /// bottom HUD (see `platform::IGNORE_AREA_TOP`) are skipped for all detectors.
+    pub fn scan(&self, image: &ImageBuffer) -> ScanResult {
+        let _timer = Timer::start_new("frame_scan");
+        let last_row = image
+            .height()
+            .checked_sub(IGNORE_AREA_BOTTOM)
+            .unwrap_or(image.height());
+
+        let hits = (0..image.height())
+            .into_par_iter()
+            .map(|y| {
+                #[allow(clippy::absurd_extreme_comparisons)] // not always 0 (macOS)
+                if y <= IGNORE_AREA_TOP || y > last_row {
+                    return vec![];
+                }
+                self.scan_row(image, y)
+            })
+            .collect::<Vec<_>>();
+
+        let mut clouds = vec![PointCloud::default(); self.detectors.len()];
+        for (index, point) in hits.into_iter().flatten() {
+            clouds[index].push(point);
+        }
+        ScanResult { clouds }
+    }
+
+    fn scan_row(&self, image: &ImageBuffer, y: u32) -> Vec<(usize, Point)> {
+        let active = self
+            .detectors
+            .iter()
+            .enumerate()
+            .filter(|(_, detector)| detector.covers_row(y))
+            .collect::<Vec<_>>();
+        if active.is_empty() {
+            return vec![];
+        }
+
+        let mut hits = vec![];
+        for x in 0..image.width() {
+            let px = image.get_pixel(x, y).0;
+            if px[3] != 255 {
+                break;
+            }
+
+            let point = Point::new(x, y);
+            // Shared by all detectors, so the pixel is converted once per color space
+            let px = PixelColor::new(&px);
+            let mut claimed = false;
+            for (index, detector) in active.iter() {
+                if (claimed && detector.exclusive) || !detector.covers(&point) {
+                    continue;
+                }
+                if detector.matches(&px) {
+                    hits.push((*index, point));
+                    claimed |= detector.exclusive;
+                }
+            }
+        }
+        hits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use crate::{
+        data::Bounds,
+        image_analyzer::{
+            frame_scanner::{Detector, FrameScanner},
+            Color,
+        },
+    };
+
+    #[test]
+    fn test_frame_scanner() {
+        let mut image = RgbaImage::from_pixel(40, 20, Rgba([0, 0, 0, 255]));
+        for x in 0..40 {
+            image.put_pixel(x, 5, Rgba([200, 10, 10, 255]));
+            image.put_pixel(x, 6, Rgba([10, 10, 200, 255]));
+        }
+
+        let mut scanner = FrameScanner::new();
+        let red = scanner.add(Detector::new(vec![Color::new(200, 10, 10)], 5));
+        let red_region = scanner.add(
+            Detector::new(vec![Color::new(202, 12, 12)], 5).in_region(Bounds::new(10, 0, 9, 19)),
+        );
+        let any = scanner.add(
+            Detector::new(vec![Color::new(200, 10, 10), Color::new(10, 10, 200)], 5)
+                .ignoring(vec![Bounds::new(0, 0, 29, 19)])
+                .exclusive(),
+        );
+        let blue = scanner.add(Detector::new(vec![Color::new(10, 10, 200)], 5).exclusive());
+        let result = scanner.scan(&image);
+
+        assert_eq!(result.cloud(red).len(), 40);
+        assert_eq!(result.cloud(red_region).to_bounds(), Bounds::new(10, 5, 9, 0));
+        assert_eq!(result.cloud(any).len(), 20);
+        // Blue pixels right of the ignored area were claimed by `any`
+        assert_eq!(result.cloud(blue).len(), 30);
+    }
+}
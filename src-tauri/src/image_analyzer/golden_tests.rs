@@ -0,0 +1,204 @@
+//! Golden-screenshot regression suite, see `fixtures/golden/README.md`.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::{
+    data::{Bounds, MobType, TargetType},
+    image_analyzer::ImageAnalyzer,
+    ipc::FarmingConfig,
+    replay::FrameReplay,
+};
+
+/// Allowed deviation of detected bounds in pixels
+const BOUNDS_TOLERANCE: u32 = 2;
+
+/// Allowed deviation of status bar percentages
+const STAT_TOLERANCE: u32 = 1;
+
+#[derive(Debug, Deserialize)]
+struct ExpectedMob {
+    mob_type: MobType,
+    bounds: Bounds,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    level: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GoldenCase {
+    description: String,
+    frames: Vec<PathBuf>,
+    #[serde(default)]
+    farming_config: FarmingConfig,
+    mobs: Vec<ExpectedMob>,
+    target_marker: Option<Bounds>,
+    hp: u32,
+    mp: u32,
+    fp: u32,
+    target_hp: u32,
+    /// Exact `(current, max)` values of bars, keyed by `hp`, `mp` or `fp`
+    #[serde(default)]
+    stat_numbers: BTreeMap<String, (u32, u32)>,
+}
+
+fn fixtures_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("fixtures")
+        .join("golden")
+}
+
+fn bounds_match(a: &Bounds, b: &Bounds) -> bool {
+    a.x.abs_diff(b.x) <= BOUNDS_TOLERANCE
+        && a.y.abs_diff(b.y) <= BOUNDS_TOLERANCE
+        && a.w.abs_diff(b.w) <= BOUNDS_TOLERANCE
+        && a.h.abs_diff(b.h) <= BOUNDS_TOLERANCE
+}
+
+/// Replay a case and return a description of every mismatch.
+fn check_case(case: &GoldenCase) -> Vec<String> {
+    let logger = slog::Logger::root(slog::Discard, slog::o!());
+    let replay =
+        FrameReplay::from_files(case.frames.iter().map(|frame| fixtures_dir().join(frame)));
+    let mut analyzer = ImageAnalyzer::headless();
+    if let Err(err) = replay.run(&mut analyzer, &logger, |_, _| {}) {
+        return vec![format!("failed to load frames: {}", err)];
+    }
+
+    let mut failures = vec![];
+
+    // Mobs
+    let mut detected = analyzer.identify_mobs(&case.farming_config);
+    for expected in case.mobs.iter() {
+        let position = detected.iter().position(|mob| {
+            mob.target_type == TargetType::Mob(expected.mob_type)
+                && bounds_match(&mob.bounds, &expected.bounds)
+        });
+        match position {
+            Some(index) => {
+                let mob = detected.remove(index);
+                let name = mob.name.map(|name| name.to_string());
+                if expected.name.is_some() && name != expected.name {
+                    failures.push(format!(
+                        "mob at {:?} is named {:?}, expected {:?}",
+                        mob.bounds, name, expected.name
+                    ));
+                }
+                if expected.level.is_some() && mob.level != expected.level {
+                    failures.push(format!(
+                        "mob at {:?} is level {:?}, expected {:?}",
+                        mob.bounds, mob.level, expected.level
+                    ));
+                }
+            }
+            None => failures.push(format!(
+                "missing {:?} mob at {:?}",
+                expected.mob_type, expected.bounds
+            )),
+        }
+    }
+    for mob in detected {
+        failures.push(format!(
+            "unexpected {:?} at {:?}",
+            mob.target_type, mob.bounds
+        ));
+    }
+
+    // Target marker, as seen by the stats update and by a direct lookup
+    let markers = [
+        ("ClientStats::update", analyzer.client_stats.target_marker),
+        (
+            "identify_target_marker",
+            analyzer.identify_target_marker(false),
+        ),
+    ];
+    for (source, marker) in markers {
+        match (marker.map(|marker| marker.bounds), case.target_marker) {
+            (Some(found), Some(expected)) if !bounds_match(&found, &expected) => {
+                failures.push(format!(
+                    "{}: target marker at {:?}, expected {:?}",
+                    source, found, expected
+                ))
+            }
+            (Some(found), None) => failures.push(format!(
+                "{}: unexpected target marker at {:?}",
+                source, found
+            )),
+            (None, Some(expected)) => failures.push(format!(
+                "{}: missing target marker at {:?}",
+                source, expected
+            )),
+            _ => {}
+        }
+    }
+
+    // Status bars
+    let stats = &analyzer.client_stats;
+    let bars = [
+        ("hp", stats.hp.value, case.hp),
+        ("mp", stats.mp.value, case.mp),
+        ("fp", stats.fp.value, case.fp),
+        ("target_hp", stats.target_hp.value, case.target_hp),
+    ];
+    for (name, value, expected) in bars {
+        if value.abs_diff(expected) > STAT_TOLERANCE {
+            failures.push(format!("{} is {}%, expected {}%", name, value, expected));
+        }
+    }
+    for (name, expected) in case.stat_numbers.iter() {
+        let stat = match name.as_str() {
+            "hp" => &stats.hp,
+            "mp" => &stats.mp,
+            "fp" => &stats.fp,
+            _ => {
+                failures.push(format!("unknown stat {:?}", name));
+                continue;
+            }
+        };
+        let found = stat.current.zip(stat.max);
+        if found != Some(*expected) {
+            failures.push(format!(
+                "{} reads {:?}, expected {:?}",
+                name, found, expected
+            ));
+        }
+    }
+
+    failures
+}
+
+// macOS captures include the title bar (see `platform::IGNORE_AREA_TOP`), the fixtures don't.
+#[cfg(not(target_os = "macos"))]
+#[test]
+fn test_golden_screenshots() {
+    let mut case_paths = fs::read_dir(fixtures_dir())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().map(|ext| ext == "json").unwrap_or(false))
+        .collect::<Vec<_>>();
+    case_paths.sort();
+    assert!(!case_paths.is_empty(), "no golden cases found");
+
+    let mut report = vec![];
+    for path in case_paths {
+        let case: GoldenCase = serde_json::from_str(&fs::read_to_string(&path).unwrap())
+            .unwrap_or_else(|err| panic!("invalid golden case {}: {}", path.display(), err));
+        let failures = check_case(&case);
+        if !failures.is_empty() {
+            report.push(format!(
+                "{} ({}):\n  {}",
+                path.file_name().unwrap().to_string_lossy(),
+                case.description,
+                failures.join("\n  ")
+            ));
+        }
+    }
+
+    assert!(report.is_empty(), "\n{}", report.join("\n"));
+}
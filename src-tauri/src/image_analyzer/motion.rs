@@ -0,0 +1,170 @@
+//! Notices when the character walks without the scene around it changing, e.g. against a wall.
+
+use crate::{
+    data::{Bounds, Point},
+    image_analyzer::Frame,
+    platform::{IGNORE_AREA_BOTTOM, IGNORE_AREA_TOP},
+};
+
+/// Cells per side of the grid a scene is reduced to
+const GRID_SIZE: u32 = 16;
+/// Only every this many pixels of a row and column are sampled
+const SAMPLE_STEP: u32 = 4;
+/// Mean brightness difference of the cells below which two frames show the same scene
+const STILL_THRESHOLD: f32 = 2.0;
+/// Meters walked between two frames for their likeness to matter
+const MIN_WALKED_DISTANCE: f32 = 1.0;
+/// Successive frames showing the same scene while walking before the character is stuck
+const STILL_FRAMES: u32 = 3;
+
+/// Brightness of a frame on a coarse grid, leaving out the HUD.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneThumbnail {
+    /// Mean brightness per cell, `None` for cells fully covered by the HUD
+    cells: Vec<Option<f32>>,
+}
+
+impl SceneThumbnail {
+    pub fn new(frame: &Frame) -> Self {
+        let (width, height) = frame.size();
+        #[allow(clippy::unnecessary_min_or_max)] // not always 0 (macOS)
+        let top = IGNORE_AREA_TOP.min(height);
+        let bottom = height.saturating_sub(IGNORE_AREA_BOTTOM).max(top);
+        let hud = frame.hud();
+        let hud_areas = [hud.stat_tray, hud.target_hp_bar, hud.target_mp_bar]
+            .into_iter()
+            .chain(hud.mob_ignore_areas.iter().copied())
+            .chain(hud.minimap)
+            .chain(hud.flight_gauge)
+            .collect::<Vec<Bounds>>();
+
+        let cell_count = (GRID_SIZE * GRID_SIZE) as usize;
+        let (mut sums, mut counts) = (vec![0_u64; cell_count], vec![0_u32; cell_count]);
+        let image = frame.image();
+        for y in (top..bottom).step_by(SAMPLE_STEP as usize) {
+            let row = ((y - top) * GRID_SIZE / (bottom - top)) as usize;
+            for x in (0..width).step_by(SAMPLE_STEP as usize) {
+                let point = Point::new(x, y);
+                if hud_areas.iter().any(|area| area.contains_point(&point)) {
+                    continue;
+                }
+                let cell = row * GRID_SIZE as usize + (x * GRID_SIZE / width) as usize;
+                let [r, g, b, _] = image.get_pixel(x, y).0;
+                sums[cell] += r as u64 + g as u64 + b as u64;
+                counts[cell] += 1;
+            }
+        }
+
+        Self {
+            cells: sums
+                .into_iter()
+                .zip(counts)
+                .map(|(sum, count)| (count > 0).then(|| sum as f32 / (3 * count) as f32))
+                .collect(),
+        }
+    }
+
+    /// Mean brightness difference of the cells both scenes show, 0 to 255.
+    pub fn difference(&self, other: &SceneThumbnail) -> f32 {
+        let (mut total, mut count) = (0.0, 0);
+        for (a, b) in self.cells.iter().zip(&other.cells) {
+            if let (Some(a), Some(b)) = (a, b) {
+                total += (a - b).abs();
+                count += 1;
+            }
+        }
+        match count {
+            0 => 0.0,
+            _ => total / count as f32,
+        }
+    }
+}
+
+/// Compares successive frames against the distance the movements should have covered.
+#[derive(Debug, Default)]
+pub struct MotionEstimator {
+    /// Scene of the last frame and the distance travelled by then
+    previous: Option<(SceneThumbnail, f32)>,
+    still_frames: u32,
+}
+
+impl MotionEstimator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compare `frame` with the last one, given the total distance `travelled` according to
+    /// the movements played. `true` once walking didn't change the scene for a few frames.
+    pub fn update(&mut self, frame: &Frame, travelled: f32) -> bool {
+        let scene = SceneThumbnail::new(frame);
+        if let Some((previous, previous_travelled)) = &self.previous {
+            if travelled - previous_travelled >= MIN_WALKED_DISTANCE {
+                self.still_frames = match scene.difference(previous) < STILL_THRESHOLD {
+                    true => self.still_frames + 1,
+                    false => 0,
+                };
+            }
+        }
+        self.previous = Some((scene, travelled));
+
+        if self.still_frames >= STILL_FRAMES {
+            self.still_frames = 0;
+            return true;
+        }
+        false
+    }
+
+    /// Forget the last frame, e.g. after the character was moved on purpose.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use crate::{
+        image_analyzer::{BarColors, Frame, MobColors, MotionEstimator},
+        layout::HudLayout,
+    };
+
+    /// A scene with a gradient shifted by `offset`, as when walking forward.
+    fn frame(offset: u32) -> Frame {
+        let image = RgbaImage::from_fn(800, 600, |x, y| {
+            let value = ((x + y + offset) % 256) as u8;
+            Rgba([value, value / 2, 255 - value, 255])
+        });
+        Frame::new(
+            image,
+            &HudLayout::default(),
+            1.0,
+            MobColors::default(),
+            BarColors::default(),
+        )
+    }
+
+    #[test]
+    fn test_detect_stuck() {
+        let mut motion = MotionEstimator::new();
+
+        // Walking through a changing scene
+        for step in 0..6 {
+            assert!(!motion.update(&frame(step * 40), step as f32 * 2.5));
+        }
+
+        // Standing still isn't being stuck
+        for _ in 0..6 {
+            assert!(!motion.update(&frame(200), 12.5));
+        }
+
+        // Walking against a wall
+        let stuck = (1..=3)
+            .map(|step| motion.update(&frame(200), 12.5 + step as f32 * 2.5))
+            .collect::<Vec<_>>();
+        assert_eq!(stuck, vec![false, false, true]);
+
+        // and the count starts over
+        assert!(!motion.update(&frame(200), 22.5));
+    }
+}
@@ -1,7 +1,17 @@
 mod bot_config;
+mod calibration;
 mod frontend_info;
+mod patrol_route;
 
 pub use self::{
-    bot_config::{BotConfig, BotMode, FarmingConfig, ShoutConfig, SlotType, SupportConfig},
-    frontend_info::FrontendInfo,
+    bot_config::{
+        BotConfig, BotMode, ColorMatchMode, FarmingConfig, MobPolicy, ShoutConfig, SlotType, StatValue,
+        SupportConfig,
+    },
+    calibration::{CalibrationRequest, CalibrationResult, CalibrationTarget, HistogramBin},
+    frontend_info::{
+        FrontendInfo, MobPolicyStats, PipelineStats, PreviewFrame, PreviewMob, SkippedInput,
+        StageStats,
+    },
+    patrol_route::{PatrolStep, Waypoint, WaypointAction},
 };
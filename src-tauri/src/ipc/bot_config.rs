@@ -2,6 +2,13 @@ use std::{fmt, fs::File, time::Instant};
 
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    data::{MobType, Neighbourhood, StatusBarKind},
+    image_analyzer::MOB_NAME_NEIGHBOURHOOD,
+    ipc::Waypoint,
+    ocr::UNKNOWN_CHAR,
+};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum SlotType {
     Unused,
@@ -39,6 +46,28 @@ impl fmt::Display for SlotType {
     }
 }
 
+/// What the farming behavior does with a class of mobs.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MobPolicy {
+    Attack,
+    #[default]
+    Ignore,
+    /// Move away when one comes close
+    Avoid,
+}
+
+/// How the pixels of mob names are compared against their reference colors.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColorMatchMode {
+    /// Each RGB channel within the tolerance
+    #[default]
+    Rgb,
+    /// Hue and saturation within the tolerance, whatever the brightness
+    Hsv,
+    /// Perceptual distance within the tolerance
+    Lab,
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct SlotBar {
     slots: Option<[Slot; 10]>,
@@ -67,7 +96,7 @@ impl SlotBar {
     pub fn get_usable_slot_index(
         &self,
         slot_type: SlotType,
-        threshold: Option<u32>,
+        threshold: Option<StatValue>,
         last_slots_usage: [[Option<Instant>; 10]; 9],
         slot_bar_index: usize,
     ) -> Option<(usize, usize)> {
@@ -77,10 +106,10 @@ impl SlotBar {
             .filter(|(index, slot)| {
                 slot.slot_type == slot_type
                     && slot.slot_enabled
-                    && slot.slot_threshold.unwrap_or(100) >= threshold.unwrap_or(0)
+                    && slot.is_triggered_by(threshold)
                     && last_slots_usage[slot_bar_index][*index].is_none()
             })
-            .min_by(|x, y| x.1.slot_threshold.cmp(&y.1.slot_threshold))
+            .min_by_key(|(_, slot)| slot.threshold_rank(threshold))
             //.choose(rng)
             .map(|(index, _)| (slot_bar_index, index))
     }
@@ -105,11 +134,25 @@ impl SlotBar {
     }
 }
 
+/// Percentage an absolute slot threshold stands for until the max stat has been read once
+const UNKNOWN_MAX_THRESHOLD_PERCENT: u32 = 50;
+
+/// A stat as compared against slot thresholds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatValue {
+    pub percent: u32,
+    /// Exact value and maximum, if they could be read
+    pub current: Option<u32>,
+    pub max: Option<u32>,
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Slot {
     slot_type: SlotType,
     slot_cooldown: Option<u32>,
     slot_threshold: Option<u32>,
+    /// Compare `slot_threshold` against the exact stat value instead of the percentage
+    slot_threshold_absolute: Option<bool>,
     slot_enabled: bool,
 }
 
@@ -119,6 +162,7 @@ impl Default for Slot {
             slot_type: SlotType::Unused,
             slot_cooldown: None,
             slot_threshold: None,
+            slot_threshold_absolute: None,
             slot_enabled: true,
         }
     }
@@ -132,6 +176,48 @@ impl Slot {
         }
         Some(100)
     }
+
+    pub fn is_threshold_absolute(&self) -> bool {
+        self.slot_threshold_absolute.unwrap_or(false)
+    }
+
+    /// Key to pick the slot with the lowest threshold first, comparing absolute and percent
+    /// thresholds as shares of the max stat in hundredths of a percent. Absolute thresholds
+    /// come last while the max stat is unknown.
+    fn threshold_rank(&self, stat: Option<StatValue>) -> (bool, u64) {
+        let max = stat.and_then(|stat| stat.max).filter(|max| *max > 0);
+        match (self.slot_threshold, self.is_threshold_absolute(), max) {
+            (None, _, _) => (false, 0),
+            (Some(threshold), false, _) => (false, threshold as u64 * 100),
+            (Some(threshold), true, Some(max)) => (false, threshold as u64 * 10000 / max as u64),
+            (Some(threshold), true, None) => (true, threshold as u64),
+        }
+    }
+
+    /// Whether the stat is at or below the slot threshold. While the exact stat value is
+    /// unknown, absolute thresholds are compared against the bar percentage of the last known
+    /// max, or against `UNKNOWN_MAX_THRESHOLD_PERCENT` if the max was never read.
+    pub fn is_triggered_by(&self, stat: Option<StatValue>) -> bool {
+        let stat = match stat {
+            Some(stat) => stat,
+            None => return true,
+        };
+        let max = stat.max.filter(|max| *max > 0);
+        match (
+            self.slot_threshold,
+            self.is_threshold_absolute(),
+            stat.current,
+            max,
+        ) {
+            (None, _, _, _) => true,
+            (Some(threshold), true, Some(current), _) => threshold >= current,
+            (Some(threshold), true, None, Some(max)) => {
+                threshold as u64 * 100 >= stat.percent as u64 * max as u64
+            }
+            (Some(_), true, None, None) => UNKNOWN_MAX_THRESHOLD_PERCENT >= stat.percent,
+            (Some(threshold), false, _, _) => threshold >= stat.percent,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -170,18 +256,34 @@ pub struct FarmingConfig {
 
     passive_mobs_colors: Option<[Option<u8>; 3]>,
     passive_tolerence: Option<u8>,
+    passive_mobs_policy: Option<MobPolicy>,
+    passive_color_match_mode: Option<ColorMatchMode>,
 
     aggressive_mobs_colors: Option<[Option<u8>; 3]>,
     aggressive_tolerence: Option<u8>,
+    aggressive_mobs_policy: Option<MobPolicy>,
+    aggressive_color_match_mode: Option<ColorMatchMode>,
 
     violet_mobs_colors: Option<[Option<u8>; 3]>,
     violet_tolerence: Option<u8>,
+    violet_mobs_policy: Option<MobPolicy>,
+    violet_color_match_mode: Option<ColorMatchMode>,
+    /// Match mode of the mob types without their own
+    color_match_mode: Option<ColorMatchMode>,
 
     obstacle_avoidance_cooldown: Option<u64>,
     obstacle_avoidance_max_try: Option<u32>,
 
     min_mobs_name_width: Option<u32>,
     max_mobs_name_width: Option<u32>,
+    /// Largest gap in pixels between two glyphs of a name, horizontally and vertically
+    mobs_name_gap_x: Option<u32>,
+    mobs_name_gap_y: Option<u32>,
+
+    /// Only attack mobs with one of these names, if not empty
+    mobs_name_allow_list: Option<Vec<String>>,
+    /// Never attack mobs with one of these names
+    mobs_name_deny_list: Option<Vec<String>>,
 
     min_hp_attack: Option<u32>,
     on_death_disconnect: Option<bool>,
@@ -191,6 +293,23 @@ pub struct FarmingConfig {
 
     on_afk_disconnect: Option<bool>,
     afk_timeout: Option<u64>,
+
+    /// Save an annotated debug frame every this many frames, never if 0
+    debug_frames_interval: Option<u32>,
+
+    /// Walk towards the mob dots of the minimap when no mob is in sight
+    use_minimap: Option<bool>,
+    /// Meters the character may wander from where farming started, unlimited if 0
+    leash_radius: Option<u32>,
+    /// Waypoints walked when no mob is found, one per line, see `Waypoint`
+    patrol_route: Option<Vec<String>>,
+
+    /// Fly to another area with the flying slot when `mobs_timeout` expires, instead of exiting
+    fly_on_mobs_timeout: Option<bool>,
+    /// Where to fly, relative to the take-off, as a `Waypoint`
+    flight_destination: Option<String>,
+    /// How long to fly straight ahead without a destination, in milliseconds
+    flight_duration: Option<u64>,
 }
 
 impl FarmingConfig {
@@ -225,6 +344,49 @@ impl FarmingConfig {
         self.obstacle_avoidance_max_try.unwrap_or(5)
     }
 
+    pub fn debug_frames_interval(&self) -> u32 {
+        self.debug_frames_interval.unwrap_or(0)
+    }
+
+    pub fn use_minimap(&self) -> bool {
+        self.use_minimap.unwrap_or(false)
+    }
+
+    pub fn leash_radius(&self) -> u32 {
+        self.leash_radius.unwrap_or(0)
+    }
+
+    pub fn fly_on_mobs_timeout(&self) -> bool {
+        self.fly_on_mobs_timeout.unwrap_or(false)
+    }
+
+    /// Where to fly, `None` if not set.
+    pub fn flight_destination(&self) -> Result<Option<Waypoint>, String> {
+        self.flight_destination
+            .as_deref()
+            .filter(|line| !line.trim().is_empty())
+            .map(str::parse)
+            .transpose()
+    }
+
+    pub fn flight_duration(&self) -> u128 {
+        self.flight_duration.unwrap_or(10000).into()
+    }
+
+    /// Waypoints of the patrol route, skipping blank lines. Fails on the first invalid line.
+    pub fn patrol_route(&self) -> Result<Vec<Waypoint>, String> {
+        self.patrol_route
+            .iter()
+            .flatten()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                line.parse()
+                    .map_err(|err| format!("line {}: {}", index + 1, err))
+            })
+            .collect()
+    }
+
     pub fn min_mobs_name_width(&self) -> u32 {
         self.min_mobs_name_width.unwrap_or(11)
     }
@@ -233,6 +395,54 @@ impl FarmingConfig {
         self.max_mobs_name_width.unwrap_or(180)
     }
 
+    /// How far apart pixels of mob names may be to belong to the same name.
+    pub fn mobs_name_neighbourhood(&self) -> Neighbourhood {
+        Neighbourhood::new(
+            self.mobs_name_gap_x.unwrap_or(MOB_NAME_NEIGHBOURHOOD.x),
+            self.mobs_name_gap_y.unwrap_or(MOB_NAME_NEIGHBOURHOOD.y),
+        )
+    }
+
+    pub fn mobs_name_allow_list(&self) -> Vec<String> {
+        self.mobs_name_allow_list.clone().unwrap_or_default()
+    }
+
+    pub fn mobs_name_deny_list(&self) -> Vec<String> {
+        self.mobs_name_deny_list.clone().unwrap_or_default()
+    }
+
+    /// Whether the allow or deny list names any mob.
+    pub fn has_mob_name_lists(&self) -> bool {
+        [&self.mobs_name_allow_list, &self.mobs_name_deny_list]
+            .into_iter()
+            .flatten()
+            .flatten()
+            .any(|entry| !entry.trim().is_empty())
+    }
+
+    /// Check a mob name against the allow and deny lists, names are compared case-insensitively.
+    /// Names which couldn't be read are unknown and always allowed.
+    pub fn is_mob_name_allowed(&self, name: Option<&str>) -> bool {
+        let matches = |list: &Option<Vec<String>>, name: &str| {
+            list.iter()
+                .flatten()
+                .any(|entry| entry.trim().eq_ignore_ascii_case(name.trim()))
+        };
+        let has_allow_list = self
+            .mobs_name_allow_list
+            .iter()
+            .flatten()
+            .any(|entry| !entry.trim().is_empty());
+
+        match name {
+            Some(name) if !name.contains(UNKNOWN_CHAR) => {
+                !matches(&self.mobs_name_deny_list, name)
+                    && (!has_allow_list || matches(&self.mobs_name_allow_list, name))
+            }
+            _ => true,
+        }
+    }
+
     pub fn min_hp_attack(&self) -> u32 {
         self.min_hp_attack.unwrap_or(0)
     }
@@ -262,6 +472,26 @@ impl FarmingConfig {
         self.violet_tolerence.unwrap_or(10)
     }
 
+    /// How the names of a type of mobs are compared, the general mode unless set for the type.
+    pub fn color_match_mode(&self, mob_type: MobType) -> ColorMatchMode {
+        match mob_type {
+            MobType::Passive => self.passive_color_match_mode,
+            MobType::Aggressive => self.aggressive_color_match_mode,
+            MobType::Violet => self.violet_color_match_mode,
+        }
+        .or(self.color_match_mode)
+        .unwrap_or_default()
+    }
+
+    /// Policy for a class of mobs, regular mobs are attacked and violets ignored by default.
+    pub fn mob_policy(&self, mob_type: MobType) -> MobPolicy {
+        match mob_type {
+            MobType::Passive => self.passive_mobs_policy.unwrap_or(MobPolicy::Attack),
+            MobType::Aggressive => self.aggressive_mobs_policy.unwrap_or(MobPolicy::Attack),
+            MobType::Violet => self.violet_mobs_policy.unwrap_or_default(),
+        }
+    }
+
     pub fn slot_bars(&self) -> Vec<SlotBar> {
         self.slot_bars
             .map(|slots| slots.into_iter().collect::<Vec<_>>())
@@ -311,7 +541,7 @@ impl FarmingConfig {
     pub fn get_usable_slot_index(
         &self,
         slot_type: SlotType,
-        threshold: Option<u32>,
+        threshold: Option<StatValue>,
         last_slots_usage: [[Option<Instant>; 10]; 9],
     ) -> Option<(usize, usize)> {
         for n in 0..9 {
@@ -350,6 +580,8 @@ pub struct SupportConfig {
     afk_timeout: Option<u64>,
     interval_between_buffs: Option<u64>,
     max_main_distance: Option<u32>,
+    /// Walk towards the party dots of the minimap when the leader is out of sight
+    use_minimap: Option<bool>,
 }
 
 impl SupportConfig {
@@ -393,7 +625,7 @@ impl SupportConfig {
     pub fn get_usable_slot_index(
         &self,
         slot_type: SlotType,
-        threshold: Option<u32>,
+        threshold: Option<StatValue>,
         last_slots_usage: [[Option<Instant>; 10]; 9],
     ) -> Option<(usize, usize)> {
         for n in 0..9 {
@@ -434,6 +666,10 @@ impl SupportConfig {
     pub fn get_max_main_distance(&self) -> u32 {
         self.max_main_distance.unwrap_or(100)
     }
+
+    pub fn use_minimap(&self) -> bool {
+        self.use_minimap.unwrap_or(false)
+    }
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
@@ -476,6 +712,21 @@ pub struct BotConfig {
     farming_config: FarmingConfig,
     support_config: SupportConfig,
     shout_config: ShoutConfig,
+
+    /// Seed of all randomness, picked at random if not set. A string because the frontend
+    /// can't hold integers above 2^53 exactly
+    rng_seed: Option<String>,
+
+    /// Analyze and decide as usual, but only log input instead of sending it
+    dry_run: Option<bool>,
+
+    /// Stream an annotated preview of the analyzed frames to the frontend
+    show_preview: Option<bool>,
+
+    /// Shades of the stat bars, as found by the color calibration
+    hp_bar_colors: Option<[[u8; 3]; 4]>,
+    mp_bar_colors: Option<[[u8; 3]; 4]>,
+    fp_bar_colors: Option<[[u8; 3]; 4]>,
 }
 
 impl BotConfig {
@@ -512,6 +763,59 @@ impl BotConfig {
         self.mode.clone()
     }
 
+    pub fn rng_seed(&self) -> Option<&str> {
+        self.rng_seed.as_deref()
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run.unwrap_or(false)
+    }
+
+    pub fn show_preview(&self) -> bool {
+        self.show_preview.unwrap_or(false)
+    }
+
+    pub fn hp_bar_colors(&self) -> Option<[[u8; 3]; 4]> {
+        self.hp_bar_colors
+    }
+
+    pub fn mp_bar_colors(&self) -> Option<[[u8; 3]; 4]> {
+        self.mp_bar_colors
+    }
+
+    pub fn fp_bar_colors(&self) -> Option<[[u8; 3]; 4]> {
+        self.fp_bar_colors
+    }
+
+    /// Use other shades for a stat bar. The target bars share them with the HP and MP bars.
+    pub fn set_bar_colors(&mut self, kind: StatusBarKind, colors: [[u8; 3]; 4]) {
+        match kind {
+            StatusBarKind::Hp | StatusBarKind::TargetHP => self.hp_bar_colors = Some(colors),
+            StatusBarKind::Mp | StatusBarKind::TargetMP => self.mp_bar_colors = Some(colors),
+            StatusBarKind::Fp => self.fp_bar_colors = Some(colors),
+        }
+    }
+
+    /// Search a type of mobs with another name color and tolerance.
+    pub fn set_mob_colors(&mut self, mob_type: MobType, color: [u8; 3], tolerance: u8) {
+        let farming_config = &mut self.farming_config;
+        let color = Some(color.map(Some));
+        match mob_type {
+            MobType::Passive => {
+                farming_config.passive_mobs_colors = color;
+                farming_config.passive_tolerence = Some(tolerance);
+            }
+            MobType::Aggressive => {
+                farming_config.aggressive_mobs_colors = color;
+                farming_config.aggressive_tolerence = Some(tolerance);
+            }
+            MobType::Violet => {
+                farming_config.violet_mobs_colors = color;
+                farming_config.violet_tolerence = Some(tolerance);
+            }
+        }
+    }
+
     /// Serialize config to disk
     pub fn serialize(&self, path: String) {
         let config = {
@@ -533,3 +837,173 @@ impl BotConfig {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::SlotBar;
+    use crate::{
+        data::{MobType, Neighbourhood},
+        image_analyzer::MOB_NAME_NEIGHBOURHOOD,
+        ipc::{ColorMatchMode, FarmingConfig, MobPolicy, SlotType, StatValue},
+    };
+
+    #[test]
+    fn test_mob_name_lists() {
+        let config: FarmingConfig = serde_json::from_str(
+            r#"{ "mobs_name_allow_list": ["Aibatt", " "], "mobs_name_deny_list": ["mia"] }"#,
+        )
+        .unwrap();
+        assert!(config.is_mob_name_allowed(Some("aibatt")));
+        assert!(!config.is_mob_name_allowed(Some("Red Mantis")));
+
+        // Unknown names are never filtered out
+        assert!(config.is_mob_name_allowed(None));
+        assert!(config.is_mob_name_allowed(Some("Ai?att")));
+
+        let config: FarmingConfig =
+            serde_json::from_str(r#"{ "mobs_name_deny_list": ["Mia"] }"#).unwrap();
+        assert!(!config.is_mob_name_allowed(Some("Mia")));
+        assert!(config.is_mob_name_allowed(Some("Aibatt")));
+    }
+
+    #[test]
+    fn test_color_match_modes() {
+        let config: FarmingConfig = serde_json::from_str(
+            r#"{ "color_match_mode": "Hsv", "violet_color_match_mode": "Lab" }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.color_match_mode(MobType::Passive),
+            ColorMatchMode::Hsv
+        );
+        assert_eq!(
+            config.color_match_mode(MobType::Aggressive),
+            ColorMatchMode::Hsv
+        );
+        assert_eq!(
+            config.color_match_mode(MobType::Violet),
+            ColorMatchMode::Lab
+        );
+        assert_eq!(
+            FarmingConfig::default().color_match_mode(MobType::Violet),
+            ColorMatchMode::Rgb
+        );
+    }
+
+    #[test]
+    fn test_mobs_name_neighbourhood() {
+        assert_eq!(
+            FarmingConfig::default().mobs_name_neighbourhood(),
+            MOB_NAME_NEIGHBOURHOOD
+        );
+        let config: FarmingConfig = serde_json::from_str(r#"{ "mobs_name_gap_y": 6 }"#).unwrap();
+        assert_eq!(
+            config.mobs_name_neighbourhood(),
+            Neighbourhood::new(MOB_NAME_NEIGHBOURHOOD.x, 6)
+        );
+    }
+
+    #[test]
+    fn test_patrol_route() {
+        let config: FarmingConfig = serde_json::from_str(
+            r#"{ "patrol_route": ["W 1000", "", "@ 30 -20 | 500"], "flight_destination": " " }"#,
+        )
+        .unwrap();
+        assert_eq!(config.patrol_route().map(|route| route.len()), Ok(2));
+        assert_eq!(config.flight_destination(), Ok(None));
+
+        let config: FarmingConfig = serde_json::from_str(
+            r#"{ "patrol_route": ["W 1000", "not a waypoint"], "flight_destination": "@ 30" }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.patrol_route(),
+            Err("line 2: invalid step \"not a waypoint\"".to_string())
+        );
+        assert!(config.flight_destination().is_err());
+    }
+
+    #[test]
+    fn test_mob_policies() {
+        let config = FarmingConfig::default();
+        assert_eq!(config.mob_policy(MobType::Passive), MobPolicy::Attack);
+        assert_eq!(config.mob_policy(MobType::Aggressive), MobPolicy::Attack);
+        assert_eq!(config.mob_policy(MobType::Violet), MobPolicy::Ignore);
+
+        let config: FarmingConfig = serde_json::from_str(
+            r#"{ "aggressive_mobs_policy": "Avoid", "violet_mobs_policy": "Attack" }"#,
+        )
+        .unwrap();
+        assert_eq!(config.mob_policy(MobType::Passive), MobPolicy::Attack);
+        assert_eq!(config.mob_policy(MobType::Aggressive), MobPolicy::Avoid);
+        assert_eq!(config.mob_policy(MobType::Violet), MobPolicy::Attack);
+    }
+
+    #[test]
+    fn test_usable_slot_thresholds() {
+        let slot = |threshold: u32, absolute: bool| {
+            json!({
+                "slot_type": "Food",
+                "slot_threshold": threshold,
+                "slot_threshold_absolute": absolute,
+                "slot_enabled": true,
+            })
+        };
+        let unused = json!({ "slot_type": "Unused", "slot_enabled": true });
+        let mut slots = vec![unused; 10];
+        slots[0] = slot(50, false);
+        slots[1] = slot(300, true);
+        let bar: SlotBar = serde_json::from_value(json!({ "slots": slots })).unwrap();
+        let usable = |stat: StatValue| {
+            bar.get_usable_slot_index(SlotType::Food, Some(stat), [[None; 10]; 9], 0)
+        };
+
+        // 300 of 1000 HP is 30%, below the 50% threshold
+        let stat = StatValue {
+            percent: 20,
+            current: Some(200),
+            max: Some(1000),
+        };
+        assert_eq!(usable(stat), Some((0, 1)));
+
+        // 300 of 400 HP is 75%, above it
+        let stat = StatValue {
+            percent: 25,
+            current: Some(100),
+            max: Some(400),
+        };
+        assert_eq!(usable(stat), Some((0, 0)));
+
+        // Without the exact value, 20% of the last known 1000 HP is below 300 HP
+        let stat = StatValue {
+            percent: 20,
+            current: None,
+            max: Some(1000),
+        };
+        assert_eq!(usable(stat), Some((0, 1)));
+
+        // 40% of 1000 HP is above 300 HP, but still below the 50% threshold
+        let stat = StatValue {
+            percent: 40,
+            current: None,
+            max: Some(1000),
+        };
+        assert_eq!(usable(stat), Some((0, 0)));
+
+        // Without any max, absolute thresholds fall back to half the bar and rank last
+        let stat = StatValue {
+            percent: 20,
+            current: None,
+            max: None,
+        };
+        assert_eq!(usable(stat), Some((0, 0)));
+        let stat = StatValue {
+            percent: 60,
+            current: None,
+            max: None,
+        };
+        assert_eq!(usable(stat), None);
+    }
+}
@@ -0,0 +1,88 @@
+use guard::guard;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    data::{Bounds, MobType, StatusBarKind},
+    ipc::BotConfig,
+};
+
+/// What the regions marked for a color calibration show.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CalibrationTarget {
+    PassiveMob,
+    AggressiveMob,
+    VioletMob,
+    HpBar,
+    MpBar,
+    FpBar,
+}
+
+impl CalibrationTarget {
+    /// Mob names have a single reference color, bars four shades.
+    pub fn is_bar(&self) -> bool {
+        self.bar_kind().is_some()
+    }
+
+    pub fn mob_type(&self) -> Option<MobType> {
+        match self {
+            CalibrationTarget::PassiveMob => Some(MobType::Passive),
+            CalibrationTarget::AggressiveMob => Some(MobType::Aggressive),
+            CalibrationTarget::VioletMob => Some(MobType::Violet),
+            _ => None,
+        }
+    }
+
+    pub fn bar_kind(&self) -> Option<StatusBarKind> {
+        match self {
+            CalibrationTarget::HpBar => Some(StatusBarKind::Hp),
+            CalibrationTarget::MpBar => Some(StatusBarKind::Mp),
+            CalibrationTarget::FpBar => Some(StatusBarKind::Fp),
+            _ => None,
+        }
+    }
+}
+
+/// Regions of a preview frame the user marked as showing `target`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CalibrationRequest {
+    /// `PreviewFrame::frame_id` of the preview the regions were marked on
+    pub frame_id: u64,
+    pub target: CalibrationTarget,
+    pub regions: Vec<Bounds>,
+}
+
+/// A color of the marked regions and how many of their pixels have it.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HistogramBin {
+    pub color: [u8; 3],
+    pub count: u32,
+}
+
+/// Colors proposed by a calibration, and how well they'd detect the marked regions.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CalibrationResult {
+    pub target: CalibrationTarget,
+    /// One reference color for mob names, four shades for bars
+    pub colors: Vec<[u8; 3]>,
+    pub tolerance: u8,
+    /// Pixels of the marked regions matching the colors
+    pub matches: u32,
+    /// Pixels elsewhere in the frame matching the colors
+    pub false_positives: u32,
+    /// Most frequent colors of the marked regions
+    pub histogram: Vec<HistogramBin>,
+}
+
+impl CalibrationResult {
+    /// Use the proposed colors in `config`.
+    pub fn apply_to(&self, config: &mut BotConfig) {
+        guard!(let Some(&last) = self.colors.last() else { return; });
+        if let Some(mob_type) = self.target.mob_type() {
+            config.set_mob_colors(mob_type, self.colors[0], self.tolerance);
+        }
+        if let Some(kind) = self.target.bar_kind() {
+            let shades = [0, 1, 2, 3].map(|index| *self.colors.get(index).unwrap_or(&last));
+            config.set_bar_colors(kind, shades);
+        }
+    }
+}
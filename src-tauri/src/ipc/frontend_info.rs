@@ -1,5 +1,71 @@
 use serde::{Deserialize, Serialize};
 
+use crate::data::MobType;
+
+/// How often a class of special mobs was seen and how it was handled.
+#[derive(Debug, Default, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
+pub struct MobPolicyStats {
+    /// Mobs of the class on the last scan
+    pub seen: u32,
+    /// Fights started against one
+    pub attacked: u32,
+    /// Detections skipped while searching for a target
+    pub ignored: u32,
+    /// Times the character moved away from one
+    pub avoided: u32,
+}
+
+/// Throughput of one stage of the frame pipeline.
+#[derive(Debug, Default, Serialize, Deserialize, Copy, Clone, PartialEq)]
+pub struct StageStats {
+    /// Completed runs per second
+    pub fps: f32,
+    /// Time from receiving its input to finishing
+    pub latency_ms: f32,
+}
+
+/// Throughput of the frame pipeline by stage.
+#[derive(Debug, Default, Serialize, Deserialize, Copy, Clone, PartialEq)]
+pub struct PipelineStats {
+    pub capture: StageStats,
+    pub analysis: StageStats,
+    /// Latency is measured from the capture of the frame a decision was made on
+    pub behavior: StageStats,
+}
+
+/// Input a dry run kept from reaching the client.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct SkippedInput {
+    /// Unix time in milliseconds
+    pub timestamp: u64,
+    pub action: String,
+}
+
+/// A mob seen on a preview frame.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct PreviewMob {
+    pub mob_type: MobType,
+    pub name: Option<String>,
+    pub level: Option<u32>,
+}
+
+/// A downscaled, annotated capture of what the bot sees.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct PreviewFrame {
+    /// Id of the analyzed frame the preview shows
+    pub frame_id: u64,
+    /// JPEG data URL
+    pub image: String,
+    pub width: u32,
+    pub height: u32,
+    /// Size of the captured frame, to map positions on the preview back to it
+    pub frame_width: u32,
+    pub frame_height: u32,
+    /// Current state of the behavior
+    pub state: Option<String>,
+    pub mobs: Vec<PreviewMob>,
+}
+
 #[derive(Debug, Default, Serialize, Deserialize, Copy, Clone)]
 pub struct FrontendInfo {
     /* enemy_bounds: Option<Vec<Bounds>>,
@@ -13,6 +79,12 @@ pub struct FrontendInfo {
     is_running: bool,
     is_alive: bool,
     afk_ready_to_disconnect: bool,
+    passive_mobs: MobPolicyStats,
+    aggressive_mobs: MobPolicyStats,
+    violet_mobs: MobPolicyStats,
+    pipeline: PipelineStats,
+    /// Times the character got stuck while searching
+    stuck_count: u32,
 }
 
 impl FrontendInfo {
@@ -43,6 +115,22 @@ impl FrontendInfo {
         self.last_search_duration = action_duration.0.try_into().unwrap_or(0);
         self.last_fight_duration = action_duration.1.try_into().unwrap_or(0);
     }
+    pub fn set_mob_policy_stats(&mut self, mob_type: MobType, stats: MobPolicyStats) {
+        match mob_type {
+            MobType::Passive => self.passive_mobs = stats,
+            MobType::Aggressive => self.aggressive_mobs = stats,
+            MobType::Violet => self.violet_mobs = stats,
+        }
+    }
+
+    pub fn set_stuck_count(&mut self, stuck_count: u32) {
+        self.stuck_count = stuck_count;
+    }
+
+    pub fn set_pipeline_stats(&mut self, pipeline: PipelineStats) {
+        self.pipeline = pipeline;
+    }
+
     pub fn set_is_attacking(&mut self, is_attacking: bool) {
         self.is_attacking = is_attacking;
     }
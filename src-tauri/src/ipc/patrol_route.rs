@@ -0,0 +1,121 @@
+use std::str::FromStr;
+
+/// Keys a patrol step may hold
+const MOVEMENT_KEYS: [&str; 6] = ["W", "S", "A", "D", "Left", "Right"];
+
+/// One step of a movement sequence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatrolStep {
+    /// Hold a movement key for some milliseconds
+    Hold(String, u64),
+    Jump,
+    Wait(u64),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WaypointAction {
+    /// Keys to play once
+    Movements(Vec<PatrolStep>),
+    /// Meters east (`x`) and south (`y`) of where farming started, as estimated by the odometer
+    /// from the keys pressed and the mobs clicked
+    OdometerTarget { x: f32, y: f32 },
+}
+
+/// A stop of a patrol route and how long to wait there for mobs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Waypoint {
+    pub action: WaypointAction,
+    /// Milliseconds spent searching for mobs once the waypoint is reached
+    pub dwell: u64,
+}
+
+/// Parses a line like `W 2000, Left 400, Jump | 5000` (a movement sequence) or
+/// `@ 30 -20 | 5000` (a position relative to where farming started), the dwell time after `|`
+/// being optional.
+impl FromStr for Waypoint {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let (action, dwell) = match line.split_once('|') {
+            Some((action, dwell)) => (action, parse_number(dwell)?),
+            None => (line, 0),
+        };
+
+        let action = action.trim();
+        let action = match action.strip_prefix('@') {
+            Some(target) => {
+                let coords = target
+                    .split_whitespace()
+                    .map(|coord| {
+                        coord
+                            .parse::<f32>()
+                            .map_err(|_| format!("invalid coordinate {:?}", coord))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                match coords[..] {
+                    [x, y] => WaypointAction::OdometerTarget { x, y },
+                    _ => return Err(format!("expected `@ x y`, got {:?}", action)),
+                }
+            }
+            None => WaypointAction::Movements(
+                action
+                    .split(',')
+                    .map(parse_step)
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+        };
+        Ok(Self { action, dwell })
+    }
+}
+
+fn parse_number(text: &str) -> Result<u64, String> {
+    text.trim()
+        .parse()
+        .map_err(|_| format!("invalid duration {:?}", text.trim()))
+}
+
+fn parse_step(step: &str) -> Result<PatrolStep, String> {
+    let words = step.split_whitespace().collect::<Vec<_>>();
+    match words[..] {
+        ["Jump"] => Ok(PatrolStep::Jump),
+        ["Wait", duration] => Ok(PatrolStep::Wait(parse_number(duration)?)),
+        [key, duration] if MOVEMENT_KEYS.contains(&key) => {
+            Ok(PatrolStep::Hold(key.to_string(), parse_number(duration)?))
+        }
+        _ => Err(format!("invalid step {:?}", step.trim())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ipc::{PatrolStep, Waypoint, WaypointAction};
+
+    #[test]
+    fn test_parse_waypoint() {
+        let waypoint = "W 2000, Left 400,Jump , Wait 100 | 5000"
+            .parse::<Waypoint>()
+            .unwrap();
+        assert_eq!(
+            waypoint.action,
+            WaypointAction::Movements(vec![
+                PatrolStep::Hold("W".to_string(), 2000),
+                PatrolStep::Hold("Left".to_string(), 400),
+                PatrolStep::Jump,
+                PatrolStep::Wait(100),
+            ])
+        );
+        assert_eq!(waypoint.dwell, 5000);
+
+        let waypoint = "@ 30 -20.5".parse::<Waypoint>().unwrap();
+        assert_eq!(
+            waypoint.action,
+            WaypointAction::OdometerTarget { x: 30.0, y: -20.5 }
+        );
+        assert_eq!(waypoint.dwell, 0);
+
+        assert!("Q 100".parse::<Waypoint>().is_err());
+        assert!("W".parse::<Waypoint>().is_err());
+        assert!("@ 30".parse::<Waypoint>().is_err());
+        assert!("W 100 | soon".parse::<Waypoint>().is_err());
+    }
+}
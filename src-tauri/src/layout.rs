@@ -0,0 +1,220 @@
+use std::{fs, path::Path, sync::OnceLock};
+
+use serde::{Deserialize, Serialize};
+
+use crate::data::Bounds;
+
+/// Point of the capture a HUD element stays attached to when the capture is resized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    /// Position of the anchor as a fraction of the capture size.
+    fn fraction(&self) -> (f64, f64) {
+        use Anchor::*;
+
+        match self {
+            TopLeft => (0.0, 0.0),
+            Top => (0.5, 0.0),
+            TopRight => (1.0, 0.0),
+            Left => (0.0, 0.5),
+            Center => (0.5, 0.5),
+            Right => (1.0, 0.5),
+            BottomLeft => (0.0, 1.0),
+            Bottom => (0.5, 1.0),
+            BottomRight => (1.0, 1.0),
+        }
+    }
+}
+
+/// How HUD elements grow with the capture.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HudScaling {
+    /// Elements keep their size and only follow their anchor, like the client HUD does
+    #[default]
+    Fixed,
+    /// Elements grow with the capture, keeping its aspect ratio
+    Proportional,
+}
+
+/// A HUD element, measured in a capture of the layout's reference size.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LayoutRect {
+    pub anchor: Anchor,
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+/// HUD element regions resolved for one capture size.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HudRegions {
+    pub stat_tray: Bounds,
+    pub target_hp_bar: Bounds,
+    pub target_mp_bar: Bounds,
+    /// Areas where mob names are never searched, e.g. because the stat bars share their colors
+    pub mob_ignore_areas: Vec<Bounds>,
+    /// `None` if the layout doesn't know where the minimap is
+    pub minimap: Option<Bounds>,
+    /// `None` if the layout doesn't know where the flight gauge is
+    pub flight_gauge: Option<Bounds>,
+}
+
+/// A HUD layout profile, see `assets/layouts/default.json`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HudLayout {
+    pub name: String,
+    pub reference_width: u32,
+    pub reference_height: u32,
+    #[serde(default)]
+    pub scaling: HudScaling,
+    pub stat_tray: LayoutRect,
+    pub target_hp_bar: LayoutRect,
+    pub target_mp_bar: LayoutRect,
+    #[serde(default)]
+    pub mob_ignore_areas: Vec<LayoutRect>,
+    /// Where the minimap is drawn when it's open
+    #[serde(default)]
+    pub minimap: Option<LayoutRect>,
+    /// Where the gauge shown while flying is drawn
+    #[serde(default)]
+    pub flight_gauge: Option<LayoutRect>,
+}
+
+impl Default for HudLayout {
+    fn default() -> Self {
+        Self::builtin().clone()
+    }
+}
+
+impl HudLayout {
+    /// The layout of the client at its default settings.
+    pub fn builtin() -> &'static HudLayout {
+        static LAYOUT: OnceLock<HudLayout> = OnceLock::new();
+        LAYOUT.get_or_init(|| {
+            Self::parse(include_str!("../assets/layouts/default.json"))
+                .expect("Bundled HUD layout is invalid")
+        })
+    }
+
+    /// Parse a layout from its JSON representation.
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let layout = serde_json::from_str::<Self>(source).map_err(|err| err.to_string())?;
+        if layout.reference_width == 0 || layout.reference_height == 0 {
+            return Err("reference size must not be zero".to_string());
+        }
+        Ok(layout)
+    }
+
+    /// Load a layout profile from disk.
+    pub fn from_file<P>(path: P) -> Result<Self, String>
+    where
+        P: AsRef<Path>,
+    {
+        Self::parse(&fs::read_to_string(path).map_err(|err| err.to_string())?)
+    }
+
+    /// Resolve all elements for a capture of `width` x `height` physical pixels.
+    /// `scale_factor` is the DPI scale of the client window.
+    pub fn resolve(&self, width: u32, height: u32, scale_factor: f64) -> HudRegions {
+        let scale_factor = if scale_factor > 0.0 { scale_factor } else { 1.0 };
+        let scale = scale_factor
+            * match self.scaling {
+                HudScaling::Fixed => 1.0,
+                HudScaling::Proportional => {
+                    let logical_w = width as f64 / scale_factor;
+                    let logical_h = height as f64 / scale_factor;
+                    (logical_w / self.reference_width as f64)
+                        .min(logical_h / self.reference_height as f64)
+                }
+            };
+        let resolve = |rect: &LayoutRect| self.resolve_rect(rect, width, height, scale);
+
+        HudRegions {
+            stat_tray: resolve(&self.stat_tray),
+            target_hp_bar: resolve(&self.target_hp_bar),
+            target_mp_bar: resolve(&self.target_mp_bar),
+            mob_ignore_areas: self.mob_ignore_areas.iter().map(resolve).collect(),
+            minimap: self.minimap.as_ref().map(resolve),
+            flight_gauge: self.flight_gauge.as_ref().map(resolve),
+        }
+    }
+
+    /// Move `rect` along with its anchor and scale its distance to the anchor and its size.
+    fn resolve_rect(&self, rect: &LayoutRect, width: u32, height: u32, scale: f64) -> Bounds {
+        let (anchor_x, anchor_y) = rect.anchor.fraction();
+        let resolve_axis = |pos: u32, size: u32, anchor: f64, reference: u32, capture: u32| {
+            let offset = pos as f64 - anchor * reference as f64;
+            let start = (anchor * capture as f64 + offset * scale).round();
+            let end = (start + size as f64 * scale).round();
+            let start = start.clamp(0.0, capture as f64) as u32;
+            let end = end.clamp(0.0, capture as f64) as u32;
+            (start, end - start)
+        };
+
+        let (x, w) = resolve_axis(rect.x, rect.w, anchor_x, self.reference_width, width);
+        let (y, h) = resolve_axis(rect.y, rect.h, anchor_y, self.reference_height, height);
+        Bounds::new(x, y, w, h)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        data::Bounds,
+        layout::{HudLayout, HudScaling},
+    };
+
+    #[test]
+    fn test_resolve_layout() {
+        let layout = HudLayout::builtin();
+
+        // The reference size resolves to the measured pixels
+        let hud = layout.resolve(800, 600, 1.0);
+        assert_eq!(hud.stat_tray, Bounds::new(105, 30, 120, 80));
+        assert_eq!(hud.target_hp_bar, Bounds::new(300, 30, 250, 30));
+        assert_eq!(hud.mob_ignore_areas, vec![Bounds::new(0, 0, 250, 110)]);
+        assert_eq!(hud.minimap, None);
+
+        // Fixed elements follow their anchor
+        let hud = layout.resolve(1280, 720, 1.0);
+        assert_eq!(hud.stat_tray, Bounds::new(105, 30, 120, 80));
+        assert_eq!(hud.target_hp_bar, Bounds::new(540, 30, 250, 30));
+        assert_eq!(hud.flight_gauge, None);
+
+        // and grow with the DPI scale
+        let hud = layout.resolve(1600, 1200, 2.0);
+        assert_eq!(hud.stat_tray, Bounds::new(210, 60, 240, 160));
+        assert_eq!(hud.target_hp_bar, Bounds::new(600, 60, 500, 60));
+
+        let mut layout = layout.clone();
+        layout.scaling = HudScaling::Proportional;
+        let hud = layout.resolve(1600, 1000, 1.0);
+        assert_eq!(hud.stat_tray, Bounds::new(175, 50, 200, 133));
+        assert_eq!(hud.target_hp_bar, Bounds::new(633, 50, 417, 50));
+
+        // Elements are clipped to the capture
+        let hud = HudLayout::builtin().resolve(200, 40, 1.0);
+        assert_eq!(hud.stat_tray, Bounds::new(105, 30, 95, 10));
+    }
+
+    #[test]
+    fn test_parse_layout() {
+        assert!(HudLayout::parse("{}").is_err());
+
+        let mut layout = HudLayout::builtin().clone();
+        layout.reference_width = 0;
+        assert!(HudLayout::parse(&serde_json::to_string(&layout).unwrap()).is_err());
+    }
+}
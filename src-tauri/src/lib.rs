@@ -0,0 +1,20 @@
+//! Capture analysis and behaviors of the bot, shared by the Tauri app and `neuz-analyze`.
+//! Window capture and input go through Tauri and are only built with the `app` feature, so
+//! the detection builds without it.
+
+pub mod behavior;
+pub mod data;
+pub mod debug_frames;
+pub mod flight_recorder;
+pub mod image_analyzer;
+pub mod ipc;
+pub mod layout;
+pub mod movement;
+pub mod ocr;
+#[cfg(feature = "app")]
+pub mod pipeline;
+pub mod platform;
+#[cfg(feature = "app")]
+pub mod preview;
+pub mod replay;
+pub mod utils;
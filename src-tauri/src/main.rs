@@ -1,29 +1,40 @@
 #![cfg_attr(all(not(debug_assertions), target_os = "windows"), windows_subsystem = "windows")]
 
-mod behavior;
-mod data;
-mod image_analyzer;
-mod ipc;
-mod movement;
-mod platform;
-mod utils;
-
-use std::{ fs, io, os::windows::process, path::{ Path, PathBuf }, sync::Arc, time::Duration };
+use std::{
+    fs,
+    io,
+    os::windows::process,
+    path::{ Path, PathBuf },
+    sync::{ atomic::Ordering, Arc },
+    time::Duration,
+};
 
 use guard::guard;
-use ipc::FrontendInfo;
-use parking_lot::RwLock;
+use parking_lot::{ Mutex, RwLock };
 use slog::{ Drain, Level, Logger };
-use tauri::{ LogicalSize, Manager, Size, Window };
+use tauri::{ LogicalSize, Manager, Window };
 
-use crate::{
+use app::{
     behavior::{ Behavior, FarmingBehavior, ShoutBehavior, SupportBehavior },
-    image_analyzer::ImageAnalyzer,
+    image_analyzer::{ calibrate, BarColors, ImageAnalyzer, MobColors },
+    layout::HudLayout,
     data::AliveState,
-    ipc::{ BotConfig, BotMode },
+    debug_frames::DebugFrameExporter,
+    flight_recorder::{ FlightRecorder, RecordedInputSink },
+    ipc::{
+        BotConfig,
+        BotMode,
+        CalibrationRequest,
+        CalibrationResult,
+        FrontendInfo,
+        PreviewFrame,
+        SkippedInput,
+    },
     movement::MovementAccessor,
-    platform::{ eval_send_key, KeyMode },
-    utils::Timer,
+    pipeline::FramePipeline,
+    preview::PreviewStreamer,
+    platform::{ self, DryRunInputSink, EvalInputSink, InputSink, KeyMode },
+    utils::{ RngSource, SystemClock, Timer, SEED_ENV_VAR },
 };
 
 struct AppState {
@@ -60,6 +71,9 @@ fn main() {
     let drain = sentry_slog::SentryDrain::new(drain).fuse();
     let logger = Logger::root(drain.fuse(), slog::o!());
 
+    // Dump the flight recorder of the running bot on panic
+    FlightRecorder::install_panic_hook();
+
     // Build app
     tauri::Builder
         ::default()
@@ -336,6 +350,24 @@ fn start_bot(profile_id: String, state: tauri::State<AppState>, app_handle: taur
             local_config.write().toggle_active();
         });
 
+        // Listen for calibrated colors accepted in the UI
+        let local_config = config.clone();
+        let logger_calibration_c2s = logger.clone();
+        app_handle.listen_global("calibration_apply_c2s", move |e| {
+            if let Some(payload) = e.payload() {
+                match serde_json::from_str::<CalibrationResult>(payload) {
+                    Ok(result) => {
+                        let mut new_config = local_config.read().clone();
+                        result.apply_to(&mut new_config);
+                        *local_config.write() = new_config.changed();
+                    }
+                    Err(e) => {
+                        slog::error!(logger_calibration_c2s, "Failed to parse calibration"; "error" => e.to_string(), "error_payload" => payload);
+                    }
+                }
+            }
+        });
+
         let send_config = |config: &BotConfig| {
             drop(app_handle.emit_all("bot_config_s2c", config) as Result<(), _>)
         };
@@ -344,6 +376,18 @@ fn start_bot(profile_id: String, state: tauri::State<AppState>, app_handle: taur
             drop(app_handle.emit_all("bot_info_s2c", config) as Result<(), _>)
         };
 
+        let send_skipped_input = |skipped: &Vec<SkippedInput>| {
+            drop(app_handle.emit_all("bot_skipped_input_s2c", skipped) as Result<(), _>)
+        };
+
+        let send_preview = |preview: &PreviewFrame| {
+            drop(app_handle.emit_all("bot_preview_s2c", preview) as Result<(), _>)
+        };
+
+        let send_calibration = |result: &CalibrationResult| {
+            drop(app_handle.emit_all("calibration_s2c", result) as Result<(), _>)
+        };
+
         // Wait a second for frontend to become ready
         std::thread::sleep(Duration::from_secs(1));
 
@@ -359,16 +403,127 @@ fn start_bot(profile_id: String, state: tauri::State<AppState>, app_handle: taur
         let eval_js = eval_js.replace("$env.DEBUG", "false");
         drop(window.eval(&eval_js));
 
-        let mut image_analyzer: ImageAnalyzer = ImageAnalyzer::new(&window);
+        // All behaviors and stats measure time with this clock
+        let clock = SystemClock::shared();
+
+        // Seed all randomness of this session, so it can be replayed
+        let rng = RngSource::from_env_or(config.read().rng_seed()).unwrap_or_else(|err| {
+            slog::error!(logger, "Failed to read random seed"; "error" => err);
+            RngSource::random()
+        });
+        slog::info!(logger, "Random seed"; "seed" => rng.seed(), "env_var" => SEED_ENV_VAR);
+
+        // Keep the last frames and decisions to find out what went wrong on death or panic
+        let recorder = Arc::new(
+            FlightRecorder::new(
+                PathBuf::from(
+                    format!(r"{}\flight_recorder", config_folder_path(&app_handle, &profile_id))
+                ),
+                logger.clone()
+            )
+        );
+        recorder.dump_on_panic();
+
+        // Send and record input to the client window, unless the config asks for a dry run
+        let window_input = Arc::new(
+            RecordedInputSink::new(Arc::new(EvalInputSink::new(window.clone())), recorder.clone())
+        );
+        let dry_run_input = Arc::new(DryRunInputSink::new(window_input, logger.clone()));
+        dry_run_input.set_dry_run(config.read().is_dry_run());
+        let input: Arc<dyn InputSink> = dry_run_input.clone();
+
+        let mut image_analyzer: ImageAnalyzer = ImageAnalyzer::new(
+            &window,
+            input.clone(),
+            clock.clone()
+        );
         image_analyzer.window_id = platform::get_window_id(&window).unwrap_or(0);
 
+        // Use the profile's HUD layout if there is one
+        let layout_path = format!(
+            r"{}\.hudlayout_{}",
+            app_handle.path_resolver().app_data_dir().unwrap().to_string_lossy(),
+            profile_id
+        );
+        if Path::new(&layout_path).exists() {
+            match HudLayout::from_file(&layout_path) {
+                Ok(layout) => {
+                    slog::info!(logger, "Loaded HUD layout"; "name" => &layout.name);
+                    image_analyzer.set_layout(layout);
+                }
+                Err(err) => {
+                    slog::error!(logger, "Failed to load HUD layout"; "path" => &layout_path, "error" => err);
+                }
+            }
+        }
+
+        // Capture and analyze frames on their own thread
+        let mut pipeline = FramePipeline::spawn(image_analyzer, logger.clone());
+        // Behaviors work on the frames published by the pipeline
+        let mut image_analyzer = ImageAnalyzer::headless();
+
+        // Save annotated frames to tune the detection
+        let mut debug_frames = DebugFrameExporter::new(
+            PathBuf::from(
+                format!(
+                    r"{}\debug_frames_{}",
+                    app_handle.path_resolver().app_data_dir().unwrap().to_string_lossy(),
+                    profile_id
+                )
+            ),
+            logger.clone()
+        );
+        let debug_frame_request = debug_frames.request_flag();
+        app_handle.listen_global("debug_frame_c2s", move |_| {
+            debug_frame_request.store(true, Ordering::Relaxed);
+        });
+
+        // Derive colors from regions the user marked on the preview
+        let calibration_request: Arc<Mutex<Option<CalibrationRequest>>> = Arc::new(
+            Mutex::new(None)
+        );
+        let local_calibration_request = calibration_request.clone();
+        let logger_calibrate_c2s = logger.clone();
+        app_handle.listen_global("calibrate_c2s", move |e| {
+            if let Some(payload) = e.payload() {
+                match serde_json::from_str::<CalibrationRequest>(payload) {
+                    Ok(request) => {
+                        *local_calibration_request.lock() = Some(request);
+                    }
+                    Err(e) => {
+                        slog::error!(logger_calibrate_c2s, "Failed to parse calibration request"; "error" => e.to_string(), "error_payload" => payload);
+                    }
+                }
+            }
+        });
+
+        // Keep the preview frame the user starts marking regions on
+        let preview_hold_request: Arc<Mutex<Option<u64>>> = Arc::new(Mutex::new(None));
+        let local_preview_hold_request = preview_hold_request.clone();
+        let logger_preview_hold_c2s = logger.clone();
+        app_handle.listen_global("preview_hold_c2s", move |e| {
+            if let Some(payload) = e.payload() {
+                match serde_json::from_str::<u64>(payload) {
+                    Ok(frame_id) => {
+                        *local_preview_hold_request.lock() = Some(frame_id);
+                    }
+                    Err(e) => {
+                        slog::error!(logger_preview_hold_c2s, "Failed to parse preview frame id"; "error" => e.to_string(), "error_payload" => payload);
+                    }
+                }
+            }
+        });
+
+        // Show what the bot sees in the frontend
+        let mut preview = PreviewStreamer::new();
+
         // Create movement accessor
-        let movement = MovementAccessor::new(window.clone() /*&accessor*/);
+        let movement = MovementAccessor::new(input.clone(), clock.clone(), &rng /*&accessor*/);
 
         // Instantiate behaviors
-        let mut farming_behavior = FarmingBehavior::new(&logger, &movement, &window);
-        let mut shout_behavior = ShoutBehavior::new(&logger, &movement, &window);
-        let mut support_behavior = SupportBehavior::new(&logger, &movement, &window);
+        let mut farming_behavior = FarmingBehavior::new(&logger, &movement, &*input, &*clock, &rng);
+        let mut shout_behavior = ShoutBehavior::new(&logger, &movement, &*input, &*clock, &rng);
+        let mut support_behavior = SupportBehavior::new(&logger, &movement, &*input, &*clock, &rng);
 
         let mut last_mode: Option<BotMode> = None;
         let mut last_is_running: Option<bool> = None;
@@ -381,6 +536,27 @@ fn start_bot(profile_id: String, state: tauri::State<AppState>, app_handle: taur
             let timer = Timer::start_new("main_loop");
             let config = &*config.read();
             let mut frontend_info_mut = *frontend_info.read();
+            pipeline.set_running(config.is_running());
+
+            // Calibrate on the preview frame the regions were marked on, even while stopped
+            let hold_request = preview_hold_request.lock().take();
+            if let Some(frame_id) = hold_request {
+                if !preview.hold(frame_id) {
+                    slog::warn!(logger, "Preview frame is gone"; "frame_id" => frame_id);
+                }
+            }
+            let request = calibration_request.lock().take();
+            if let Some(request) = request {
+                let result = preview
+                    .frame(request.frame_id)
+                    .and_then(|frame| calibrate(frame, &request));
+                match result {
+                    Some(result) => send_calibration(&result),
+                    None => {
+                        slog::warn!(logger, "Nothing to calibrate"; "target" => format!("{:?}", request.target), "frame_id" => request.frame_id)
+                    }
+                }
+            }
 
             // Send changed config to frontend if needed
             if last_config_change_id == 0 || config.change_id() > last_config_change_id {
@@ -394,7 +570,16 @@ fn start_bot(profile_id: String, state: tauri::State<AppState>, app_handle: taur
                 send_config(config);
                 last_config_change_id = config.change_id();
 
-                // Update behaviors
+                dry_run_input.set_dry_run(config.is_dry_run());
+
+                debug_frames.set_interval(config.farming_config().debug_frames_interval());
+                preview.set_enabled(config.show_preview());
+
+                // Scan for the configured mob colors
+                pipeline.set_mob_colors(MobColors::from_config(config.farming_config()));
+                pipeline.set_bar_colors(BarColors::from_config(config));
+
+                // Update behaviors, the farming behavior parses and reports its waypoints
                 farming_behavior.update(config);
                 shout_behavior.update(config);
                 support_behavior.update(config);
@@ -427,6 +612,11 @@ fn start_bot(profile_id: String, state: tauri::State<AppState>, app_handle: taur
                     continue;
                 }
 
+                // Leash the character to where the bot is engaged
+                if last_is_running != Some(true) {
+                    movement.schedule(|coordinator| coordinator.odometer_mut().reset());
+                }
+
                 // Check if mode is different from last mode
                 if let Some(last_mode_value) = last_mode.as_mut() {
                     if &mode != last_mode_value {
@@ -448,18 +638,6 @@ fn start_bot(profile_id: String, state: tauri::State<AppState>, app_handle: taur
                         last_mode = None;
                     }
                 }
-
-                if !config.farming_config().is_stop_fighting() {
-                    drop(
-                        window.set_size(
-                            Size::Logical(LogicalSize {
-                                width: 800.0,
-                                height: 600.0,
-                            })
-                        )
-                    );
-                    drop(window.set_resizable(false));
-                }
             }
 
             // Client window is closed
@@ -480,21 +658,27 @@ fn start_bot(profile_id: String, state: tauri::State<AppState>, app_handle: taur
 
             frontend_info_mut.set_is_running(true);
 
-            // Capture client window
-            image_analyzer.capture_window(&logger);
-
-            // Try capturing the window contents
-            if image_analyzer.image_is_some() {
-                // Update stats
-                image_analyzer.client_stats.update(&image_analyzer.clone(), &logger);
+            // Wait for the next analyzed frame
+            if let Some(analyzed) = pipeline.next_frame(Duration::from_millis(100)) {
+                let frame_id = analyzed.id;
+                let captured_at = analyzed.captured_at;
+                image_analyzer.set_analyzed_frame(analyzed);
+                if let Some(frame) = image_analyzer.frame() {
+                    recorder.record_frame(frame_id, frame);
+                }
+                recorder.record_stats(frame_id, &image_analyzer.client_stats);
 
                 // Run the current behavior
                 guard!(let Some(mode) = config.mode() else { continue; });
 
                 //Regardless if it's alive or not, if the bot is inactive should be dcd
                 if frontend_info_mut.is_afk_ready_to_disconnect() {
-                    app_handle.exit(0);
-                    return;
+                    recorder.dump_and_log("afk");
+                    input.exit();
+                    if !dry_run_input.is_dry_run() {
+                        return;
+                    }
+                    frontend_info_mut.set_afk_ready_to_disconnect(false);
                 }
                 let is_alive = image_analyzer.client_stats.is_alive;
                 let return_earlier = match is_alive {
@@ -505,7 +689,7 @@ fn start_bot(profile_id: String, state: tauri::State<AppState>, app_handle: taur
                             let should_disconnect = should_disconnect_on_death(config);
                             if !should_disconnect {
                                 // close chat after beign rez
-                                eval_send_key(&window, "Escape", KeyMode::Press);
+                                input.send_key("Escape", KeyMode::Press);
                                 std::thread::sleep(Duration::from_millis(1000));
                             }
                         }
@@ -513,9 +697,10 @@ fn start_bot(profile_id: String, state: tauri::State<AppState>, app_handle: taur
                     }
                     AliveState::Dead => {
                         if frontend_info_mut.is_alive() {
+                            recorder.dump_and_log("death");
                             let should_disconnect = should_disconnect_on_death(config);
                             if should_disconnect {
-                                app_handle.exit(0);
+                                input.exit();
                             }
 
                             frontend_info_mut.set_is_alive(false);
@@ -523,7 +708,7 @@ fn start_bot(profile_id: String, state: tauri::State<AppState>, app_handle: taur
                             // Send infos to frontend
                             send_info(&frontend_info.read());
                         } else {
-                            eval_send_key(&window, "Enter", KeyMode::Press);
+                            input.send_key("Enter", KeyMode::Press);
                             std::thread::sleep(Duration::from_millis(500));
                         }
                         true
@@ -559,11 +744,47 @@ fn start_bot(profile_id: String, state: tauri::State<AppState>, app_handle: taur
                         );
                     }
                 }
+                pipeline.record_behavior(captured_at);
+
+                let annotations = match mode {
+                    BotMode::Farming => farming_behavior.annotations(),
+                    BotMode::AutoShout => shout_behavior.annotations(),
+                    BotMode::Support => support_behavior.annotations(),
+                };
+                recorder.record_state(annotations.state.as_deref());
+                if debug_frames.should_export() {
+                    debug_frames.export(
+                        frame_id,
+                        &image_analyzer,
+                        config.farming_config(),
+                        &annotations
+                    );
+                }
+                if preview.should_send() {
+                    if
+                        let Some(preview_frame) = preview.render(
+                            frame_id,
+                            &image_analyzer,
+                            config.farming_config(),
+                            &annotations
+                        )
+                    {
+                        send_preview(&preview_frame);
+                    }
+                }
+
+                frontend_info_mut.set_pipeline_stats(pipeline.stats());
                 frontend_info = Arc::new(RwLock::new(frontend_info_mut));
                 // Send infos to frontend
                 send_info(&frontend_info.read());
             }
 
+            // Show what a dry run kept from the client
+            let skipped_input = dry_run_input.take_skipped();
+            if !skipped_input.is_empty() {
+                send_skipped_input(&skipped_input);
+            }
+
             // Update last mode
             last_mode = config.mode();
             last_is_running = Some(config.is_running());
@@ -1,11 +1,13 @@
 mod movement_accessor;
 mod movement_coordinator;
+mod odometer;
 
 pub use self::{
     movement_accessor::MovementAccessor,
     movement_coordinator::{
         ActionDuration, Movement, MovementCoordinator, MovementDirection, RotationDirection,
     },
+    odometer::Odometer,
 };
 
 pub mod prelude {
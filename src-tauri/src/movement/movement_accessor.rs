@@ -1,17 +1,30 @@
+use std::sync::Arc;
+
 use parking_lot::Mutex;
-use tauri::Window;
 
 //use crate::platform::PlatformAccessor;
-use super::MovementCoordinator;
+use super::{MovementCoordinator, Odometer};
+use crate::{
+    platform::InputSink,
+    utils::{Clock, RngSource, RngStream},
+};
 
 pub struct MovementAccessor {
     coordinator: Mutex<MovementCoordinator>,
 }
 
 impl MovementAccessor {
-    pub fn new(window: Window /*platform: &'a PlatformAccessor<'a>*/) -> Self {
+    pub fn new(
+        input: Arc<dyn InputSink>,
+        clock: Arc<dyn Clock>,
+        rng: &RngSource, /*platform: &'a PlatformAccessor<'a>*/
+    ) -> Self {
         Self {
-            coordinator: Mutex::new(MovementCoordinator::new(window /*platform*/)),
+            coordinator: Mutex::new(MovementCoordinator::new(
+                input,
+                clock,
+                rng.stream(RngStream::Movement), /*platform*/
+            )),
         }
     }
 
@@ -22,4 +35,9 @@ impl MovementAccessor {
         let mut coordinator = self.coordinator.lock();
         func(&mut coordinator);
     }
+
+    /// Where the played movements took the character.
+    pub fn odometer(&self) -> Odometer {
+        self.coordinator.lock().odometer()
+    }
 }
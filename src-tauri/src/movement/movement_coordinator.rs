@@ -1,9 +1,12 @@
-use std::{ops::Range, thread, time::Duration};
+use std::{ops::Range, sync::Arc, time::Duration};
 
-use rand::Rng;
-use tauri::Window;
+use rand::{rngs::StdRng, Rng};
 
-use crate::platform::{eval_send_key, eval_send_message /* , PlatformAccessor*/, KeyMode};
+use super::Odometer;
+use crate::{
+    platform::{InputSink /* , PlatformAccessor*/, KeyMode},
+    utils::Clock,
+};
 
 #[allow(dead_code)]
 #[derive(Debug, Clone, Copy)]
@@ -29,7 +32,7 @@ pub enum ActionDuration {
 }
 
 impl ActionDuration {
-    fn to_duration(&self, rng: &mut rand::rngs::ThreadRng) -> Duration {
+    fn to_duration(&self, rng: &mut StdRng) -> Duration {
         match self {
             Self::Fixed(ms) => Duration::from_millis(*ms),
             Self::Random(range) => Duration::from_millis(rng.gen_range(range.clone())),
@@ -54,21 +57,58 @@ pub enum Movement<'a> {
     Wait(ActionDuration),
 }
 
+/// Headings within this many degrees of a destination are straight enough to walk
+const STEERING_DEAD_ZONE: f32 = 20.0;
+
+impl Movement<'_> {
+    /// Turn by about `turn` degrees (negative to the left) and walk a few steps forward, see
+    /// `Odometer::ms_per_degree`. Meant to be played repeatedly while the heading gets
+    /// corrected between the steps.
+    pub fn steer_towards(turn: f32, ms_per_degree: f32) -> Vec<Movement<'static>> {
+        let walk = Movement::Move(MovementDirection::Forward, ActionDuration::Fixed(500));
+        if turn.abs() <= STEERING_DEAD_ZONE {
+            return vec![walk];
+        }
+        let direction = match turn < 0.0 {
+            true => RotationDirection::Left,
+            false => RotationDirection::Right,
+        };
+        let duration = (turn.abs() * ms_per_degree) as u64;
+        vec![
+            Movement::Rotate(direction, ActionDuration::Fixed(duration)),
+            walk,
+        ]
+    }
+}
+
 pub struct MovementCoordinator {
-    rng: rand::rngs::ThreadRng,
-    window: Window,
+    rng: StdRng,
+    input: Arc<dyn InputSink>,
+    clock: Arc<dyn Clock>,
+    /// Keys held down right now
+    held_keys: Vec<String>,
+    odometer: Odometer,
 }
 
 impl<'a> MovementCoordinator {
-    pub fn new(window: Window) -> Self {
-        let rng = rand::thread_rng();
-
+    pub fn new(input: Arc<dyn InputSink>, clock: Arc<dyn Clock>, rng: StdRng) -> Self {
         Self {
             rng, /*, platform */
-            window,
+            input,
+            clock,
+            held_keys: vec![],
+            odometer: Odometer::default(),
         }
     }
 
+    pub fn odometer(&self) -> Odometer {
+        self.odometer
+    }
+
+    pub fn odometer_mut(&mut self) -> &mut Odometer {
+        &mut self.odometer
+    }
+
     // Wrapper functions
 
     /*   pub fn with_probability<F>(&mut self, probability: f64, func: F)
@@ -94,9 +134,9 @@ impl<'a> MovementCoordinator {
     fn play_single(&mut self, movement: Movement) {
         match movement {
             Movement::Jump => {
-                eval_send_key(&self.window, "Space", KeyMode::Hold);
-                std::thread::sleep(std::time::Duration::from_millis(500));
-                eval_send_key(&self.window, "Space", KeyMode::Release);
+                self.hold_key("Space");
+                self.wait(Duration::from_millis(500));
+                self.release_key("Space");
             }
             Movement::Move(direction, duration) => {
                 let key = match direction {
@@ -110,9 +150,10 @@ impl<'a> MovementCoordinator {
                         }
                     }
                 };
-                eval_send_key(&self.window, key, KeyMode::Hold);
-                thread::sleep(duration.to_duration(&mut self.rng));
-                eval_send_key(&self.window, key, KeyMode::Release);
+                self.hold_key(key);
+                let duration = duration.to_duration(&mut self.rng);
+                self.wait(duration);
+                self.release_key(key);
             }
             Movement::Rotate(direction, duration) => {
                 let key = match direction {
@@ -126,36 +167,41 @@ impl<'a> MovementCoordinator {
                         }
                     }
                 };
-                eval_send_key(&self.window, key, KeyMode::Hold);
-                thread::sleep(duration.to_duration(&mut self.rng));
-                eval_send_key(&self.window, key, KeyMode::Release);
+                self.hold_key(key);
+                let duration = duration.to_duration(&mut self.rng);
+                self.wait(duration);
+                self.release_key(key);
+            }
+            Movement::Wait(duration) => {
+                let duration = duration.to_duration(&mut self.rng);
+                self.wait(duration);
             }
-            Movement::Wait(duration) => thread::sleep(duration.to_duration(&mut self.rng)),
             Movement::Type(text) => {
-                eval_send_message(&self.window, &text);
+                self.input.send_message(&text);
             }
             Movement::PressKey(key) => {
-                eval_send_key(&self.window, key, KeyMode::Press);
+                self.input.send_key(key, KeyMode::Press);
             }
             Movement::HoldKeyFor(key, duration) => {
-                eval_send_key(&self.window, key, KeyMode::Hold);
-                thread::sleep(duration.to_duration(&mut self.rng));
-                eval_send_key(&self.window, key, KeyMode::Release);
+                self.hold_key(key);
+                let duration = duration.to_duration(&mut self.rng);
+                self.wait(duration);
+                self.release_key(key);
             }
             Movement::HoldKey(key) => {
-                eval_send_key(&self.window, key, KeyMode::Hold);
+                self.hold_key(key);
             }
             Movement::HoldKeys(keys) => {
                 for key in keys {
-                    eval_send_key(&self.window, key, KeyMode::Hold);
+                    self.hold_key(key);
                 }
             }
             Movement::ReleaseKey(key) => {
-                eval_send_key(&self.window, key, KeyMode::Release);
+                self.release_key(key);
             }
             Movement::ReleaseKeys(keys) => {
                 for key in keys {
-                    eval_send_key(&self.window, key, KeyMode::Release);
+                    self.release_key(key);
                 }
             }
             Movement::Repeat(times, movements) => {
@@ -165,4 +211,21 @@ impl<'a> MovementCoordinator {
             }
         }
     }
+
+    fn hold_key(&mut self, key: &str) {
+        self.input.send_key(key, KeyMode::Hold);
+        self.held_keys.push(key.to_string());
+    }
+
+    fn release_key(&mut self, key: &str) {
+        self.input.send_key(key, KeyMode::Release);
+        self.held_keys.retain(|held| held != key);
+    }
+
+    /// Sleep while the held keys keep moving the character.
+    fn wait(&mut self, duration: Duration) {
+        self.clock.sleep(duration);
+        self.odometer
+            .advance(self.held_keys.iter().map(String::as_str), duration);
+    }
 }
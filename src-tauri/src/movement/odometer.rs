@@ -0,0 +1,218 @@
+use std::time::Duration;
+
+/// First guess of the time the character takes to turn by one degree while a rotation key is
+/// held, refined from the headings read on the minimap (see `Odometer::fix_heading`)
+const ROTATION_MS_PER_DEGREE: f32 = 2.5;
+/// Bounds of the learned rotation speed, against misread headings
+const MIN_MS_PER_DEGREE: f32 = 0.5;
+const MAX_MS_PER_DEGREE: f32 = 20.0;
+/// Weight of a new measure of the rotation speed
+const ROTATION_LEARNING_RATE: f32 = 0.3;
+/// Smallest turn between two headings read worth learning from, in degrees
+const MIN_LEARNED_TURN: f32 = 10.0;
+/// Largest turn between two headings read that can't be mistaken for a turn the other way
+const MAX_LEARNED_TURN: f32 = 150.0;
+/// Rough walking speed of a character without buffs, in meters per second
+const WALK_SPEED: f32 = 5.0;
+/// Rough speed of a board or broom, in meters per second
+const FLY_SPEED: f32 = 15.0;
+
+/// Where the character went since the odometer was reset, estimated from the keys held and
+/// for how long. Errors add up over time, so headings should be corrected whenever they can
+/// be read, e.g. from the minimap.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Odometer {
+    /// Meters east and south of the origin
+    position: (f32, f32),
+    /// Degrees clockwise from north
+    heading: f32,
+    is_flying: bool,
+    /// Meters moved in total, never reset
+    travelled: f32,
+    /// Time a rotation key is held to turn by one degree
+    ms_per_degree: f32,
+    /// Heading read last, and milliseconds rotation keys were held since, negative to the left
+    last_fix: Option<(f32, f32)>,
+}
+
+impl Default for Odometer {
+    fn default() -> Self {
+        Self {
+            position: (0.0, 0.0),
+            heading: 0.0,
+            is_flying: false,
+            travelled: 0.0,
+            ms_per_degree: ROTATION_MS_PER_DEGREE,
+            last_fix: None,
+        }
+    }
+}
+
+impl Odometer {
+    /// Make the current position the origin.
+    pub fn reset(&mut self) {
+        self.position = (0.0, 0.0);
+    }
+
+    /// Move at the speed of a board or broom instead of walking.
+    pub fn set_flying(&mut self, is_flying: bool) {
+        self.is_flying = is_flying;
+    }
+
+    pub fn heading(&self) -> f32 {
+        self.heading
+    }
+
+    pub fn set_heading(&mut self, heading: f32) {
+        self.heading = heading.rem_euclid(360.0);
+    }
+
+    /// Use a heading read from the client, e.g. on the minimap. How far the character turned
+    /// since the last reading tells how fast it turns.
+    pub fn fix_heading(&mut self, heading: f32) {
+        if let Some((last_heading, turned_ms)) = self.last_fix {
+            let turned = (heading - last_heading + 540.0).rem_euclid(360.0) - 180.0;
+            let expected = turned_ms / self.ms_per_degree;
+            if expected.abs() <= MAX_LEARNED_TURN
+                && turned.abs() >= MIN_LEARNED_TURN
+                && turned.signum() == turned_ms.signum()
+            {
+                let measured = (turned_ms / turned).clamp(MIN_MS_PER_DEGREE, MAX_MS_PER_DEGREE);
+                self.ms_per_degree += (measured - self.ms_per_degree) * ROTATION_LEARNING_RATE;
+            }
+        }
+        self.last_fix = Some((heading, 0.0));
+        self.set_heading(heading);
+    }
+
+    /// Time to hold a rotation key to turn by one degree.
+    pub fn ms_per_degree(&self) -> f32 {
+        self.ms_per_degree
+    }
+
+    /// Distance to the origin, in meters.
+    pub fn distance(&self) -> f32 {
+        self.distance_to((0.0, 0.0))
+    }
+
+    pub fn travelled(&self) -> f32 {
+        self.travelled
+    }
+
+    /// Meters east and south of the origin.
+    pub fn position(&self) -> (f32, f32) {
+        self.position
+    }
+
+    /// Distance to `(x, y)` meters east and south of the origin.
+    pub fn distance_to(&self, (x, y): (f32, f32)) -> f32 {
+        (x - self.position.0).hypot(y - self.position.1)
+    }
+
+    /// Degrees to turn to face the origin, negative to the left.
+    pub fn turn_towards_origin(&self) -> f32 {
+        self.turn_towards((0.0, 0.0))
+    }
+
+    /// Degrees to turn to face `(x, y)` meters east and south of the origin, negative to
+    /// the left.
+    pub fn turn_towards(&self, (x, y): (f32, f32)) -> f32 {
+        let bearing = (x - self.position.0)
+            .atan2(self.position.1 - y)
+            .to_degrees();
+        (bearing - self.heading + 540.0).rem_euclid(360.0) - 180.0
+    }
+
+    /// Move by `forward` and `right` meters relative to the heading.
+    pub fn displace(&mut self, forward: f32, right: f32) {
+        self.travelled += forward.hypot(right);
+        let (sin, cos) = self.heading.to_radians().sin_cos();
+        self.position.0 += forward * sin + right * cos;
+        self.position.1 += -forward * cos + right * sin;
+    }
+
+    /// Account for `keys` having been held together during `duration`.
+    pub fn advance<'a>(&mut self, keys: impl IntoIterator<Item = &'a str>, duration: Duration) {
+        let (mut forward, mut turn) = (0.0, 0.0);
+        for key in keys {
+            match key {
+                "W" => forward += 1.0,
+                "S" => forward -= 1.0,
+                "A" | "Left" => turn -= 1.0,
+                "D" | "Right" => turn += 1.0,
+                _ => {}
+            }
+        }
+        let ms = duration.as_millis() as f32;
+        let speed = match self.is_flying {
+            true => FLY_SPEED,
+            false => WALK_SPEED,
+        };
+        // Walk along arcs in a few steps
+        let steps = match turn == 0.0 || forward == 0.0 {
+            true => 1.0,
+            false => (ms / 10.0).ceil().max(1.0),
+        };
+        if let Some((_, turned_ms)) = self.last_fix.as_mut() {
+            *turned_ms += turn * ms;
+        }
+        for _ in 0..steps as u32 {
+            self.set_heading(self.heading + turn * ms / steps / self.ms_per_degree);
+            self.displace(forward * speed * ms / steps / 1000.0, 0.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::movement::Odometer;
+
+    #[test]
+    fn test_odometer() {
+        let mut odometer = Odometer::default();
+        odometer.set_heading(90.0);
+
+        // 10m east, then 5m north
+        odometer.advance(["W"], Duration::from_secs(2));
+        odometer.advance(["Left"], Duration::from_millis(225));
+        assert!(odometer.heading().abs() < 0.01);
+        odometer.advance(["W", "Space"], Duration::from_secs(1));
+        assert!((odometer.distance() - 125_f32.sqrt()).abs() < 0.01);
+
+        // The origin is to the south-west, behind on the left
+        assert!((odometer.turn_towards_origin() + 116.57).abs() < 0.1);
+
+        // Turning while walking moves along an arc
+        let mut odometer = Odometer::default();
+        odometer.advance(["W", "D"], Duration::from_millis(450));
+        assert!((odometer.heading() - 180.0).abs() < 0.01);
+        assert!(odometer.distance() > 1.0 && odometer.distance() < 2.25);
+
+        odometer.reset();
+        assert_eq!(odometer.distance(), 0.0);
+        odometer.displace(3.0, 4.0);
+        assert!((odometer.distance() - 5.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_learn_rotation_speed() {
+        // The character turns at 5ms per degree rather than the first guess
+        let mut odometer = Odometer::default();
+        let mut heading = 0.0;
+        odometer.fix_heading(heading);
+        for _ in 0..10 {
+            odometer.advance(["Right"], Duration::from_millis(300));
+            heading += 60.0;
+            odometer.fix_heading(heading);
+        }
+        assert!((odometer.ms_per_degree() - 5.0).abs() < 0.2);
+
+        // Readings which can't be told apart from a turn the other way are ignored
+        let learned = odometer.ms_per_degree();
+        odometer.advance(["Left"], Duration::from_millis(1000));
+        odometer.fix_heading(heading + 160.0);
+        assert_eq!(odometer.ms_per_degree(), learned);
+    }
+}
@@ -0,0 +1,465 @@
+use std::sync::OnceLock;
+
+use crate::data::PointCloud;
+
+/// Minimum intersection over union between a glyph and the name pixels to accept the glyph
+const MIN_GLYPH_SCORE: f32 = 0.6;
+
+/// Character used for pixels that don't match any glyph
+pub const UNKNOWN_CHAR: char = '?';
+
+/// A single character of a bitmap font.
+#[derive(Debug, Clone)]
+pub struct Glyph {
+    pub ch: char,
+    pub width: u32,
+    pixels: Vec<bool>,
+}
+
+impl Glyph {
+    #[inline]
+    fn get(&self, x: u32, y: u32) -> bool {
+        x < self.width && self.pixels.get((y * self.width + x) as usize) == Some(&true)
+    }
+}
+
+/// A bitmap font, see `assets/ocr/flyff_ui_font.txt` for the file format.
+#[derive(Debug, Clone)]
+pub struct GlyphAtlas {
+    height: u32,
+    space: u32,
+    glyphs: Vec<Glyph>,
+}
+
+/// Binary image of a text line, anchored at the top left pixel of its point cloud.
+struct LineMask {
+    width: u32,
+    height: u32,
+    pixels: Vec<bool>,
+}
+
+impl LineMask {
+    fn new(cloud: &PointCloud, min_height: u32) -> Self {
+        let bounds = cloud.to_bounds();
+        let width = bounds.w + 1;
+        let height = (bounds.h + 1).max(min_height);
+        let mut pixels = vec![false; (width * height) as usize];
+        for point in cloud.as_ref() {
+            pixels[((point.y - bounds.y) * width + point.x - bounds.x) as usize] = true;
+        }
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    #[inline]
+    fn get(&self, x: u32, y: i32) -> bool {
+        y >= 0
+            && (y as u32) < self.height
+            && x < self.width
+            && self.pixels[(y as u32 * self.width + x) as usize]
+    }
+
+    fn column_is_empty(&self, x: u32) -> bool {
+        (0..self.height as i32).all(|y| !self.get(x, y))
+    }
+}
+
+impl GlyphAtlas {
+    /// The Flyff UI font bundled with the bot.
+    pub fn flyff_ui() -> &'static GlyphAtlas {
+        static ATLAS: OnceLock<GlyphAtlas> = OnceLock::new();
+        ATLAS.get_or_init(|| {
+            Self::parse(include_str!("../assets/ocr/flyff_ui_font.txt"))
+                .expect("Bundled glyph atlas is invalid")
+        })
+    }
+
+    /// Parse an atlas from its text representation.
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut height = None;
+        let mut space = 3;
+        let mut glyphs: Vec<(char, Vec<&str>)> = vec![];
+
+        for (line_index, line) in source.lines().enumerate() {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+
+            if let Some(value) = line.strip_prefix("height ") {
+                height = Some(value.trim().parse::<u32>().map_err(|err| err.to_string())?);
+            } else if let Some(value) = line.strip_prefix("space ") {
+                space = value.trim().parse::<u32>().map_err(|err| err.to_string())?;
+            } else if let Some(ch) = line.strip_prefix(": ") {
+                let mut chars = ch.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(ch), None) => glyphs.push((ch, vec![])),
+                    _ => {
+                        return Err(format!(
+                            "line {}: expected a single character",
+                            line_index + 1
+                        ))
+                    }
+                }
+            } else if let Some((_, rows)) = glyphs.last_mut() {
+                rows.push(line);
+            } else {
+                return Err(format!("line {}: unexpected content", line_index + 1));
+            }
+        }
+
+        let height = height.ok_or("missing height")?;
+        let glyphs = glyphs
+            .into_iter()
+            .map(|(ch, rows)| Self::parse_glyph(ch, &rows, height))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            height,
+            space,
+            glyphs,
+        })
+    }
+
+    fn parse_glyph(ch: char, rows: &[&str], height: u32) -> Result<Glyph, String> {
+        if rows.len() != height as usize {
+            return Err(format!(
+                "glyph '{}' has {} rows instead of {}",
+                ch,
+                rows.len(),
+                height
+            ));
+        }
+        let is_set = |row: &str, x: usize| row.as_bytes().get(x) == Some(&b'#');
+        let row_width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+
+        // Trim glyph to its leftmost and rightmost pixels
+        let used_columns = (0..row_width)
+            .filter(|&x| rows.iter().any(|row| is_set(row, x)))
+            .collect::<Vec<_>>();
+        let (first, last) = match (used_columns.first(), used_columns.last()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => return Err(format!("glyph '{}' is empty", ch)),
+        };
+
+        let mut pixels = vec![];
+        for row in rows {
+            for x in first..=last {
+                pixels.push(is_set(row, x));
+            }
+        }
+
+        Ok(Glyph {
+            ch,
+            width: (last - first + 1) as u32,
+            pixels,
+        })
+    }
+
+    /// Height of every glyph in pixels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Pixels of `text` drawn in this font, relative to the top left corner of the line, and
+    /// the width of the line. Characters without a glyph are drawn as spaces.
+    pub fn render_text(&self, text: &str) -> (Vec<(u32, u32)>, u32) {
+        let mut pixels = vec![];
+        let mut x = 0;
+        for ch in text.chars() {
+            match self.glyphs.iter().find(|glyph| glyph.ch == ch) {
+                Some(glyph) => {
+                    for y in 0..self.height {
+                        for glyph_x in 0..glyph.width {
+                            if glyph.get(glyph_x, y) {
+                                pixels.push((x + glyph_x, y));
+                            }
+                        }
+                    }
+                    x += glyph.width + 1;
+                }
+                None => x += self.space,
+            }
+        }
+        (pixels, x.saturating_sub(1))
+    }
+
+    /// Read the text of a single line of name pixels.
+    pub fn read_text(&self, cloud: &PointCloud) -> String {
+        if cloud.is_empty() {
+            return String::new();
+        }
+        let mask = LineMask::new(cloud, self.height);
+
+        // The top of the line is either the cap height or, without capitals, the x-height
+        (0..=2)
+            .map(|offset| self.read_line(&mask, offset))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(text, _)| text)
+            .unwrap_or_default()
+    }
+
+    /// Cut a name plate into glyphs at its blank columns, written in the atlas format with `?`
+    /// as character. Used to add glyphs from client captures to the atlas: fill in the
+    /// characters, drop duplicates and split glyphs which touch.
+    pub fn cut_glyphs(&self, cloud: &PointCloud) -> String {
+        if cloud.is_empty() {
+            return String::new();
+        }
+        let mask = LineMask::new(cloud, self.height);
+
+        let mut sheet = String::new();
+        let mut x = 0;
+        while x < mask.width {
+            if mask.column_is_empty(x) {
+                x += 1;
+                continue;
+            }
+            let start = x;
+            while x < mask.width && !mask.column_is_empty(x) {
+                x += 1;
+            }
+            sheet.push_str(&format!(": {}\n", UNKNOWN_CHAR));
+            for y in 0..mask.height as i32 {
+                for column in start..x {
+                    sheet.push(if mask.get(column, y) { '#' } else { '.' });
+                }
+                sheet.push('\n');
+            }
+            sheet.push('\n');
+        }
+        sheet
+    }
+
+    /// Read a line whose top row is `offset` rows below the top of the glyphs.
+    /// Returns the text and the total glyph score.
+    fn read_line(&self, mask: &LineMask, offset: i32) -> (String, f32) {
+        let mut text = String::new();
+        let mut total_score = 0.0;
+
+        let mut x = 0;
+        let mut gap = 0;
+        while x < mask.width {
+            if mask.column_is_empty(x) {
+                gap += 1;
+                x += 1;
+                continue;
+            }
+
+            // Find the end of the current run of columns
+            let start = x;
+            while x < mask.width && !mask.column_is_empty(x) {
+                x += 1;
+            }
+            if !text.is_empty() && gap >= self.space {
+                text.push(' ');
+            }
+            gap = 0;
+
+            let (chars, score) = self.read_segment(mask, start, x - 1, offset);
+            text.push_str(&chars);
+            total_score += score;
+        }
+
+        (text, total_score)
+    }
+
+    /// Read the glyphs of a run of non-empty columns. Glyphs usually have a blank column
+    /// between them, touching glyphs are split greedily.
+    fn read_segment(&self, mask: &LineMask, start: u32, end: u32, offset: i32) -> (String, f32) {
+        // Whole segment is a single glyph
+        let segment_width = end - start + 1;
+        if let Some((glyph, score)) = self
+            .glyphs
+            .iter()
+            .filter(|glyph| glyph.width == segment_width)
+            .map(|glyph| (glyph, self.score(glyph, mask, start, end, offset)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+        {
+            if score >= MIN_GLYPH_SCORE {
+                return (glyph.ch.to_string(), score);
+            }
+        }
+
+        let mut text = String::new();
+        let mut total_score = 0.0;
+        let mut x = start;
+        while x <= end {
+            let best = self
+                .glyphs
+                .iter()
+                .filter(|glyph| x + glyph.width - 1 <= end)
+                .map(|glyph| (glyph, self.score(glyph, mask, x, end, offset)))
+                .max_by(|a, b| a.1.total_cmp(&b.1).then(a.0.width.cmp(&b.0.width)));
+
+            match best {
+                Some((glyph, score)) if score >= MIN_GLYPH_SCORE => {
+                    text.push(glyph.ch);
+                    total_score += score;
+                    x += glyph.width;
+                }
+                _ => {
+                    text.push(UNKNOWN_CHAR);
+                    break;
+                }
+            }
+        }
+
+        (text, total_score)
+    }
+
+    /// Intersection over union of a glyph placed at column `x` and the name pixels.
+    /// Unless the glyph ends the segment, the column after it has to be blank too.
+    fn score(&self, glyph: &Glyph, mask: &LineMask, x: u32, end: u32, offset: i32) -> f32 {
+        let columns = if x + glyph.width - 1 < end {
+            glyph.width + 1
+        } else {
+            glyph.width
+        };
+
+        let (mut intersection, mut union) = (0, 0);
+        for glyph_y in 0..self.height {
+            let mask_y = glyph_y as i32 - offset;
+            for glyph_x in 0..columns {
+                let a = glyph.get(glyph_x, glyph_y);
+                let b = mask.get(x + glyph_x, mask_y);
+                if a && b {
+                    intersection += 1;
+                }
+                if a || b {
+                    union += 1;
+                }
+            }
+        }
+
+        if union == 0 {
+            0.0
+        } else {
+            intersection as f32 / union as f32
+        }
+    }
+}
+
+/// Split a name plate like `Lv.12 Aibatt` into the name and the level.
+pub fn parse_name_level(text: &str) -> (Option<String>, Option<u32>) {
+    let mut level = None;
+    let mut words = vec![];
+
+    for word in text.split_whitespace() {
+        let word = word.trim_matches(|c| c == '[' || c == ']');
+        if let Some(digits) = word.strip_prefix("Lv.").or_else(|| word.strip_prefix("Lv")) {
+            if level.is_none() {
+                level = digits.parse::<u32>().ok();
+            }
+            continue;
+        }
+        if !word.is_empty() {
+            words.push(word);
+        }
+    }
+
+    let name = match words.is_empty() {
+        true => None,
+        false => Some(words.join(" ")),
+    };
+    (name, level)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        data::{Point, PointCloud},
+        ocr::{parse_name_level, GlyphAtlas},
+    };
+
+    /// Render `text` with one blank column between glyphs, like the client does.
+    fn render(atlas: &GlyphAtlas, text: &str, x: u32, y: u32) -> PointCloud {
+        let (pixels, _) = atlas.render_text(text);
+        let mut cloud = PointCloud::default();
+        for (glyph_x, glyph_y) in pixels {
+            cloud.push(Point::new(x + glyph_x, y + glyph_y));
+        }
+        cloud
+    }
+
+    #[test]
+    fn test_read_text() {
+        let atlas = GlyphAtlas::flyff_ui();
+
+        // Every glyph reads back as itself
+        for glyph in atlas.glyphs.iter() {
+            let text = format!("A{}", glyph.ch);
+            assert_eq!(atlas.read_text(&render(atlas, &text, 10, 20)), text);
+        }
+
+        for text in [
+            "Lv.12 Aibatt",
+            "Mia Lv.3",
+            "Red Mantis",
+            "ocean",
+            "Giggle Box",
+        ] {
+            assert_eq!(atlas.read_text(&render(atlas, text, 300, 200)), text);
+        }
+
+        // Touching glyphs are split too
+        let mut cloud = render(atlas, "Mu", 0, 0);
+        for point in render(atlas, "ss", 11, 0).as_ref() {
+            cloud.push(*point);
+        }
+        assert_eq!(atlas.read_text(&cloud), "Muss");
+
+        assert_eq!(atlas.read_text(&PointCloud::default()), "");
+        assert_eq!(
+            atlas.read_text(&PointCloud::from([(0, 0), (1, 1), (2, 0)])),
+            "?"
+        );
+    }
+
+    #[test]
+    fn test_cut_glyphs() {
+        let atlas = GlyphAtlas::flyff_ui();
+        let sheet = atlas.cut_glyphs(&render(atlas, "Ab", 40, 30));
+        let cut = GlyphAtlas::parse(&format!("height {}\n{}", atlas.height, sheet)).unwrap();
+
+        assert_eq!(cut.glyphs.len(), 2);
+        for (glyph, ch) in cut.glyphs.iter().zip(['A', 'b']) {
+            let expected = atlas.glyphs.iter().find(|glyph| glyph.ch == ch).unwrap();
+            assert_eq!(glyph.ch, '?');
+            assert_eq!(glyph.width, expected.width);
+            assert_eq!(glyph.pixels, expected.pixels);
+        }
+        assert_eq!(atlas.cut_glyphs(&PointCloud::default()), "");
+    }
+
+    #[test]
+    fn test_parse_name_level() {
+        assert_eq!(
+            parse_name_level("Lv.12 Aibatt"),
+            (Some("Aibatt".to_string()), Some(12))
+        );
+        assert_eq!(
+            parse_name_level("Giggle Box [Lv.3]"),
+            (Some("Giggle Box".to_string()), Some(3))
+        );
+        assert_eq!(parse_name_level("Lv.1?"), (None, None));
+        assert_eq!(
+            parse_name_level("Aibatt"),
+            (Some("Aibatt".to_string()), None)
+        );
+    }
+
+    #[test]
+    fn test_parse_atlas() {
+        let atlas = GlyphAtlas::parse("height 2\nspace 4\n: x\n.#.\n#.#\n").unwrap();
+        assert_eq!(atlas.space, 4);
+        assert_eq!(atlas.glyphs[0].width, 3);
+
+        assert!(GlyphAtlas::parse(": x\n#\n").is_err());
+        assert!(GlyphAtlas::parse("height 2\n: x\n#\n").is_err());
+        assert!(GlyphAtlas::parse("height 1\n: x\n.\n").is_err());
+    }
+}
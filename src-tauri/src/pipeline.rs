@@ -0,0 +1,171 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use parking_lot::Mutex;
+use slog::Logger;
+
+use crate::{
+    data::ClientStats,
+    image_analyzer::{BarColors, Frame, ImageAnalyzer, MobColors},
+    ipc::PipelineStats,
+};
+
+mod latest;
+mod stage_meter;
+
+pub use self::{latest::Latest, stage_meter::StageMeter};
+
+/// Shortest time between two captures, caps the pipeline at about 30 frames per second
+const MIN_FRAME_INTERVAL: Duration = Duration::from_millis(33);
+/// How long the capture thread sleeps while the bot isn't running
+const IDLE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A captured frame with the client stats as of this frame.
+#[derive(Debug)]
+pub struct AnalyzedFrame {
+    /// Increases by one with every capture
+    pub id: u64,
+    pub captured_at: Instant,
+    pub frame: Frame,
+    pub client_stats: ClientStats,
+}
+
+/// Captures and analyzes frames on its own thread, so detection keeps up while behaviors
+/// wait for their input to play out.
+///
+/// Only the newest analyzed frame is kept for the behaviors, see `Latest`.
+pub struct FramePipeline {
+    frames: Latest<AnalyzedFrame>,
+    mob_colors: Latest<MobColors>,
+    bar_colors: Latest<BarColors>,
+    is_running: Arc<AtomicBool>,
+    is_stopped: Arc<AtomicBool>,
+    stats: Arc<Mutex<PipelineStats>>,
+    behavior_meter: StageMeter,
+    /// The capture thread, joined on drop
+    thread: Option<JoinHandle<()>>,
+}
+
+impl FramePipeline {
+    /// Start capturing with `analyzer`, which also owns the client stats from now on.
+    pub fn spawn(mut analyzer: ImageAnalyzer, logger: Logger) -> Self {
+        let mut pipeline = Self {
+            frames: Latest::new(),
+            mob_colors: Latest::new(),
+            bar_colors: Latest::new(),
+            is_running: Arc::new(AtomicBool::new(false)),
+            is_stopped: Arc::new(AtomicBool::new(false)),
+            stats: Arc::new(Mutex::new(PipelineStats::default())),
+            behavior_meter: StageMeter::new(),
+            thread: None,
+        };
+
+        let frames = pipeline.frames.clone();
+        let mob_colors = pipeline.mob_colors.clone();
+        let bar_colors = pipeline.bar_colors.clone();
+        let is_running = pipeline.is_running.clone();
+        let is_stopped = pipeline.is_stopped.clone();
+        let stats = pipeline.stats.clone();
+        pipeline.thread = Some(thread::spawn(move || {
+            let mut capture_meter = StageMeter::new();
+            let mut analysis_meter = StageMeter::new();
+            let mut next_id = 0;
+
+            while !is_stopped.load(Ordering::Relaxed) {
+                if !is_running.load(Ordering::Relaxed) {
+                    thread::sleep(IDLE_INTERVAL);
+                    continue;
+                }
+                if let Some(colors) = mob_colors.take() {
+                    analyzer.set_mob_colors(colors);
+                }
+                if let Some(colors) = bar_colors.take() {
+                    analyzer.set_bar_colors(colors);
+                }
+
+                // Capture
+                let captured_at = Instant::now();
+                analyzer.capture_window(&logger);
+                let frame = match analyzer.take_frame() {
+                    Some(frame) => frame,
+                    None => {
+                        thread::sleep(MIN_FRAME_INTERVAL);
+                        continue;
+                    }
+                };
+                capture_meter.record(captured_at);
+
+                // Analyze
+                let analysis_started = Instant::now();
+                frame.scan();
+                analyzer.client_stats.update(&frame, &logger);
+                analysis_meter.record(analysis_started);
+
+                {
+                    let mut stats = stats.lock();
+                    stats.capture = capture_meter.stats();
+                    stats.analysis = analysis_meter.stats();
+                }
+                frames.publish(AnalyzedFrame {
+                    id: next_id,
+                    captured_at,
+                    frame,
+                    client_stats: analyzer.client_stats.clone(),
+                });
+                next_id += 1;
+
+                if let Some(remaining) = MIN_FRAME_INTERVAL.checked_sub(captured_at.elapsed()) {
+                    thread::sleep(remaining);
+                }
+            }
+        }));
+
+        pipeline
+    }
+
+    /// Only capture while the bot is running.
+    pub fn set_running(&self, is_running: bool) {
+        self.is_running.store(is_running, Ordering::Relaxed);
+    }
+
+    /// Search mob names with these colors, starting with the next capture.
+    pub fn set_mob_colors(&self, mob_colors: MobColors) {
+        self.mob_colors.publish(mob_colors);
+    }
+
+    /// Search the stat bars with these colors, starting with the next capture.
+    pub fn set_bar_colors(&self, bar_colors: BarColors) {
+        self.bar_colors.publish(bar_colors);
+    }
+
+    /// Take the newest analyzed frame, waiting up to `timeout` for one.
+    pub fn next_frame(&self, timeout: Duration) -> Option<AnalyzedFrame> {
+        self.frames.wait(timeout)
+    }
+
+    /// Record that behaviors finished acting on the frame captured at `captured_at`.
+    pub fn record_behavior(&mut self, captured_at: Instant) {
+        self.behavior_meter.record(captured_at);
+        self.stats.lock().behavior = self.behavior_meter.stats();
+    }
+
+    pub fn stats(&self) -> PipelineStats {
+        *self.stats.lock()
+    }
+}
+
+impl Drop for FramePipeline {
+    fn drop(&mut self) {
+        self.is_stopped.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            // A panic of the capture thread was already reported by the panic hook
+            drop(thread.join());
+        }
+    }
+}
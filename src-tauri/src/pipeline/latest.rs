@@ -0,0 +1,79 @@
+use std::{sync::Arc, time::Duration};
+
+use parking_lot::{Condvar, Mutex};
+
+/// A channel that only keeps the most recent value.
+///
+/// Publishing replaces a value nobody took yet, so a slow consumer always gets the newest
+/// value instead of working through a backlog of stale ones.
+#[derive(Debug)]
+pub struct Latest<T> {
+    shared: Arc<(Mutex<Option<T>>, Condvar)>,
+}
+
+impl<T> Clone for Latest<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Default for Latest<T> {
+    fn default() -> Self {
+        Self {
+            shared: Arc::new((Mutex::new(None), Condvar::new())),
+        }
+    }
+}
+
+impl<T> Latest<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Publish `value`. Returns `true` if it replaced a value that was never taken.
+    pub fn publish(&self, value: T) -> bool {
+        let (slot, published) = &*self.shared;
+        let replaced = slot.lock().replace(value).is_some();
+        published.notify_all();
+        replaced
+    }
+
+    /// Take the current value, if there is one.
+    pub fn take(&self) -> Option<T> {
+        self.shared.0.lock().take()
+    }
+
+    /// Take the current value, waiting up to `timeout` for one to be published.
+    pub fn wait(&self, timeout: Duration) -> Option<T> {
+        let (slot, published) = &*self.shared;
+        let mut slot = slot.lock();
+        if slot.is_none() {
+            published.wait_for(&mut slot, timeout);
+        }
+        slot.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use crate::pipeline::Latest;
+
+    #[test]
+    fn test_latest() {
+        let latest = Latest::new();
+        assert!(!latest.publish(1));
+        assert!(latest.publish(2));
+        assert_eq!(latest.take(), Some(2));
+        assert_eq!(latest.take(), None);
+        assert_eq!(latest.wait(Duration::from_millis(1)), None);
+
+        let publisher = latest.clone();
+        let handle = thread::spawn(move || publisher.publish(3));
+        assert_eq!(latest.wait(Duration::from_secs(5)), Some(3));
+        assert!(!handle.join().unwrap());
+    }
+}
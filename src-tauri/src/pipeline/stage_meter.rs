@@ -0,0 +1,45 @@
+use std::time::{Duration, Instant};
+
+use crate::ipc::StageStats;
+
+/// Weight of the newest sample in the moving averages
+const SMOOTHING: f32 = 0.1;
+
+/// Measures how often a pipeline stage completes and how long it takes.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StageMeter {
+    last_completed: Option<Instant>,
+    stats: StageStats,
+}
+
+impl StageMeter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a completed run of the stage which started processing its input at `started`.
+    pub fn record(&mut self, started: Instant) {
+        let now = Instant::now();
+        let latency = now.saturating_duration_since(started);
+        let smooth = |average: f32, sample: f32| {
+            if average == 0.0 {
+                sample
+            } else {
+                average + (sample - average) * SMOOTHING
+            }
+        };
+
+        self.stats.latency_ms = smooth(self.stats.latency_ms, latency.as_secs_f32() * 1000.0);
+        if let Some(last_completed) = self.last_completed {
+            let interval = now.saturating_duration_since(last_completed);
+            if interval > Duration::ZERO {
+                self.stats.fps = smooth(self.stats.fps, 1.0 / interval.as_secs_f32());
+            }
+        }
+        self.last_completed = Some(now);
+    }
+
+    pub fn stats(&self) -> StageStats {
+        self.stats
+    }
+}
@@ -1,6 +1,16 @@
 pub mod shared;
 pub use self::shared::*;
 
+#[cfg(feature = "app")]
+mod window;
+#[cfg(feature = "app")]
+pub use self::window::*;
+
+mod input_sink;
+#[cfg(feature = "app")]
+pub use self::input_sink::EvalInputSink;
+pub use self::input_sink::{DryRunInputSink, InputAction, InputSink, RecordingInputSink};
+
 //
 // Windows
 //
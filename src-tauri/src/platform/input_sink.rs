@@ -0,0 +1,282 @@
+use std::{
+    fmt::{self, Debug},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use parking_lot::Mutex;
+use slog::Logger;
+#[cfg(feature = "app")]
+use tauri::{Manager, Window};
+
+use super::KeyMode;
+#[cfg(feature = "app")]
+use super::{eval_mob_click, eval_send_key, eval_send_message, eval_simple_click, send_slot_eval};
+use crate::{data::Point, ipc::SkippedInput};
+
+/// Skipped input kept until the frontend takes it
+const MAX_SKIPPED_INPUT: usize = 200;
+
+/// Receives the input behaviors send to the client.
+pub trait InputSink: Debug + Send + Sync {
+    fn send_key(&self, key: &str, mode: KeyMode);
+
+    /// Use the slot `slot_index` of the slot bar `slot_bar_index`.
+    fn send_slot(&self, slot_bar_index: usize, slot_index: usize);
+
+    /// Click at `pos` if there's a mob under the cursor.
+    fn mob_click(&self, pos: Point);
+
+    fn click(&self, pos: Point);
+
+    /// Write `text` into the chat input.
+    fn send_message(&self, text: &str);
+
+    /// Close the client, e.g. when no mob was found for too long.
+    fn exit(&self);
+
+    /// Whether input reaches the client, rather than only being logged.
+    fn sends_input(&self) -> bool {
+        true
+    }
+}
+
+/// Sends input to the client window through `eval.js`.
+#[cfg(feature = "app")]
+#[derive(Debug)]
+pub struct EvalInputSink {
+    window: Window,
+}
+
+#[cfg(feature = "app")]
+impl EvalInputSink {
+    pub fn new(window: Window) -> Self {
+        Self { window }
+    }
+}
+
+#[cfg(feature = "app")]
+impl InputSink for EvalInputSink {
+    fn send_key(&self, key: &str, mode: KeyMode) {
+        eval_send_key(&self.window, key, mode);
+    }
+
+    fn send_slot(&self, slot_bar_index: usize, slot_index: usize) {
+        send_slot_eval(&self.window, slot_bar_index, slot_index);
+    }
+
+    fn mob_click(&self, pos: Point) {
+        eval_mob_click(&self.window, pos);
+    }
+
+    fn click(&self, pos: Point) {
+        eval_simple_click(&self.window, pos);
+    }
+
+    fn send_message(&self, text: &str) {
+        eval_send_message(&self.window, text);
+    }
+
+    fn exit(&self) {
+        self.window.app_handle().exit(0);
+    }
+}
+
+/// Forwards input to another sink, or only logs and records it while dry run is enabled.
+#[derive(Debug)]
+pub struct DryRunInputSink {
+    inner: Arc<dyn InputSink>,
+    is_dry_run: AtomicBool,
+    skipped: Mutex<Vec<SkippedInput>>,
+    logger: Logger,
+}
+
+impl DryRunInputSink {
+    pub fn new(inner: Arc<dyn InputSink>, logger: Logger) -> Self {
+        Self {
+            inner,
+            is_dry_run: AtomicBool::new(false),
+            skipped: Mutex::new(Vec::new()),
+            logger,
+        }
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.is_dry_run.load(Ordering::Relaxed)
+    }
+
+    pub fn set_dry_run(&self, is_dry_run: bool) {
+        if self.is_dry_run.swap(is_dry_run, Ordering::Relaxed) != is_dry_run {
+            slog::info!(self.logger, "Dry run changed"; "is_dry_run" => is_dry_run);
+        }
+    }
+
+    /// Take all input skipped since the last call, oldest first.
+    pub fn take_skipped(&self) -> Vec<SkippedInput> {
+        std::mem::take(&mut *self.skipped.lock())
+    }
+
+    fn handle(&self, action: InputAction, send: impl FnOnce(&dyn InputSink)) {
+        if !self.is_dry_run() {
+            send(&*self.inner);
+            return;
+        }
+
+        let action = action.to_string();
+        slog::info!(self.logger, "Dry run skipped input"; "action" => &action);
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since_epoch| since_epoch.as_millis() as u64)
+            .unwrap_or(0);
+        let mut skipped = self.skipped.lock();
+        if skipped.len() == MAX_SKIPPED_INPUT {
+            skipped.remove(0);
+        }
+        skipped.push(SkippedInput { timestamp, action });
+    }
+}
+
+impl InputSink for DryRunInputSink {
+    fn send_key(&self, key: &str, mode: KeyMode) {
+        self.handle(InputAction::Key(key.to_string(), mode), |inner| {
+            inner.send_key(key, mode)
+        });
+    }
+
+    fn send_slot(&self, slot_bar_index: usize, slot_index: usize) {
+        self.handle(InputAction::Slot(slot_bar_index, slot_index), |inner| {
+            inner.send_slot(slot_bar_index, slot_index)
+        });
+    }
+
+    fn mob_click(&self, pos: Point) {
+        self.handle(InputAction::MobClick(pos), |inner| inner.mob_click(pos));
+    }
+
+    fn click(&self, pos: Point) {
+        self.handle(InputAction::Click(pos), |inner| inner.click(pos));
+    }
+
+    fn send_message(&self, text: &str) {
+        self.handle(InputAction::Message(text.to_string()), |inner| {
+            inner.send_message(text)
+        });
+    }
+
+    fn exit(&self) {
+        self.handle(InputAction::Exit, |inner| inner.exit());
+    }
+
+    fn sends_input(&self) -> bool {
+        !self.is_dry_run() && self.inner.sends_input()
+    }
+}
+
+/// Input as recorded by `RecordingInputSink`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputAction {
+    Key(String, KeyMode),
+    Slot(usize, usize),
+    MobClick(Point),
+    Click(Point),
+    Message(String),
+    Exit,
+}
+
+impl fmt::Display for InputAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InputAction::Key(key, mode) => write!(f, "{:?} key {}", mode, key),
+            InputAction::Slot(slot_bar_index, slot_index) => {
+                write!(f, "use slot {} of bar {}", slot_index, slot_bar_index)
+            }
+            InputAction::MobClick(pos) => write!(f, "click mob at {}", pos),
+            InputAction::Click(pos) => write!(f, "click at {}", pos),
+            InputAction::Message(text) => write!(f, "write message {:?}", text),
+            InputAction::Exit => write!(f, "close the client"),
+        }
+    }
+}
+
+/// Keeps all input in memory instead of sending it anywhere, so tests can check it.
+#[derive(Debug, Default)]
+pub struct RecordingInputSink {
+    actions: Mutex<Vec<InputAction>>,
+}
+
+impl RecordingInputSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take all actions recorded so far.
+    pub fn take(&self) -> Vec<InputAction> {
+        std::mem::take(&mut *self.actions.lock())
+    }
+
+    fn record(&self, action: InputAction) {
+        self.actions.lock().push(action);
+    }
+}
+
+impl InputSink for RecordingInputSink {
+    fn send_key(&self, key: &str, mode: KeyMode) {
+        self.record(InputAction::Key(key.to_string(), mode));
+    }
+
+    fn send_slot(&self, slot_bar_index: usize, slot_index: usize) {
+        self.record(InputAction::Slot(slot_bar_index, slot_index));
+    }
+
+    fn mob_click(&self, pos: Point) {
+        self.record(InputAction::MobClick(pos));
+    }
+
+    fn click(&self, pos: Point) {
+        self.record(InputAction::Click(pos));
+    }
+
+    fn send_message(&self, text: &str) {
+        self.record(InputAction::Message(text.to_string()));
+    }
+
+    fn exit(&self) {
+        self.record(InputAction::Exit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use slog::Logger;
+
+    use crate::platform::{DryRunInputSink, InputAction, InputSink, RecordingInputSink};
+
+    #[test]
+    fn test_dry_run_input_sink() {
+        let recording = Arc::new(RecordingInputSink::new());
+        let sink = DryRunInputSink::new(recording.clone(), Logger::root(slog::Discard, slog::o!()));
+
+        sink.send_slot(0, 1);
+        assert_eq!(recording.take(), vec![InputAction::Slot(0, 1)]);
+        assert!(sink.take_skipped().is_empty());
+        assert!(sink.sends_input());
+
+        sink.set_dry_run(true);
+        assert!(!sink.sends_input());
+        sink.send_slot(0, 1);
+        sink.exit();
+        assert_eq!(recording.take(), vec![]);
+        let skipped = sink
+            .take_skipped()
+            .into_iter()
+            .map(|skipped| skipped.action)
+            .collect::<Vec<_>>();
+        assert_eq!(skipped, vec!["use slot 1 of bar 0", "close the client"]);
+        assert!(sink.take_skipped().is_empty());
+    }
+}
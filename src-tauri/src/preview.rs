@@ -0,0 +1,208 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use image::{codecs::jpeg::JpegEncoder, imageops, ColorType, DynamicImage};
+
+use crate::{
+    data::TargetType,
+    debug_frames::{annotate_frame, FrameAnnotations},
+    image_analyzer::{Frame, ImageAnalyzer},
+    ipc::{FarmingConfig, PreviewFrame, PreviewMob},
+};
+
+/// Time between two previews
+const PREVIEW_INTERVAL: Duration = Duration::from_millis(500);
+/// Width previews are scaled down to
+const PREVIEW_WIDTH: u32 = 400;
+const PREVIEW_JPEG_QUALITY: u8 = 60;
+/// Frames of the last previews kept until the user picks one to mark regions on
+const RECENT_FRAMES: usize = 4;
+
+/// Builds annotated previews of the analyzed frames for the frontend, a few times a second.
+///
+/// The frames of the last previews are kept by id, so a calibration runs on the pixels the
+/// user marked regions on rather than on the newest capture.
+#[derive(Debug, Default)]
+pub struct PreviewStreamer {
+    is_enabled: bool,
+    last_sent: Option<Instant>,
+    recent_frames: VecDeque<(u64, Frame)>,
+    /// Frame the user marks regions on, kept until another one is held
+    held_frame: Option<(u64, Frame)>,
+}
+
+impl PreviewStreamer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_enabled(&mut self, is_enabled: bool) {
+        self.is_enabled = is_enabled;
+    }
+
+    /// Tell whether a preview is due, and if so count it as sent.
+    pub fn should_send(&mut self) -> bool {
+        if !self.is_enabled {
+            return false;
+        }
+        let now = Instant::now();
+        match self.last_sent {
+            Some(last_sent) if now.saturating_duration_since(last_sent) < PREVIEW_INTERVAL => false,
+            _ => {
+                self.last_sent = Some(now);
+                true
+            }
+        }
+    }
+
+    /// Keep the frame of the preview `frame_id` until another one is held. `false` if the
+    /// frame is already gone.
+    pub fn hold(&mut self, frame_id: u64) -> bool {
+        if matches!(&self.held_frame, Some((id, _)) if *id == frame_id) {
+            return true;
+        }
+        match self.recent_frames.iter().find(|(id, _)| *id == frame_id) {
+            Some((_, frame)) => {
+                self.held_frame = Some((frame_id, frame.share()));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The frame a preview was made from, if it's still kept.
+    pub fn frame(&self, frame_id: u64) -> Option<&Frame> {
+        self.held_frame
+            .iter()
+            .chain(self.recent_frames.iter())
+            .find(|(id, _)| *id == frame_id)
+            .map(|(_, frame)| frame)
+    }
+
+    /// Annotate and downscale the current frame of `analyzer`, whose id is `frame_id`.
+    pub fn render(
+        &mut self,
+        frame_id: u64,
+        analyzer: &ImageAnalyzer,
+        config: &FarmingConfig,
+        annotations: &FrameAnnotations,
+    ) -> Option<PreviewFrame> {
+        let frame = analyzer.frame()?;
+        // Name plates read for the behavior are kept by the frame, so this doesn't read them again
+        let mobs = frame.identify_mobs(config);
+        let target_marker = analyzer.client_stats.target_marker.as_ref();
+        let image = annotate_frame(frame, &mobs, target_marker, annotations);
+
+        let (width, height) = image.dimensions();
+        let preview_width = width.min(PREVIEW_WIDTH);
+        let preview_height = (height * preview_width / width.max(1)).max(1);
+        let image = imageops::resize(
+            &image,
+            preview_width,
+            preview_height,
+            imageops::FilterType::Triangle,
+        );
+        let image = DynamicImage::ImageRgba8(image).to_rgb8();
+
+        let mut jpeg = vec![];
+        JpegEncoder::new_with_quality(&mut jpeg, PREVIEW_JPEG_QUALITY)
+            .encode(
+                image.as_raw(),
+                preview_width,
+                preview_height,
+                ColorType::Rgb8,
+            )
+            .ok()?;
+
+        if self.recent_frames.len() == RECENT_FRAMES {
+            self.recent_frames.pop_front();
+        }
+        self.recent_frames.push_back((frame_id, frame.share()));
+
+        Some(PreviewFrame {
+            frame_id,
+            image: format!("data:image/jpeg;base64,{}", BASE64.encode(&jpeg)),
+            width: preview_width,
+            height: preview_height,
+            frame_width: width,
+            frame_height: height,
+            state: annotations.state.clone(),
+            mobs: mobs
+                .iter()
+                .filter_map(|mob| match mob.target_type {
+                    TargetType::Mob(mob_type) => Some(PreviewMob {
+                        mob_type,
+                        name: mob.name.map(|name| name.to_string()),
+                        level: mob.level,
+                    }),
+                    _ => None,
+                })
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use crate::{
+        debug_frames::FrameAnnotations, image_analyzer::ImageAnalyzer, ipc::FarmingConfig,
+        preview::PreviewStreamer,
+    };
+
+    #[test]
+    fn test_render_preview() {
+        let mut streamer = PreviewStreamer::new();
+        assert!(!streamer.should_send());
+        streamer.set_enabled(true);
+        assert!(streamer.should_send());
+        assert!(!streamer.should_send());
+
+        let analyzer =
+            ImageAnalyzer::from_image(RgbaImage::from_pixel(800, 600, Rgba([0, 0, 0, 255])));
+        let preview = streamer
+            .render(
+                7,
+                &analyzer,
+                &FarmingConfig::default(),
+                &FrameAnnotations::default(),
+            )
+            .unwrap();
+        assert_eq!(preview.frame_id, 7);
+        assert_eq!((preview.width, preview.height), (400, 300));
+        assert_eq!((preview.frame_width, preview.frame_height), (800, 600));
+        assert!(preview.image.starts_with("data:image/jpeg;base64,/9j/"));
+        assert!(preview.mobs.is_empty());
+
+        assert!(streamer
+            .render(
+                8,
+                &ImageAnalyzer::headless(),
+                &FarmingConfig::default(),
+                &FrameAnnotations::default()
+            )
+            .is_none());
+
+        // The frame of a held preview outlives newer previews
+        assert!(streamer.hold(7));
+        let other =
+            ImageAnalyzer::from_image(RgbaImage::from_pixel(800, 600, Rgba([9, 9, 9, 255])));
+        for frame_id in 10..20 {
+            streamer.render(
+                frame_id,
+                &other,
+                &FarmingConfig::default(),
+                &FrameAnnotations::default(),
+            );
+        }
+        let held = streamer.frame(7).unwrap();
+        assert_eq!(held.image().get_pixel(0, 0), &Rgba([0, 0, 0, 255]));
+        assert!(streamer.frame(19).is_some());
+        assert!(streamer.frame(10).is_none());
+        assert!(!streamer.hold(10));
+    }
+}
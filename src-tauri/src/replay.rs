@@ -0,0 +1,133 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use slog::Logger;
+
+use crate::image_analyzer::{ImageAnalyzer, ImageBuffer};
+
+/// A recorded session: an ordered list of PNG frames on disk.
+///
+/// Frames are fed through the same `ImageAnalyzer` and `ClientStats` code as live captures,
+/// so detection bugs can be reproduced from saved screenshots without a client window.
+#[derive(Debug, Clone, Default)]
+pub struct FrameReplay {
+    frames: Vec<PathBuf>,
+}
+
+#[allow(dead_code)]
+impl FrameReplay {
+    /// Replay the given files in the given order.
+    pub fn from_files<I, P>(files: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        Self {
+            frames: files.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// Replay all PNG files of a directory, sorted by file name.
+    pub fn from_dir<P>(dir: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let mut frames = vec![];
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let is_png = path
+                .extension()
+                .map(|ext| ext.eq_ignore_ascii_case("png"))
+                .unwrap_or(false);
+            if path.is_file() && is_png {
+                frames.push(path);
+            }
+        }
+        frames.sort();
+
+        Ok(Self { frames })
+    }
+
+    pub fn frames(&self) -> &[PathBuf] {
+        &self.frames
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Read a single frame into a capture buffer.
+    pub fn load_frame<P>(path: P) -> image::ImageResult<ImageBuffer>
+    where
+        P: AsRef<Path>,
+    {
+        Ok(image::open(path)?.to_rgba8())
+    }
+
+    /// Feed every frame through `analyzer` and update its stats, like the main loop does
+    /// after a capture. `on_frame` is called once the stats of a frame are up to date.
+    pub fn run<F>(
+        &self,
+        analyzer: &mut ImageAnalyzer,
+        logger: &Logger,
+        mut on_frame: F,
+    ) -> image::ImageResult<()>
+    where
+        F: FnMut(&Path, &ImageAnalyzer),
+    {
+        for path in self.frames.iter() {
+            analyzer.set_image(Self::load_frame(path)?);
+            analyzer.update_stats(logger);
+            on_frame(path, analyzer);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use image::{Rgba, RgbaImage};
+
+    use crate::{image_analyzer::ImageAnalyzer, replay::FrameReplay};
+
+    #[test]
+    fn test_replay_dir() {
+        let dir = std::env::temp_dir().join(format!("neuz_replay_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        RgbaImage::from_pixel(40, 30, Rgba([0, 0, 0, 255]))
+            .save(dir.join("frame_002.png"))
+            .unwrap();
+        RgbaImage::from_pixel(20, 10, Rgba([0, 0, 0, 255]))
+            .save(dir.join("frame_001.png"))
+            .unwrap();
+        fs::write(dir.join("notes.txt"), "not a frame").unwrap();
+
+        let replay = FrameReplay::from_dir(&dir).unwrap();
+        assert_eq!(replay.len(), 2);
+        assert!(replay.frames()[0].ends_with("frame_001.png"));
+
+        let logger = slog::Logger::root(slog::Discard, slog::o!());
+        let mut analyzer = ImageAnalyzer::headless();
+        let mut sizes = vec![];
+        replay
+            .run(&mut analyzer, &logger, |_, analyzer| {
+                sizes.push(analyzer.image_size());
+            })
+            .unwrap();
+        assert_eq!(sizes, vec![Some((20, 10)), Some((40, 30))]);
+
+        // Blank frames carry no stats
+        assert_eq!(analyzer.client_stats.hp.value, 0);
+        assert!(analyzer.client_stats.target_marker.is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
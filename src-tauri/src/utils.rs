@@ -1,4 +1,11 @@
+mod clock;
 mod datetime;
+mod rng;
 mod timer;
 
-pub use self::{datetime::DateTime, timer::Timer};
+pub use self::{
+    clock::{Clock, ManualClock, SystemClock},
+    datetime::DateTime,
+    rng::{RngSource, RngStream, SEED_ENV_VAR},
+    timer::Timer,
+};
@@ -0,0 +1,99 @@
+use std::env;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// Environment variable overriding the seed of a bot session
+pub const SEED_ENV_VAR: &str = "NEUZ_SEED";
+
+/// Independent streams of randomness, one per consumer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RngStream {
+    Movement,
+    Farming,
+    Shout,
+}
+
+/// Seeds all randomness of a bot session, so a session can be replayed exactly.
+///
+/// Every consumer gets its own stream, so draws of one don't shift the values of another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RngSource {
+    seed: u64,
+}
+
+impl RngSource {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    pub fn random() -> Self {
+        Self::new(rand::thread_rng().gen())
+    }
+
+    /// Seed from `NEUZ_SEED` if set, then from `configured`, otherwise pick a random seed.
+    pub fn from_env_or(configured: Option<&str>) -> Result<Self, String> {
+        let (value, source) = match env::var(SEED_ENV_VAR) {
+            Ok(value) => (Some(value), SEED_ENV_VAR),
+            Err(_) => (configured.map(String::from), "configured seed"),
+        };
+        match value {
+            Some(value) => value
+                .trim()
+                .parse::<u64>()
+                .map(Self::new)
+                .map_err(|err| format!("invalid {}: {}", source, err)),
+            None => Ok(Self::random()),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn stream(&self, stream: RngStream) -> StdRng {
+        // Spread stream ids over all bits so nearby seeds don't share streams
+        let id = (stream as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        StdRng::seed_from_u64(self.seed ^ id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use crate::utils::{RngSource, RngStream, SEED_ENV_VAR};
+
+    #[test]
+    fn test_rng_streams() {
+        let draw = |source: RngSource, stream| {
+            let mut rng = source.stream(stream);
+            (0..8).map(|_| rng.gen::<u32>()).collect::<Vec<_>>()
+        };
+        let source = RngSource::new(42);
+
+        assert_eq!(
+            draw(source, RngStream::Movement),
+            draw(RngSource::new(42), RngStream::Movement)
+        );
+        assert_ne!(
+            draw(source, RngStream::Movement),
+            draw(source, RngStream::Farming)
+        );
+        assert_ne!(
+            draw(source, RngStream::Movement),
+            draw(RngSource::new(43), RngStream::Movement)
+        );
+    }
+
+    #[test]
+    fn test_configured_seed() {
+        if std::env::var(SEED_ENV_VAR).is_ok() {
+            return;
+        }
+        assert_eq!(
+            RngSource::from_env_or(Some("18446744073709551615")),
+            Ok(RngSource::new(u64::MAX))
+        );
+        assert!(RngSource::from_env_or(Some("1.5e19")).is_err());
+    }
+}
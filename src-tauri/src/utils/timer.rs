@@ -1,25 +1,38 @@
 use std::{
     cell::RefCell,
+    sync::Arc,
     time::{Duration, Instant},
 };
 
 //use slog::Logger;
+use super::{Clock, SystemClock};
 
 pub struct Timer {
     label: String,
     start: Instant,
     is_silenced: RefCell<bool>,
+    clock: Arc<dyn Clock>,
 }
 
 impl Timer {
     pub fn start_new<S>(label: S) -> Timer
+    where
+        S: ToString,
+    {
+        Self::start_with_clock(label, SystemClock::shared())
+    }
+
+    /// Start a timer measuring the time of `clock`.
+    #[allow(dead_code)]
+    pub fn start_with_clock<S>(label: S, clock: Arc<dyn Clock>) -> Timer
     where
         S: ToString,
     {
         Timer {
             label: label.to_string(),
-            start: Instant::now(),
+            start: clock.now(),
             is_silenced: RefCell::new(false),
+            clock,
         }
     }
 
@@ -45,7 +58,7 @@ impl Timer {
     }
 
     pub fn elapsed(&self) -> Duration {
-        self.start.elapsed()
+        self.clock.elapsed(self.start)
     }
 
     pub fn report(&self) {
@@ -15,6 +15,8 @@ import FarmingConfig from "./components/behaviors/FarmingConfig"
 import SupportConfig from "./components/behaviors/SupportConfig"
 import ShoutConfig from "./components/behaviors/ShoutConfig"
 import Footer from "./components/Footer"
+import DryRunLog from "./components/DryRunLog"
+import Preview from "./components/Preview"
 import { FrontendInfoModel } from "./models/FrontendInfo"
 import { invoke } from "@tauri-apps/api"
 import { useStopWatch } from "./components/utils/StopWatch"
@@ -58,6 +60,18 @@ const MissionControl = ({ className, lastVersion, currentVersion }: Props) => {
         emit('bot_config_c2s', newConfig)
     }
 
+    const setDryRunToggle = () => {
+        if (!config) return
+        const newConfig = { ...config, dry_run: !config.dry_run }
+        emit('bot_config_c2s', newConfig)
+    }
+
+    const setPreviewToggle = () => {
+        if (!config) return
+        const newConfig = { ...config, show_preview: !config.show_preview }
+        emit('bot_config_c2s', newConfig)
+    }
+
     const handleToggle = () => {
         if (!config) return
         const newConfig = { ...config }
@@ -105,10 +119,15 @@ const MissionControl = ({ className, lastVersion, currentVersion }: Props) => {
                 )}
 
 
+                {config?.show_preview && <Preview />}
+                {config?.dry_run && <DryRunLog />}
+
                 <div className="footer">
                     <div className="sm footerCombinedBtn">
                         {!isNil(config?.mode) && <div className="btn sm" onClick={toogleLightMode}>Compact</div>}
                         {!isNil(config?.mode) && <div className="btn sm" onClick={() => {invoke("focus_client")}}>Focus</div>}
+                        {!isNil(config?.mode) && <div className="btn sm" onClick={setPreviewToggle}>{config?.show_preview ? 'Hide view' : 'View'}</div>}
+                        {!isNil(config?.mode) && <div className="btn sm" onClick={setDryRunToggle}>{config?.dry_run ? 'Live input' : 'Dry run'}</div>}
                     </div>
                     {!isNil(config?.mode) && <div className="btn" onClick={setRunningToggle}>{config?.is_running ? 'Disengage' : 'Engage'}</div>}
                 </div>
@@ -312,7 +331,7 @@ export default styled(MissionControl)`
         display: flex;
         flex-direction: row;
         & .btn {
-            width: 50%;
+            width: 25%;
         }
     }
 `
@@ -0,0 +1,120 @@
+import { useEffect, useState } from 'react'
+import styled from 'styled-components'
+import { emit, listen } from '@tauri-apps/api/event'
+
+import { BoundsModel, CalibrationResultModel, CalibrationTargetModel } from '../models/FrontendInfo'
+
+type Props = {
+    className?: string,
+    /** Frame the regions are marked on */
+    frameId: number,
+    /** Regions marked on the preview, in frame coordinates */
+    regions: BoundsModel[],
+    onClear: () => void,
+}
+
+const targets: { [key in CalibrationTargetModel]: string } = {
+    PassiveMob: 'Passive mob name',
+    AggressiveMob: 'Aggressive mob name',
+    VioletMob: 'Violet mob name',
+    HpBar: 'HP bar',
+    MpBar: 'MP bar',
+    FpBar: 'FP bar',
+}
+
+const swatch = (color: number[]) => `rgb(${color.join(',')})`
+
+const Calibration = ({ className, frameId, regions, onClear }: Props) => {
+    const [target, setTarget] = useState<CalibrationTargetModel>('PassiveMob')
+    const [result, setResult] = useState<CalibrationResultModel | null>(null)
+
+    useEffect(() => {
+        const unlisten = listen('calibration_s2c', event => {
+            setResult(event.payload as CalibrationResultModel)
+        })
+        return () => { unlisten.then(f => f()) }
+    }, [])
+
+    const calibrate = () => {
+        setResult(null)
+        emit('calibrate_c2s', { frame_id: frameId, target, regions })
+    }
+
+    const apply = () => {
+        if (!result) return
+        emit('calibration_apply_c2s', result)
+        setResult(null)
+        onClear()
+    }
+
+    return (
+        <div className={className}>
+            <div className="row">
+                <select value={target} onChange={e => setTarget(e.target.value as CalibrationTargetModel)}>
+                    {Object.entries(targets).map(([value, label]) => <option key={value} value={value}>{label}</option>)}
+                </select>
+                <span>{regions.length} region(s)</span>
+                <div className="btn sm" onClick={onClear}>Clear</div>
+                {regions.length > 0 && <div className="btn sm" onClick={calibrate}>Calibrate</div>}
+            </div>
+            {regions.length === 0 && <div className="hint">Drag over the preview to mark {targets[target].toLowerCase()}s</div>}
+            {result && (
+                <>
+                    <div className="row">
+                        {result.colors.map((color, index) => <span key={index} className="swatch" style={{ background: swatch(color) }} title={color.join(', ')} />)}
+                        <span>tolerance {result.tolerance}</span>
+                    </div>
+                    <div>Matches {result.matches} px, {result.false_positives} px elsewhere</div>
+                    <div className="row">
+                        {result.histogram.map(({ color, count }, index) => <span key={index} className="swatch small" style={{ background: swatch(color) }} title={`${color.join(', ')}: ${count} px`} />)}
+                    </div>
+                    <div className="row">
+                        <div className="btn sm" onClick={apply}>Apply to {targets[result.target].toLowerCase()}</div>
+                    </div>
+                </>
+            )}
+        </div>
+    )
+}
+
+export default styled(Calibration)`
+    display: flex;
+    flex-direction: column;
+    gap: .25rem;
+    padding-top: .25rem;
+    border-top: 1px solid hsl(0,0%,20%);
+
+    & .row {
+        display: flex;
+        align-items: center;
+        gap: .5rem;
+    }
+
+    & .btn {
+        cursor: pointer;
+        user-select: none;
+        padding: 0 .5rem;
+        border-radius: 0.25rem;
+        border: 1px solid hsl(0,0%,30%);
+
+        &:hover {
+            background: hsla(203, 100%, 45%, .5);
+        }
+    }
+
+    & .hint {
+        color: hsl(0,0%,60%);
+    }
+
+    & .swatch {
+        display: inline-block;
+        width: 1.25rem;
+        height: 1.25rem;
+        border: 1px solid hsl(0,0%,40%);
+
+        &.small {
+            width: .75rem;
+            height: .75rem;
+        }
+    }
+`
@@ -0,0 +1,71 @@
+import { useEffect, useState } from 'react'
+import styled from 'styled-components'
+import { listen } from '@tauri-apps/api/event'
+
+import { SkippedInputModel } from '../models/FrontendInfo'
+
+// Only the latest entries are kept
+const MAX_ENTRIES = 100
+
+type Props = {
+    className?: string,
+}
+
+const DryRunLog = ({ className }: Props) => {
+    const [skipped, setSkipped] = useState<SkippedInputModel[]>([])
+
+    useEffect(() => {
+        const unlisten = listen('bot_skipped_input_s2c', event => {
+            const payload = event.payload as SkippedInputModel[]
+            setSkipped(previous => [...payload.reverse(), ...previous].slice(0, MAX_ENTRIES))
+        })
+        return () => { unlisten.then(f => f()) }
+    }, [])
+
+    return (
+        <div className={className}>
+            <div className="title">Dry run: would have done</div>
+            {skipped.length === 0 && <div className="empty">Nothing yet</div>}
+            {skipped.map(({ timestamp, action }, index) => (
+                <div className="entry" key={`${timestamp}-${index}`}>
+                    <span className="time">{new Date(timestamp).toLocaleTimeString()}</span>
+                    <span>{action}</span>
+                </div>
+            ))}
+        </div>
+    )
+}
+
+export default styled(DryRunLog)`
+    margin: 0 auto;
+    display: flex;
+    flex-direction: column;
+    padding: .5rem 1rem;
+    gap: .25rem;
+    color: white;
+    background: hsla(203, 100%, 0%, .75);
+    backdrop-filter: blur(.5rem);
+    border-radius: 0.25rem;
+    border: 1px solid hsl(0,0%,10%);
+    width: calc(min(500px, max(250px, 50vw)));
+    max-height: 10rem;
+    overflow-y: auto;
+    font-size: .9rem;
+
+    & .title {
+        font-weight: bold;
+    }
+
+    & .empty {
+        color: hsl(0,0%,60%);
+    }
+
+    & .entry {
+        display: flex;
+        gap: .5rem;
+    }
+
+    & .time {
+        color: hsl(0,0%,60%);
+    }
+`
@@ -0,0 +1,140 @@
+import { MouseEvent, useEffect, useState } from 'react'
+import styled from 'styled-components'
+import { emit, listen } from '@tauri-apps/api/event'
+
+import { BoundsModel, PreviewFrameModel } from '../models/FrontendInfo'
+import Calibration from './Calibration'
+
+type Props = {
+    className?: string,
+}
+
+const mobColors = {
+    Passive: 'hsl(120, 100%, 43%)',
+    Aggressive: 'hsl(0, 100%, 58%)',
+    Violet: 'hsl(280, 100%, 62%)',
+}
+
+const Preview = ({ className }: Props) => {
+    const [latest, setLatest] = useState<PreviewFrameModel | null>(null)
+    // Preview kept still while regions are marked on it
+    const [held, setHeld] = useState<PreviewFrameModel | null>(null)
+    // Regions marked for the color calibration, in frame coordinates
+    const [regions, setRegions] = useState<BoundsModel[]>([])
+    const [dragStart, setDragStart] = useState<{ x: number, y: number } | null>(null)
+
+    useEffect(() => {
+        const unlisten = listen('bot_preview_s2c', event => {
+            setLatest(event.payload as PreviewFrameModel)
+        })
+        return () => { unlisten.then(f => f()) }
+    }, [])
+
+    const preview = held ?? latest
+    if (!preview) {
+        return <div className={className}><div className="empty">Waiting for frames...</div></div>
+    }
+
+    // Position of the mouse on the captured frame
+    const framePosition = (event: MouseEvent<HTMLDivElement>) => {
+        const rect = event.currentTarget.getBoundingClientRect()
+        return {
+            x: Math.round((event.clientX - rect.left) * preview.frame_width / rect.width),
+            y: Math.round((event.clientY - rect.top) * preview.frame_height / rect.height),
+        }
+    }
+
+    const startDrag = (event: MouseEvent<HTMLDivElement>) => {
+        if (!held) {
+            setHeld(preview)
+            emit('preview_hold_c2s', preview.frame_id)
+        }
+        setDragStart(framePosition(event))
+    }
+
+    const clearRegions = () => {
+        setRegions([])
+        setHeld(null)
+    }
+
+    const endDrag = (event: MouseEvent<HTMLDivElement>) => {
+        if (!dragStart) return
+        const end = framePosition(event)
+        const region = {
+            x: Math.max(0, Math.min(dragStart.x, end.x)),
+            y: Math.max(0, Math.min(dragStart.y, end.y)),
+            w: Math.abs(end.x - dragStart.x),
+            h: Math.abs(end.y - dragStart.y),
+        }
+        if (region.w > 0 && region.h > 0) {
+            setRegions([...regions, region])
+        }
+        setDragStart(null)
+    }
+
+    const toPreview = ({ x, y, w, h }: BoundsModel) => ({
+        left: `${x * 100 / preview.frame_width}%`,
+        top: `${y * 100 / preview.frame_height}%`,
+        width: `${w * 100 / preview.frame_width}%`,
+        height: `${h * 100 / preview.frame_height}%`,
+    })
+
+    return (
+        <div className={className}>
+            <div className="frame" onMouseDown={startDrag} onMouseUp={endDrag} onMouseLeave={() => setDragStart(null)}>
+                <img alt="" draggable={false} src={preview.image} style={{ aspectRatio: `${preview.width} / ${preview.height}` }} />
+                {regions.map((region, index) => <div key={index} className="region" style={toPreview(region)} />)}
+            </div>
+            <div className="details">
+                {preview.state && <div>State: {preview.state}</div>}
+                <div>Mobs: {preview.mobs.length}</div>
+                {preview.mobs.map(({ mob_type, name, level }, index) => (
+                    <div key={index} style={{ color: mobColors[mob_type] }}>
+                        {name ?? mob_type}{level !== undefined && ` Lv.${level}`}
+                    </div>
+                ))}
+            </div>
+            <Calibration frameId={preview.frame_id} regions={regions} onClear={clearRegions} />
+        </div>
+    )
+}
+
+export default styled(Preview)`
+    margin: 0 auto;
+    display: flex;
+    flex-direction: column;
+    gap: .25rem;
+    padding: .5rem;
+    color: white;
+    background: hsla(203, 100%, 0%, .75);
+    backdrop-filter: blur(.5rem);
+    border-radius: 0.25rem;
+    border: 1px solid hsl(0,0%,10%);
+    width: calc(min(500px, max(250px, 50vw)));
+    font-size: .9rem;
+
+    & .frame {
+        position: relative;
+        cursor: crosshair;
+    }
+
+    & img {
+        display: block;
+        width: 100%;
+    }
+
+    & .region {
+        position: absolute;
+        border: 1px dashed hsl(50, 100%, 60%);
+        pointer-events: none;
+    }
+
+    & .details {
+        max-height: 6rem;
+        overflow-y: auto;
+    }
+
+    & .empty {
+        color: hsl(0,0%,60%);
+    }
+`
@@ -62,7 +62,15 @@ const SlotModal = ({className, isShowing, hide, index, slot, onChange, barIndex,
                         <ConfigTableRow
                             layout="v"
                             label={<ConfigLabel name="Threshold" helpText="Limit trigger value." />}
-                            item={<NumericInput unit='%' value={slot.slot_threshold} onChange={value => {slot.slot_threshold = value;onChange(barIndex, index, slot)}} />}
+                            item={<NumericInput unit={slot.slot_threshold_absolute ? 'pts' : '%'} min={0} value={slot.slot_threshold} onChange={value => {slot.slot_threshold = value;onChange(barIndex, index, slot)}} />}
+                        />
+                    }
+
+                    {thresholdSlotTypes.includes(slot.slot_type) &&
+                        <ConfigTableRow
+                            layout="v"
+                            label={<ConfigLabel name="Absolute threshold" helpText="Compare the threshold with the exact value read from the stat tray instead of the percentage. The slot is not used while the value can't be read." />}
+                            item={<BooleanSlider value={slot.slot_threshold_absolute ?? false} onChange={value => {slot.slot_threshold_absolute = value;onChange(barIndex, index, slot)}} />}
                         />
                     }
 
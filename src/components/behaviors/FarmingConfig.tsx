@@ -8,15 +8,19 @@ import NumericInput from '../config/NumericInput'
 import ColorSelector from '../config/ColorSelector'
 
 import SlotBar from '../SlotBar'
-import { createSlotBars, FarmingConfigModel } from '../../models/BotConfig'
+import { colorMatchModes, ColorMatchMode, createSlotBars, FarmingConfigModel, MobPolicy, mobPolicies } from '../../models/BotConfig'
 import { useEffect, useRef, useState } from 'react'
-import { FrontendInfoModel } from '../../models/FrontendInfo'
+import { FrontendInfoModel, MobPolicyStatsModel } from '../../models/FrontendInfo'
 import Modal from '../Modal'
 import useModal from '../utils/UseModal'
 import YesNoModal from '../YesNoModal'
 import { MsFormat, StopWatchValues } from '../utils/StopWatch'
 import { DefaultValuesChecker } from '../utils/DefaultValuesChecker'
 import TimeInput from '../config/TimeInput'
+import TextInput from '../config/TextInput'
+import StringList from '../config/StringList'
+import Select from 'react-select'
+import { emit } from '@tauri-apps/api/event'
 
 type Props = {
     className?: string,
@@ -41,6 +45,9 @@ const FarmingConfig = ({ className, info, config, onChange, botStopWatch, botSta
 
     const selectedMobType = useRef(0)
 
+    const mobPolicyOptions = mobPolicies.map((policy) => ({ value: policy, label: policy }))
+    const colorMatchModeOptions = colorMatchModes.map((mode) => ({ value: mode, label: mode }))
+
     const defaultValues = {
         'passive_mobs_colors': [234, 234, 149],
         'passive_tolerence': 5,
@@ -127,6 +134,16 @@ const FarmingConfig = ({ className, info, config, onChange, botStopWatch, botSta
                         label={<ConfigLabel name="Tolerence" helpText="Monster's name color tolerence. Edit these values if you are sure what you are doing." />}
                         item={<NumericInput min={0} max={255} unit="#" value={(selectedMobType.current === 0)? config.passive_tolerence : config.aggressive_tolerence} onChange={value => onChange?.((selectedMobType.current === 0)? { ...config, passive_tolerence: value } : { ...config, aggressive_tolerence: value })} />}
                     />
+                    <ConfigTableRow
+                        layout="v"
+                        label={<ConfigLabel name="Color matching" helpText="How name colors are compared for all monsters. Rgb: each channel within the tolerence. Hsv: hue and saturation within the tolerence, whatever the brightness (night maps, fog). Lab: perceptual distance within the tolerence. Calibrate again after changing it." />}
+                        item={<div style={{width:'100%', color: 'black'}}><Select options={colorMatchModeOptions} onChange={value => onChange?.({ ...config, color_match_mode: value?.value as ColorMatchMode })} value={colorMatchModeOptions.find(x => x.value === (config.color_match_mode ?? 'Rgb'))}/></div>}
+                    />
+                    <ConfigTableRow
+                        layout="v"
+                        label={<ConfigLabel name="Color matching of this type" helpText="Compare the names of this type of monsters in another way than the others. Empty to use the one above." />}
+                        item={<div style={{width:'100%', color: 'black'}}><Select isClearable options={colorMatchModeOptions} onChange={value => onChange?.((selectedMobType.current === 0)? { ...config, passive_color_match_mode: value?.value as ColorMatchMode | undefined } : { ...config, aggressive_color_match_mode: value?.value as ColorMatchMode | undefined })} value={colorMatchModeOptions.find(x => x.value === ((selectedMobType.current === 0)? config.passive_color_match_mode : config.aggressive_color_match_mode)) ?? null}/></div>}
+                    />
                     <ConfigTableRow
                     layout="v"
                         label={<ConfigLabel name="" helpText="" />}
@@ -183,12 +200,67 @@ const FarmingConfig = ({ className, info, config, onChange, botStopWatch, botSta
                             label={<ConfigLabel name="Max mobs name width" helpText="" />}
                             item={<NumericInput unit='px' value={config.max_mobs_name_width} onChange={value => onChange({...config, max_mobs_name_width: value})} />}
                         />
+                        <ConfigTableRow
+                            layout="v"
+                            label={<ConfigLabel name="Mobs name gap" helpText="Largest horizontal and vertical gap between two letters of a mob name. Raise it if names are detected in pieces, lower it if names of mobs close to each other are merged. Default : 12 and 4" />}
+                            item={<div><NumericInput unit='px' value={config.mobs_name_gap_x ?? 12} onChange={value => onChange({...config, mobs_name_gap_x: value})} /><NumericInput unit='px' value={config.mobs_name_gap_y ?? 4} onChange={value => onChange({...config, mobs_name_gap_y: value})} /></div>}
+                        />
+                        <ConfigTableRow
+                            layout="v"
+                            label={<ConfigLabel name="Debug frames interval" helpText="Save an annotated screenshot with the detected mobs every this many frames into the profile's debug_frames folder. Value of 0 only saves on demand." />}
+                            item={<NumericInput min={0} unit='#' value={config.debug_frames_interval} onChange={value => onChange({...config, debug_frames_interval: value})} />}
+                        />
+                        <ConfigTableRow
+                            layout="v"
+                            label={<ConfigLabel name="Save debug frame" helpText="Save an annotated screenshot of the next frame" />}
+                            item={<button onClick={() => emit('debug_frame_c2s')}>📷</button>}
+                        />
                     </> }
+                    <ConfigTableRow
+                        layout="v"
+                        label={<ConfigLabel name="Allowed mobs" helpText="Only attack mobs with one of these names. Leave empty to attack every mob. Mobs whose name can't be read are attacked anyway. Names are read with a font which hasn't been checked against the client yet, so this may have no effect, see the log. One name per line, without level." />}
+                        item={<StringList messages={config.mobs_name_allow_list ?? []} onChange={value => onChange?.({ ...config, mobs_name_allow_list: value })} />}
+                    />
+                    <ConfigTableRow
+                        layout="v"
+                        label={<ConfigLabel name="Ignored mobs" helpText="Never attack mobs with one of these names. Mobs whose name can't be read are attacked anyway, see the log. One name per line, without level." />}
+                        item={<StringList messages={config.mobs_name_deny_list ?? []} onChange={value => onChange?.({ ...config, mobs_name_deny_list: value })} />}
+                    />
+                    <ConfigTableRow
+                        layout="v"
+                        label={<ConfigLabel name="Passive mobs" helpText="What to do with passive mobs: attack them, ignore them, or move away when one comes close. Default : Attack" />}
+                        item={<div style={{width:'100%', color: 'black'}}><Select options={mobPolicyOptions} onChange={value => onChange?.({ ...config, passive_mobs_policy: value?.value as MobPolicy })} value={mobPolicyOptions.find(x => x.value === (config.passive_mobs_policy ?? 'Attack'))}/></div>}
+                    />
+                    <ConfigTableRow
+                        layout="v"
+                        label={<ConfigLabel name="Aggressive mobs" helpText="What to do with aggressive mobs: attack them, ignore them, or move away when one comes close. Default : Attack" />}
+                        item={<div style={{width:'100%', color: 'black'}}><Select options={mobPolicyOptions} onChange={value => onChange?.({ ...config, aggressive_mobs_policy: value?.value as MobPolicy })} value={mobPolicyOptions.find(x => x.value === (config.aggressive_mobs_policy ?? 'Attack'))}/></div>}
+                    />
+                    <ConfigTableRow
+                        layout="v"
+                        label={<ConfigLabel name="Violet mobs" helpText="What to do with violet mobs: attack them, ignore them, or move away when one comes close. Default : Ignore" />}
+                        item={<div style={{width:'100%', color: 'black'}}><Select options={mobPolicyOptions} onChange={value => onChange?.({ ...config, violet_mobs_policy: value?.value as MobPolicy })} value={mobPolicyOptions.find(x => x.value === (config.violet_mobs_policy ?? 'Ignore'))}/></div>}
+                    />
                     <ConfigTableRow
                         layout="v"
                         label={<ConfigLabel name="Circle pattern duration" helpText="The bot will try to move in a circle pattern to find targets. Value of 0 will stay in place. Lower the value to increase circle size. Default : 30" />}
                         item={<NumericInput value={config.circle_pattern_rotation_duration} onChange={value => onChange?.({ ...config, circle_pattern_rotation_duration: value })} />}
                     />
+                    <ConfigTableRow
+                        layout="v"
+                        label={<ConfigLabel name="Search with minimap" helpText="Walk towards the closest mob dot of the minimap when no mob is in sight. Needs the minimap open and a HUD layout which includes the minimap: the default layout doesn't, so this does nothing until the profile has its own layout." />}
+                        item={<BooleanSlider value={config.use_minimap ?? false} onChange={value => onChange?.({ ...config, use_minimap: value })} />}
+                    />
+                    <ConfigTableRow
+                        layout="v"
+                        label={<ConfigLabel name="Leash radius" helpText="Walk back towards where farming started when the character gets further away than this, estimated from the movements. Value of 0 lets it wander freely. The heading is only corrected with the minimap, which needs a HUD layout which includes the minimap." />}
+                        item={<NumericInput min={0} unit='m' value={config.leash_radius} onChange={value => onChange?.({ ...config, leash_radius: value })} />}
+                    />
+                    <ConfigTableRow
+                        layout="v"
+                        label={<ConfigLabel name="Patrol route" helpText="Walked instead of the circle pattern when no mob is found, resuming after each fight. One waypoint per line: movements like 'W 2000, Left 400, Jump, Wait 500' (keys W S A D Left Right held for ms) or a position '@ 30 -20' (meters east and south of the start point, estimated from the keys pressed and mobs clicked), optionally followed by '| 5000' to wait there for mobs for 5s." />}
+                        item={<StringList messages={config.patrol_route ?? []} onChange={value => onChange?.({ ...config, patrol_route: value })} />}
+                    />
 
                     <ConfigTableRow
                         layout="v"
@@ -225,6 +297,21 @@ const FarmingConfig = ({ className, info, config, onChange, botStopWatch, botSta
                         label={<ConfigLabel name="No mob time out" helpText="After this time character will disconnect if no mobs were found" />}
                         item={<TimeInput value={config.mobs_timeout} onChange={value => onChange({...config, mobs_timeout: value})} />}
                     />
+                    <ConfigTableRow
+                        layout="v"
+                        label={<ConfigLabel name="Fly away on time out" helpText="Instead of disconnecting, mount the board or broom of the flying slot, fly to another area and farm there. (Un)mounting is assumed after a few seconds, unless the HUD layout of the profile includes the flight gauge." />}
+                        item={<BooleanSlider value={config.fly_on_mobs_timeout ?? false} onChange={value => onChange?.({ ...config, fly_on_mobs_timeout: value })} />}
+                    />
+                    {config.fly_on_mobs_timeout && <>
+                        <ConfigTableRow
+                            label={<ConfigLabel name="Flight destination" helpText="A waypoint like the ones of the patrol route, relative to where the flight starts: '@ 200 -100' or 'W 8000, Left 300'. Leave empty to fly straight ahead." />}
+                            item={<TextInput value={config.flight_destination} onChange={value => onChange({...config, flight_destination: value})} />}
+                        />
+                        <ConfigTableRow
+                            label={<ConfigLabel name="Flight duration" helpText="How long to fly straight ahead without a destination" />}
+                            item={<TimeInput value={config.flight_duration} onChange={value => onChange({...config, flight_duration: value})} />}
+                        />
+                    </>}
                     <ConfigTableRow
                         label={<ConfigLabel name="Disconnect" helpText="Enable will automatically disconnect, otherwise we'll wait for revive" />}
                         item={<BooleanSlider value={config.on_death_disconnect ?? true} onChange={value => onChange?.({ ...config, on_death_disconnect: value })} />}
@@ -258,6 +345,20 @@ const FarmingConfig = ({ className, info, config, onChange, botStopWatch, botSta
                     <div className="row">
                         <div>Botting time: {botStopWatch?.toString()}</div>
                     </div>
+                    {([['Passive', info?.passive_mobs], ['Aggressive', info?.aggressive_mobs], ['Violet', info?.violet_mobs]] as [string, MobPolicyStatsModel | undefined][]).map(([name, stats]) => (
+                        <div className="row" key={name}>
+                            <div>{name} mobs: {stats?.seen ?? 0} in view | {stats?.attacked ?? 0} attacked
+                            | {stats?.ignored ?? 0} ignored | {stats?.avoided ?? 0} avoided</div>
+                        </div>
+                    ))}
+                    <div className="row">
+                        <div>Got unstuck: {info?.stuck_count ?? 0} times</div>
+                    </div>
+                    <div className="row">
+                        <div>Frames: capture {info?.pipeline?.capture.fps.toFixed(1) ?? 0} fps ({info?.pipeline?.capture.latency_ms.toFixed(0) ?? 0} ms)
+                        | analysis {info?.pipeline?.analysis.fps.toFixed(1) ?? 0} fps ({info?.pipeline?.analysis.latency_ms.toFixed(0) ?? 0} ms)
+                        | behavior {info?.pipeline?.behavior.fps.toFixed(1) ?? 0} fps ({info?.pipeline?.behavior.latency_ms.toFixed(0) ?? 0} ms)</div>
+                    </div>
                     <div className="row">
                         <div>Last search time: {searchMobStopWatch}</div>
                     </div>
@@ -73,6 +73,11 @@ const SupportConfig = ({ className, info, config, onChange, botStopWatch, botSta
                         label={<ConfigLabel name="Max main distance" helpText="Max distance from main character" />}
                         item={<NumericInput value={config.max_main_distance} onChange={value => onChange?.({ ...config, max_main_distance: value })} />}
                     />
+                    <ConfigTableRow
+                        layout="v"
+                        label={<ConfigLabel name="Search with minimap" helpText="Walk towards the closest party member dot of the minimap when the main character is out of sight. Needs the minimap open and a HUD layout which includes the minimap: the default layout doesn't, so this does nothing until the profile has its own layout." />}
+                        item={<BooleanSlider value={config.use_minimap ?? false} onChange={value => onChange?.({ ...config, use_minimap: value })} />}
+                    />
                     <ConfigTableRow
                         layout="v"
                         label={<ConfigLabel name="Is it in a party?" helpText="If enabled will buff itself and party leader" />}
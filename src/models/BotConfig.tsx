@@ -13,7 +13,7 @@ export const slotTypes = ["Unused", "Food", "Pill", "HealSkill","AOEHealSkill",
                                                     "AOEAttackSkill", "BuffSkill", "RezSkill", "Flying", "PartySkill"] as const;
 export const thresholdSlotTypes = ["Food", "Pill","HealSkill","MpRestorer", "FpRestorer", 'AOEHealSkill'];
 export const cooldownSlotTypes = ["Food", "Pill", "HealSkill", "AOEHealSkill", "AttackSkill","AOEAttackSkill", "BuffSkill", "MpRestorer", "FpRestorer", "PickupPet","PartySkill"];
-export const farmingSlotsBlacklist = ["Flying", "RezSkill","AOEHealSkill"]
+export const farmingSlotsBlacklist = ["RezSkill","AOEHealSkill"]
 //if we are leveling someone, the support role might want to pick up
 export const supportSlotsBlacklist = ["AttackSkill","AOEAttackSkill"]
 
@@ -69,6 +69,7 @@ export type SlotModel = {
     slot_type: SlotType,
     slot_cooldown?: number,
     slot_threshold?: number,
+    slot_threshold_absolute?: boolean,
     slot_enabled: boolean,
 }
 export type SlotBarHolder = {
@@ -80,6 +81,12 @@ export type SlotBars = FixedArray<SlotBarHolder, 9>
 
 export type ModeModel = "Farming" | "Support" | "AutoShout"
 
+export type MobPolicy = "Attack" | "Ignore" | "Avoid"
+export const mobPolicies: MobPolicy[] = ["Attack", "Ignore", "Avoid"]
+
+export type ColorMatchMode = "Rgb" | "Hsv" | "Lab"
+export const colorMatchModes: ColorMatchMode[] = ["Rgb", "Hsv", "Lab"]
+
 export type FarmingConfigModel = Partial<{
     [key: string]: any;
     on_demand_pet: boolean,
@@ -90,8 +97,15 @@ export type FarmingConfigModel = Partial<{
 
     passive_mobs_colors: number[];
     passive_tolerence: number;
+    passive_mobs_policy: MobPolicy;
+    passive_color_match_mode: ColorMatchMode;
     aggressive_mobs_colors: number[];
     aggressive_tolerence: number;
+    aggressive_mobs_policy: MobPolicy;
+    aggressive_color_match_mode: ColorMatchMode;
+    violet_mobs_policy: MobPolicy;
+    violet_color_match_mode: ColorMatchMode;
+    color_match_mode: ColorMatchMode;
 
     is_stop_fighting: boolean;
     prevent_already_attacked: boolean;
@@ -102,6 +116,10 @@ export type FarmingConfigModel = Partial<{
 
     min_mobs_name_width: number,
     max_mobs_name_width: number,
+    mobs_name_gap_x: number,
+    mobs_name_gap_y: number,
+    mobs_name_allow_list: string[],
+    mobs_name_deny_list: string[],
 
     min_hp_attack: number,
     on_death_disconnect: boolean,
@@ -112,6 +130,14 @@ export type FarmingConfigModel = Partial<{
     on_afk_disconnect: boolean,
     afk_timeout: number,
     afk_ready_to_disconnect: boolean,
+
+    debug_frames_interval: number,
+    use_minimap: boolean,
+    leash_radius: number,
+    patrol_route: string[],
+    fly_on_mobs_timeout: boolean,
+    flight_destination: string,
+    flight_duration: number,
 }>
 
 export type SupportConfigModel = Partial<{
@@ -126,6 +152,7 @@ export type SupportConfigModel = Partial<{
     afk_timeout: number,
     afk_ready_to_disconnect: boolean,
     max_main_distance: number,
+    use_minimap: boolean,
 }>
 
 export type ShoutConfigModel = Partial<{
@@ -145,6 +172,13 @@ export type BotConfigModel = {
     farming_config: FarmingConfigModel,
     support_config: SupportConfigModel,
     shout_config: ShoutConfigModel,
+    /** Decimal string, since seeds go beyond the integers a number holds exactly */
+    rng_seed?: string,
+    dry_run?: boolean,
+    show_preview?: boolean,
+    hp_bar_colors?: number[][],
+    mp_bar_colors?: number[][],
+    fp_bar_colors?: number[][],
 }
 
 export type AnyConfig = FarmingConfigModel | SupportConfigModel | ShoutConfigModel
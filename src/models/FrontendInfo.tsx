@@ -1,3 +1,61 @@
+export type MobPolicyStatsModel = {
+    seen: number,
+    attacked: number,
+    ignored: number,
+    avoided: number,
+}
+
+export type StageStatsModel = {
+    fps: number,
+    latency_ms: number,
+}
+
+export type PipelineStatsModel = {
+    capture: StageStatsModel,
+    analysis: StageStatsModel,
+    behavior: StageStatsModel,
+}
+
+export type SkippedInputModel = {
+    /** Unix time in milliseconds */
+    timestamp: number,
+    action: string,
+}
+
+export type PreviewMobModel = {
+    mob_type: 'Passive' | 'Aggressive' | 'Violet',
+    name?: string,
+    level?: number,
+}
+
+export type PreviewFrameModel = {
+    /** Id of the analyzed frame, to calibrate on it */
+    frame_id: number,
+    /** JPEG data URL */
+    image: string,
+    width: number,
+    height: number,
+    frame_width: number,
+    frame_height: number,
+    state?: string,
+    mobs: PreviewMobModel[],
+}
+
+export type BoundsModel = { x: number, y: number, w: number, h: number }
+
+export type CalibrationTargetModel = 'PassiveMob' | 'AggressiveMob' | 'VioletMob' | 'HpBar' | 'MpBar' | 'FpBar'
+
+export type CalibrationResultModel = {
+    target: CalibrationTargetModel,
+    colors: number[][],
+    tolerance: number,
+    /** Pixels of the marked regions matching the colors */
+    matches: number,
+    /** Pixels elsewhere in the frame matching the colors */
+    false_positives: number,
+    histogram: { color: number[], count: number }[],
+}
+
 export type FrontendInfoModel = {
     /* enemy_bounds?: Bounds[],
     active_enemy_bounds?: Bounds, */
@@ -9,5 +67,10 @@ export type FrontendInfoModel = {
     last_search_duration: number,
     kill_min_avg: number,
     kill_hour_avg: number,
+    passive_mobs?: MobPolicyStatsModel,
+    aggressive_mobs?: MobPolicyStatsModel,
+    violet_mobs?: MobPolicyStatsModel,
+    pipeline?: PipelineStatsModel,
+    stuck_count?: number,
     version: number[],
 }